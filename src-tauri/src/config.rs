@@ -1,8 +1,12 @@
 use crate::runtime::config::{Config, SettingsForm};
+use crate::runtime::lan::interfaces::InterfaceRule;
+use crate::runtime::lan::protocol::DEFAULT_TCP_PORT;
+use crate::runtime::policy::SyncDirection;
 use crate::runtime::StartOptions;
 use crate::state::AppState;
 use serde::Serialize;
-use tauri::{AppHandle, Emitter, State};
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_store::StoreExt;
 
 #[derive(Serialize, Clone)]
@@ -39,6 +43,14 @@ pub async fn get_initial_state(
     })
 }
 
+/// Directory holding the `config.json` store; the runtime keeps its other
+/// persisted state (e.g. the LAN trust store) alongside it.
+pub fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {}", e))
+}
+
 #[tauri::command]
 pub fn get_hostname() -> Result<String, String> {
     hostname::get()
@@ -129,6 +141,52 @@ pub async fn save_settings(
         _ => "minimize_to_tray".to_string(),
     };
 
+    let lan_discovery = match form.lan_discovery.as_str() {
+        "broadcast" | "mdns" | "both" => form.lan_discovery.clone(),
+        _ => "broadcast".to_string(),
+    };
+
+    let lan_static_peers = form
+        .lan_static_peers
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(normalize_static_peer)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let lan_interfaces = form
+        .lan_interfaces
+        .iter()
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            InterfaceRule::parse(entry)
+                .map(|_| entry.to_string())
+                .map_err(|e| format!("Invalid LAN interface filter {}: {}", entry, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let lan_discovery_port = u16::try_from(form.lan_discovery_port)
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| format!("Invalid LAN discovery port: {}", form.lan_discovery_port))?;
+    let lan_tcp_port = u16::try_from(form.lan_tcp_port)
+        .map_err(|_| format!("Invalid LAN TCP port: {}", form.lan_tcp_port))?;
+
+    // Devices left at the default need no entry.
+    let peer_sync_directions = form
+        .peer_sync_directions
+        .into_iter()
+        .filter(|(_, direction)| *direction != SyncDirection::Both)
+        .collect();
+
+    let store = app
+        .store("config.json")
+        .map_err(|e| format!("Store error: {}", e))?;
+    // The global sync direction is switched from the main window; keep the
+    // stored one rather than what an open settings window last loaded.
+    let sync_direction = stored_config(&store).sync_direction;
+
     let updated_config = Config {
         server_url,
         token: token_opt,
@@ -145,17 +203,25 @@ pub async fn save_settings(
         },
         lan_device_name: form.lan_device_name.trim().to_string(),
         close_behavior,
+        lan_group_name: form.lan_group_name.trim().to_string(),
+        lan_group_secret: form.lan_group_secret.clone(),
+        lan_static_peers,
+        lan_discovery,
+        lan_interfaces,
+        lan_discovery_port,
+        lan_tcp_port,
+        lan_relay: form.lan_relay,
+        sync_direction,
+        peer_sync_directions,
     };
 
-    let store = app
-        .store("config.json")
-        .map_err(|e| format!("Store error: {}", e))?;
     store.set("config", serde_json::json!(updated_config));
     store.save().map_err(|e| format!("Save error: {}", e))?;
 
     // Reload runtime
     let options = StartOptions {
         config: updated_config.clone(),
+        data_dir: app_data_dir(&app)?,
     };
 
     state
@@ -169,3 +235,78 @@ pub async fn save_settings(
 
     Ok(())
 }
+
+/// Switch which way clipboard content flows with all devices, without
+/// restarting sync, and save the choice. Muting everything is what pause
+/// is for.
+#[tauri::command]
+pub async fn set_sync_direction(
+    app: AppHandle,
+    direction: SyncDirection,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    if direction == SyncDirection::Muted {
+        return Err("Use pause to stop syncing".to_string());
+    }
+    state
+        .handle
+        .set_sync_direction(direction)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let store = app
+        .store("config.json")
+        .map_err(|e| format!("Store error: {}", e))?;
+    let mut config = stored_config(&store);
+    config.sync_direction = direction;
+    store.set("config", serde_json::json!(config));
+    store.save().map_err(|e| format!("Save error: {}", e))?;
+
+    app.emit("config-changed", SettingsForm::from(&config))
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// The config in `store`, or the default one when there is none or it
+/// cannot be read.
+fn stored_config<R: tauri::Runtime>(store: &tauri_plugin_store::Store<R>) -> Config {
+    store
+        .get("config")
+        .and_then(|val| serde_json::from_value(val).ok())
+        .unwrap_or_default()
+}
+
+/// Check a static LAN peer entry and add the default TCP port when the
+/// entry is a bare host.
+fn normalize_static_peer(entry: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid LAN peer address: {}", entry);
+    let valid_port = |port: &str| port.parse::<u16>().is_ok_and(|p| p != 0);
+
+    // IPv6 literal, optionally scoped: `[fe80::1%eth0]:52742` or `[::1]`.
+    if let Some(rest) = entry.strip_prefix('[') {
+        let (host, tail) = rest.split_once(']').ok_or_else(invalid)?;
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        return match tail.strip_prefix(':') {
+            Some(port) if valid_port(port) => Ok(entry.to_string()),
+            None if tail.is_empty() => Ok(format!("[{}]:{}", host, DEFAULT_TCP_PORT)),
+            _ => Err(invalid()),
+        };
+    }
+    // A bare IPv6 literal has no room for a port.
+    if entry.matches(':').count() > 1 {
+        return Ok(format!("[{}]:{}", entry, DEFAULT_TCP_PORT));
+    }
+
+    match entry.rsplit_once(':') {
+        Some((host, port)) => {
+            if host.is_empty() || !valid_port(port) {
+                return Err(invalid());
+            }
+            Ok(entry.to_string())
+        }
+        None => Ok(format!("{}:{}", entry, DEFAULT_TCP_PORT)),
+    }
+}
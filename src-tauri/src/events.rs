@@ -84,6 +84,34 @@ pub fn listen_events(
                 RuntimeEvent::LanPeersChanged(peers_json) => {
                     let _ = app_handle.emit("lan-peers-changed", peers_json);
                 }
+                RuntimeEvent::LanTrustChanged(trusted_json) => {
+                    let _ = app_handle.emit("lan-trust-changed", trusted_json);
+                }
+                RuntimeEvent::LanLinkStats(stats_json) => {
+                    let _ = app_handle.emit("lan-link-stats", stats_json);
+                }
+                RuntimeEvent::Compression(stats) => {
+                    log::debug!(
+                        "{} compressed {} {} -> {} bytes ({:.1}x, {})",
+                        stats.transport,
+                        stats.content_type,
+                        stats.original_bytes,
+                        stats.compressed_bytes,
+                        stats.ratio(),
+                        stats.codec
+                    );
+                    let _ = app_handle.emit("compression-stats", stats);
+                }
+                RuntimeEvent::Delivery(report) => {
+                    let _ = app_handle.emit("clipboard-delivery", report);
+                    let _ = app_handle.emit(
+                        "status-update",
+                        format!(
+                            "Delivered to {}/{} devices ({})",
+                            report.delivered, report.targets, report.content_type
+                        ),
+                    );
+                }
             }
         }
     });
@@ -7,6 +7,7 @@
 mod app_log;
 mod config;
 mod events;
+mod peers;
 mod runtime;
 mod state;
 mod syseffects;
@@ -20,7 +21,12 @@ use tauri_plugin_store::StoreExt;
 use tokio::runtime::Runtime;
 
 use app_log::{frontend_log, open_log_folder, setup_logger};
-use config::{get_hostname, get_initial_state, save_settings};
+use config::{app_data_dir, get_hostname, get_initial_state, save_settings, set_sync_direction};
+use peers::{
+    lan_approve_peer, lan_begin_pairing, lan_block_peer, lan_link_stats, lan_list_devices,
+    lan_pair_peer, lan_revoke_peer, send_clipboard_to,
+};
+use runtime::backend::ArboardBackend;
 use runtime::config::Config;
 use runtime::{spawn_runtime, StartOptions};
 use state::AppState;
@@ -62,7 +68,7 @@ fn main() -> Result<()> {
     let runtime = Arc::new(Runtime::new()?);
 
     // Spawn core runtime
-    let (handle, event_rx) = spawn_runtime(&runtime);
+    let (handle, event_rx) = spawn_runtime(&runtime, Arc::new(ArboardBackend));
 
     let app_state = AppState::new(runtime.clone(), handle.clone());
 
@@ -94,9 +100,11 @@ fn main() -> Result<()> {
             let runtime_clone = state.runtime.clone();
 
             let config_clone = config.clone();
+            let data_dir = app_data_dir(&app_handle)?;
             runtime_clone.spawn(async move {
                 let options = StartOptions {
                     config: config_clone,
+                    data_dir,
                 };
                 // Allow some time for UI to potentially be ready
                 tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -123,8 +131,17 @@ fn main() -> Result<()> {
             open_log_folder,
             frontend_log,
             save_settings,
+            set_sync_direction,
             apply_window_effects,
-            get_hostname
+            get_hostname,
+            lan_begin_pairing,
+            lan_pair_peer,
+            lan_approve_peer,
+            lan_revoke_peer,
+            lan_block_peer,
+            lan_list_devices,
+            lan_link_stats,
+            send_clipboard_to
         ])
         .on_window_event(|window, event| {
             if let WindowEvent::CloseRequested { api, .. } = event {
@@ -0,0 +1,89 @@
+use crate::state::AppState;
+use tauri::State;
+
+/// Generate a pairing PIN for this device and return it for display.
+#[tauri::command]
+pub async fn lan_begin_pairing(state: State<'_, AppState>) -> Result<String, String> {
+    state
+        .handle
+        .lan_begin_pairing()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Pair with a connected LAN peer using the PIN shown on that peer.
+#[tauri::command]
+pub async fn lan_pair_peer(
+    state: State<'_, AppState>,
+    device_id: String,
+    pin: String,
+) -> Result<(), String> {
+    state
+        .handle
+        .lan_pair_peer(device_id, pin)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Trust a connected, pending LAN peer without a PIN.
+#[tauri::command]
+pub async fn lan_approve_peer(state: State<'_, AppState>, device_id: String) -> Result<(), String> {
+    state
+        .handle
+        .lan_approve_peer(device_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Remove a LAN peer from the trusted or blocked list.
+#[tauri::command]
+pub async fn lan_revoke_peer(state: State<'_, AppState>, device_id: String) -> Result<(), String> {
+    state
+        .handle
+        .lan_revoke_peer(device_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Block a LAN peer: it is no longer dialed and its sessions are refused.
+#[tauri::command]
+pub async fn lan_block_peer(state: State<'_, AppState>, device_id: String) -> Result<(), String> {
+    state
+        .handle
+        .lan_block_peer(device_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Discovered, trusted and blocked LAN peers as a JSON snapshot.
+#[tauri::command]
+pub async fn lan_list_devices(state: State<'_, AppState>) -> Result<String, String> {
+    state
+        .handle
+        .lan_list_devices()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Round-trip time, traffic and uptime of LAN peer sessions as JSON.
+#[tauri::command]
+pub async fn lan_link_stats(state: State<'_, AppState>) -> Result<String, String> {
+    state
+        .handle
+        .lan_link_stats()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Send the current clipboard content to one device only.
+#[tauri::command]
+pub async fn send_clipboard_to(
+    state: State<'_, AppState>,
+    device_id: String,
+) -> Result<(), String> {
+    state
+        .handle
+        .send_clipboard_to(device_id)
+        .await
+        .map_err(|e| e.to_string())
+}
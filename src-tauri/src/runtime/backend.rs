@@ -0,0 +1,186 @@
+//! Where clipboard content is read from and written to.
+//!
+//! The monitor and the setter only talk to a [`ClipboardBackend`]: it reads
+//! the current content, replaces it, and says how to learn about changes
+//! (see [`watch`](super::watch)). The app runs on [`ArboardBackend`], the
+//! system clipboard. Tests use an in-memory backend, so the whole sync loop
+//! runs without a display.
+
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+};
+
+use anyhow::{anyhow, Result};
+use arboard::{Clipboard, ImageData as ClipboardImage};
+
+use super::watch::{self, ChangeSource};
+
+/// Clipboard content in a form we can sync.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClipboardContent {
+    Text(String),
+    /// HTML and its plain-text alternative (empty when there is none).
+    Html {
+        html: String,
+        text: String,
+    },
+    /// RGBA pixels, width and height.
+    Image(Vec<u8>, u32, u32),
+}
+
+impl ClipboardContent {
+    /// A hash of the content, to tell whether the clipboard changed
+    /// without keeping a copy of it.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Access to one clipboard. All methods may block and are called on
+/// blocking threads.
+pub trait ClipboardBackend: Send + Sync + 'static {
+    /// The current content, or `None` when it holds nothing we can sync.
+    fn read(&self) -> Result<Option<ClipboardContent>>;
+
+    /// Replace the content.
+    fn write(&self, content: ClipboardContent) -> Result<()>;
+
+    /// A source signalling changes of this clipboard, or an error when it
+    /// can only be polled.
+    fn change_source(&self) -> Result<Box<dyn ChangeSource>>;
+}
+
+/// The system clipboard through `arboard`, watched with the platform's
+/// change source.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArboardBackend;
+
+impl ClipboardBackend for ArboardBackend {
+    fn read(&self) -> Result<Option<ClipboardContent>> {
+        let mut cb = Clipboard::new().map_err(|e| anyhow!("Clipboard init error: {e}"))?;
+        // HTML first: what offers HTML usually offers plain text too.
+        if let Ok(html) = cb.get().html() {
+            let text = cb.get_text().unwrap_or_default();
+            return Ok(Some(ClipboardContent::Html { html, text }));
+        }
+        if let Ok(text) = cb.get_text() {
+            return Ok(Some(ClipboardContent::Text(text)));
+        }
+        Ok(cb.get_image().ok().map(|image| {
+            ClipboardContent::Image(
+                image.bytes.into_owned(),
+                image.width as u32,
+                image.height as u32,
+            )
+        }))
+    }
+
+    fn write(&self, content: ClipboardContent) -> Result<()> {
+        let mut cb = Clipboard::new().map_err(|e| anyhow!("Clipboard init error: {e}"))?;
+        match content {
+            ClipboardContent::Text(text) => cb
+                .set_text(text)
+                .map_err(|e| anyhow!("Clipboard set_text error: {e}")),
+            ClipboardContent::Html { html, text } => {
+                let alt_text = Some(text).filter(|text| !text.is_empty());
+                cb.set_html(html, alt_text)
+                    .map_err(|e| anyhow!("Clipboard set_html error: {e}"))
+            }
+            ClipboardContent::Image(bytes, width, height) => cb
+                .set_image(ClipboardImage {
+                    width: width as usize,
+                    height: height as usize,
+                    bytes: Cow::Owned(bytes),
+                })
+                .map_err(|e| anyhow!("Clipboard set_image error: {e}")),
+        }
+    }
+
+    fn change_source(&self) -> Result<Box<dyn ChangeSource>> {
+        watch::platform_source()
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryBackend;
+
+#[cfg(test)]
+mod memory {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use parking_lot::{Condvar, Mutex};
+
+    use super::{ClipboardBackend, ClipboardContent};
+    use crate::runtime::watch::ChangeSource;
+
+    /// A clipboard held in memory. Clones share the content; every write
+    /// wakes the change sources handed out.
+    #[derive(Debug, Clone, Default)]
+    pub struct MemoryBackend {
+        inner: Arc<Shared>,
+    }
+
+    #[derive(Debug, Default)]
+    struct Shared {
+        slot: Mutex<Slot>,
+        changed: Condvar,
+    }
+
+    #[derive(Debug, Default)]
+    struct Slot {
+        /// Bumped by every write.
+        generation: u64,
+        content: Option<ClipboardContent>,
+    }
+
+    impl MemoryBackend {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    impl ClipboardBackend for MemoryBackend {
+        fn read(&self) -> Result<Option<ClipboardContent>> {
+            Ok(self.inner.slot.lock().content.clone())
+        }
+
+        fn write(&self, content: ClipboardContent) -> Result<()> {
+            let mut slot = self.inner.slot.lock();
+            slot.generation += 1;
+            slot.content = Some(content);
+            self.inner.changed.notify_all();
+            Ok(())
+        }
+
+        fn change_source(&self) -> Result<Box<dyn ChangeSource>> {
+            Ok(Box::new(MemoryChanges {
+                seen: self.inner.slot.lock().generation,
+                inner: self.inner.clone(),
+            }))
+        }
+    }
+
+    struct MemoryChanges {
+        inner: Arc<Shared>,
+        seen: u64,
+    }
+
+    impl ChangeSource for MemoryChanges {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn wait(&mut self) -> Result<()> {
+            let mut slot = self.inner.slot.lock();
+            while slot.generation == self.seen {
+                self.inner.changed.wait(&mut slot);
+            }
+            self.seen = slot.generation;
+            Ok(())
+        }
+    }
+}
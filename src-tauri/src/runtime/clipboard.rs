@@ -1,6 +1,4 @@
 use std::{
-    borrow::Cow,
-    hash::{Hash, Hasher},
     io::Cursor,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -10,10 +8,9 @@ use std::{
 };
 
 use anyhow::Result;
-use arboard::{Clipboard, ImageData as ClipboardImage};
-use base64::Engine;
 use image::{DynamicImage, ImageFormat, RgbaImage};
 use log::Level;
+use parking_lot::Mutex as ParkingMutex;
 use tokio::{
     sync::{broadcast, mpsc},
     task,
@@ -21,26 +18,136 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
+use super::backend::{ClipboardBackend, ClipboardContent};
+use super::clock::HybridClock;
+use super::delivery::{DeliveryReceipt, DeliveryStatus, DeliveryTracker};
+use super::policy::SyncPolicy;
+use super::watch;
 use super::{RuntimeEvent, RuntimeLogEvent};
 use crate::runtime::messages::{
-    ClipboardBroadcastPayload, ClipboardUpdate, ClipboardUpdatePayload, CONTENT_TYPE_IMAGE_PNG,
-    CONTENT_TYPE_TEXT, MSG_TYPE_CLIPBOARD_UPDATE,
+    ClipboardItem, HtmlContent, CONTENT_TYPE_HTML, CONTENT_TYPE_IMAGE_PNG, CONTENT_TYPE_TEXT,
 };
 
 const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
 const MIN_BROADCAST_INTERVAL: Duration = Duration::from_millis(400);
 
+/// The device's clock and its newest clipboard item, copied here or
+/// applied from elsewhere. Shared by the monitor, the setter and the
+/// transports, which send the newest item as soon as a connection is up,
+/// tally delivery receipts for items copied here and check the sync
+/// policy for each item.
+#[derive(Debug, Clone)]
+pub struct ClipboardState {
+    device_id: String,
+    pub clock: HybridClock,
+    pub deliveries: DeliveryTracker,
+    pub policy: SyncPolicy,
+    latest: Arc<ParkingMutex<Option<ClipboardItem>>>,
+    /// [`ClipboardContent::fingerprint`] of what the clipboard was last
+    /// seen or made to hold.
+    content: Arc<ParkingMutex<Option<u64>>>,
+}
+
+impl ClipboardState {
+    pub fn new(device_id: String, policy: SyncPolicy) -> Self {
+        Self {
+            device_id,
+            clock: HybridClock::new(),
+            deliveries: DeliveryTracker::default(),
+            policy,
+            latest: Arc::default(),
+            content: Arc::default(),
+        }
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// `true` for items copied on this device.
+    pub fn is_own(&self, item: &ClipboardItem) -> bool {
+        item.sender_device_id == self.device_id
+    }
+
+    /// The newest item so far, if any.
+    pub fn latest(&self) -> Option<ClipboardItem> {
+        self.latest.lock().clone()
+    }
+
+    /// Keep `item` as the newest one unless a newer
+    /// [`version`](ClipboardItem::version) is already held. Returns whether
+    /// it was kept.
+    pub fn record(&self, item: &ClipboardItem) -> bool {
+        let mut latest = self.latest.lock();
+        if latest
+            .as_ref()
+            .is_some_and(|held| item.version() <= held.version())
+        {
+            return false;
+        }
+        *latest = Some(item.clone());
+        true
+    }
+
+    /// Note the content with `fingerprint` as what the clipboard holds now:
+    /// read by the monitor, or written by the setter. Returns `false` when
+    /// it was noted already, so the monitor sends neither the same copy
+    /// twice nor an item it just applied.
+    pub fn note_content(&self, fingerprint: u64) -> bool {
+        self.content.lock().replace(fingerprint) != Some(fingerprint)
+    }
+
+    /// The newest content as a new item of this device addressed to
+    /// `target` alone. It gets a fresh stamp and id, so the target applies
+    /// it even if it already saw the content, and it does not replace the
+    /// newest item synced to everyone.
+    pub fn addressed_to(&self, target: &str) -> Option<ClipboardItem> {
+        let latest = self.latest()?;
+        let mut item = ClipboardItem::new(
+            latest.content_type,
+            latest.data,
+            self.device_id.clone(),
+            self.clock.now(),
+        );
+        item.target = Some(target.to_string());
+        Some(item)
+    }
+}
+
+/// Send what is copied on this device as items of `state`. The clipboard
+/// is read from `backend` whenever its change source reports a change, or
+/// every [`MONITOR_INTERVAL`] when it has none.
 pub async fn start_clipboard_monitor(
-    tx: broadcast::Sender<ClipboardUpdate>,
+    tx: broadcast::Sender<ClipboardItem>,
     disable_flag: Arc<AtomicBool>,
-    device_id: String,
+    state: ClipboardState,
+    backend: Arc<dyn ClipboardBackend>,
     max_image_kb: u64,
     events: mpsc::Sender<RuntimeEvent>,
     cancel: CancellationToken,
 ) {
-    let mut last_text = String::new();
-    let mut last_rgba_hash: Option<u64> = None;
+    let device_id = state.device_id().to_string();
     let mut last_send_time = Instant::now();
+    let mut changes = match backend.change_source() {
+        Ok(source) => {
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Debug,
+                    format!("剪贴板变化通知: {}", source.name()),
+                )))
+                .await;
+            Some(watch::spawn(source))
+        }
+        Err(e) => {
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Debug,
+                    format!("剪贴板变化通知不可用，改为轮询: {:#}", e),
+                )))
+                .await;
+            None
+        }
+    };
 
     loop {
         if cancel.is_cancelled() {
@@ -57,118 +164,167 @@ pub async fn start_clipboard_monitor(
             continue;
         }
 
-        let clipboard_state = task::spawn_blocking(|| read_clipboard_content()).await;
+        // Hold the read back until a send is allowed again, so a change
+        // that comes in right after one is still picked up: it may not be
+        // signalled a second time.
+        let since_send = last_send_time.elapsed();
+        if since_send < MIN_BROADCAST_INTERVAL
+            && tokio::select! {
+                _ = cancel.cancelled() => true,
+                _ = sleep(MIN_BROADCAST_INTERVAL - since_send) => false,
+            }
+        {
+            break;
+        }
 
-        if let Ok(Ok(content)) = clipboard_state {
+        let reader = backend.clone();
+        let clipboard_state = task::spawn_blocking(move || reader.read()).await;
+
+        if let Ok(Ok(Some(content))) = clipboard_state {
             let now = Instant::now();
-            if now.duration_since(last_send_time) >= MIN_BROADCAST_INTERVAL {
+            let empty = match &content {
+                ClipboardContent::Text(text) => text.is_empty(),
+                ClipboardContent::Html { html, .. } => html.is_empty(),
+                ClipboardContent::Image(..) => false,
+            };
+            // 与上次读到或刚由写入任务写入的内容相同则不是新的复制
+            if !empty && state.note_content(content.fingerprint()) {
                 match content {
                     ClipboardContent::Text(text) => {
-                        if !text.is_empty() && text != last_text {
-                            last_text = text.clone();
-                            last_send_time = now;
+                        last_send_time = now;
 
-                            let _ = events
-                                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
-                                    Level::Info,
-                                    format!("检测到文本剪贴板更新 len={}", text.len()),
-                                )))
-                                .await;
+                        let _ = events
+                            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                Level::Info,
+                                format!("检测到文本剪贴板更新 len={}", text.len()),
+                            )))
+                            .await;
 
-                            let _ = events
-                                .send(RuntimeEvent::ClipboardSent {
-                                    content_type: CONTENT_TYPE_TEXT.to_string(),
-                                })
-                                .await;
+                        let _ = events
+                            .send(RuntimeEvent::ClipboardSent {
+                                content_type: CONTENT_TYPE_TEXT.to_string(),
+                            })
+                            .await;
 
-                            let update = ClipboardUpdate {
-                                msg_type: MSG_TYPE_CLIPBOARD_UPDATE.to_string(),
-                                payload: ClipboardUpdatePayload {
-                                    content_type: CONTENT_TYPE_TEXT.to_string(),
-                                    data: text,
-                                    sender_device_id: device_id.clone(),
-                                    timestamp: current_timestamp(),
-                                },
-                            };
-                            let _ = tx.send(update);
-                        }
+                        let update = ClipboardItem::new(
+                            CONTENT_TYPE_TEXT.to_string(),
+                            text.into_bytes().into(),
+                            device_id.clone(),
+                            state.clock.now(),
+                        );
+                        state.record(&update);
+                        let _ = tx.send(update);
+                    }
+                    ClipboardContent::Html { html, text } => {
+                        last_send_time = now;
+
+                        let _ = events
+                            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                Level::Info,
+                                format!(
+                                    "检测到富文本剪贴板更新 html_len={} text_len={}",
+                                    html.len(),
+                                    text.len()
+                                ),
+                            )))
+                            .await;
+
+                        let _ = events
+                            .send(RuntimeEvent::ClipboardSent {
+                                content_type: CONTENT_TYPE_HTML.to_string(),
+                            })
+                            .await;
+
+                        let update = ClipboardItem::new(
+                            CONTENT_TYPE_HTML.to_string(),
+                            HtmlContent { html, text }.encode().into(),
+                            device_id.clone(),
+                            state.clock.now(),
+                        );
+                        state.record(&update);
+                        let _ = tx.send(update);
                     }
                     ClipboardContent::Image(bytes, width, height) => {
-                        // 先对原始 RGBA 数据进行哈希，只有变化时才进行 PNG 编码
-                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                        width.hash(&mut hasher);
-                        height.hash(&mut hasher);
-                        bytes.hash(&mut hasher);
-                        let rgba_hash = hasher.finish();
-
-                        if Some(rgba_hash) != last_rgba_hash {
-                            if let Ok(encoded) = encode_png(bytes, width, height) {
-                                if (encoded.len() as u64) > max_image_kb * 1024 {
-                                    let _ = events
-                                        .send(RuntimeEvent::Log(RuntimeLogEvent::new(
-                                            Level::Warn,
-                                            format!(
-                                                "跳过过大的图片 size={} limit={}KB",
-                                                encoded.len(),
-                                                max_image_kb
-                                            ),
-                                        )))
-                                        .await;
-                                } else {
-                                    last_rgba_hash = Some(rgba_hash);
-                                    last_send_time = now;
-
-                                    let _ = events
-                                        .send(RuntimeEvent::Log(RuntimeLogEvent::new(
-                                            Level::Info,
-                                            format!(
-                                                "检测到图片剪贴板更新 size={} bytes",
-                                                encoded.len()
-                                            ),
-                                        )))
-                                        .await;
-
-                                    let _ = events
-                                        .send(RuntimeEvent::ClipboardSent {
-                                            content_type: CONTENT_TYPE_IMAGE_PNG.to_string(),
-                                        })
-                                        .await;
-
-                                    let b64 =
-                                        base64::engine::general_purpose::STANDARD.encode(&encoded);
-
-                                    let update = ClipboardUpdate {
-                                        msg_type: MSG_TYPE_CLIPBOARD_UPDATE.to_string(),
-                                        payload: ClipboardUpdatePayload {
-                                            content_type: CONTENT_TYPE_IMAGE_PNG.to_string(),
-                                            data: b64,
-                                            sender_device_id: device_id.clone(),
-                                            timestamp: current_timestamp(),
-                                        },
-                                    };
-                                    let _ = tx.send(update);
-                                }
+                        // 原始 RGBA 数据变化时才进行 PNG 编码
+                        if let Ok(encoded) = encode_png(bytes, width, height) {
+                            if (encoded.len() as u64) > max_image_kb * 1024 {
+                                let _ = events
+                                    .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                        Level::Warn,
+                                        format!(
+                                            "跳过过大的图片 size={} limit={}KB",
+                                            encoded.len(),
+                                            max_image_kb
+                                        ),
+                                    )))
+                                    .await;
+                            } else {
+                                last_send_time = now;
+
+                                let _ = events
+                                    .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                        Level::Info,
+                                        format!(
+                                            "检测到图片剪贴板更新 size={} bytes",
+                                            encoded.len()
+                                        ),
+                                    )))
+                                    .await;
+
+                                let _ = events
+                                    .send(RuntimeEvent::ClipboardSent {
+                                        content_type: CONTENT_TYPE_IMAGE_PNG.to_string(),
+                                    })
+                                    .await;
+
+                                let update = ClipboardItem::new(
+                                    CONTENT_TYPE_IMAGE_PNG.to_string(),
+                                    encoded.into(),
+                                    device_id.clone(),
+                                    state.clock.now(),
+                                );
+                                state.record(&update);
+                                let _ = tx.send(update);
                             }
                         }
                     }
                 }
             }
         } else if let Ok(Err(e)) = clipboard_state {
-            // 避免在无可识别内容时产生噪声日志
-            if e != "剪贴板没有可识别的内容" {
-                let _ = events
-                    .send(RuntimeEvent::Log(RuntimeLogEvent::new(
-                        Level::Error,
-                        format!("读取剪贴板失败: {}", e),
-                    )))
-                    .await;
-            }
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Error,
+                    format!("读取剪贴板失败: {}", e),
+                )))
+                .await;
         }
 
-        if tokio::select! {
-            _ = cancel.cancelled() => true,
-            _ = sleep(MONITOR_INTERVAL) => false,
-        } {
+        let stop = match changes.as_mut() {
+            Some(rx) => tokio::select! {
+                _ = cancel.cancelled() => true,
+                change = rx.recv() => {
+                    match change {
+                        Some(Ok(())) => {}
+                        Some(Err(e)) => {
+                            let _ = events
+                                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                    Level::Warn,
+                                    format!("剪贴板变化通知失败，改为轮询: {:#}", e),
+                                )))
+                                .await;
+                            changes = None;
+                        }
+                        None => changes = None,
+                    }
+                    false
+                }
+            },
+            None => tokio::select! {
+                _ = cancel.cancelled() => true,
+                _ = sleep(MONITOR_INTERVAL) => false,
+            },
+        };
+        if stop {
             break;
         }
     }
@@ -181,51 +337,69 @@ pub async fn start_clipboard_monitor(
         .await;
 }
 
+/// Apply received items to the local clipboard. Each item advances the
+/// clock of `state`, and is only applied when its
+/// [`ClipboardItem::version`] is newer than the newest item in `state`,
+/// whether that was copied here or received. The outcome is reported to
+/// the item's [`receipts`](ClipboardItem::receipts) channel, if any.
 pub async fn start_clipboard_setter(
-    mut rx: mpsc::Receiver<ClipboardBroadcastPayload>,
+    mut rx: mpsc::Receiver<ClipboardItem>,
+    state: ClipboardState,
+    backend: Arc<dyn ClipboardBackend>,
     disable_flag: Arc<AtomicBool>,
     events: mpsc::Sender<RuntimeEvent>,
     cancel: CancellationToken,
 ) {
-    let mut last_text: Option<String> = None;
-    let mut last_text_ts = 0;
-    let mut last_image_data: Option<String> = None;
-    let mut last_image_ts = 0;
     loop {
         tokio::select! {
             _ = cancel.cancelled() => break,
             maybe_payload = rx.recv() => {
                 if let Some(payload) = maybe_payload {
-                    match payload.content_type.as_str() {
+                    state.clock.observe(payload.clock);
+                    let status = match payload.content_type.as_str() {
                         CONTENT_TYPE_TEXT => {
-                            if payload.timestamp <= last_text_ts || Some(&payload.data) == last_text.as_ref() {
-                                continue;
-                            }
-                            last_text_ts = payload.timestamp;
-                            last_text = Some(payload.data.clone());
-                            if let Err(err) = set_text(&payload.data, disable_flag.clone()).await {
+                            if !state.record(&payload) {
+                                DeliveryStatus::Stale
+                            } else if let Err(err) = set_text(&backend, &state, &String::from_utf8_lossy(&payload.data), disable_flag.clone()).await {
                                 let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Error, format!("设置文本剪贴板失败: {}", err)))).await;
+                                DeliveryStatus::Failed
                             } else {
                                 let _ = events.send(RuntimeEvent::ClipboardReceived { content_type: CONTENT_TYPE_TEXT.to_string() }).await;
                                 let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Info, String::from("已应用来自远端的文本剪贴板")))).await;
+                                DeliveryStatus::Applied
                             }
                         }
-                        CONTENT_TYPE_IMAGE_PNG => {
-                            if payload.timestamp <= last_image_ts || Some(&payload.data) == last_image_data.as_ref() {
-                                continue;
+                        CONTENT_TYPE_HTML => {
+                            if !state.record(&payload) {
+                                DeliveryStatus::Stale
+                            } else if let Err(err) = set_html(&backend, &state, &payload.data, disable_flag.clone()).await {
+                                let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Error, format!("设置富文本剪贴板失败: {}", err)))).await;
+                                DeliveryStatus::Failed
+                            } else {
+                                let _ = events.send(RuntimeEvent::ClipboardReceived { content_type: CONTENT_TYPE_HTML.to_string() }).await;
+                                let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Info, String::from("已应用来自远端的富文本剪贴板")))).await;
+                                DeliveryStatus::Applied
                             }
-                            last_image_ts = payload.timestamp;
-                            last_image_data = Some(payload.data.clone());
-                            if let Err(err) = set_image_from_base64(&payload.data, disable_flag.clone()).await {
+                        }
+                        CONTENT_TYPE_IMAGE_PNG => {
+                            if !state.record(&payload) {
+                                DeliveryStatus::Stale
+                            } else if let Err(err) = set_image_from_png(&backend, &state, &payload.data, disable_flag.clone()).await {
                                 let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Error, format!("设置图片剪贴板失败: {}", err)))).await;
+                                DeliveryStatus::Failed
                             } else {
                                 let _ = events.send(RuntimeEvent::ClipboardReceived { content_type: CONTENT_TYPE_IMAGE_PNG.to_string() }).await;
                                 let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Info, String::from("已应用来自远端的图片剪贴板")))).await;
+                                DeliveryStatus::Applied
                             }
                         }
                         other => {
                             let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Warn, format!("收到未知类型剪贴板: {}", other)))).await;
+                            DeliveryStatus::Rejected
                         }
+                    };
+                    if let Some(receipts) = &payload.receipts {
+                        let _ = receipts.try_send(DeliveryReceipt::new(&payload, &state.device_id, status));
                     }
                 } else {
                     break;
@@ -242,78 +416,65 @@ pub async fn start_clipboard_setter(
         .await;
 }
 
-async fn set_text(text: &str, disable_flag: Arc<AtomicBool>) -> Result<()> {
-    let content = text.to_string();
-    disable_flag.store(true, Ordering::SeqCst);
-    let result = task::spawn_blocking(move || {
-        let mut cb = Clipboard::new().map_err(|e| format!("Clipboard init error: {e}"))?;
-        cb.set_text(content)
-            .map_err(|e| format!("Clipboard set_text error: {e}"))?;
-        Ok::<(), String>(())
-    })
-    .await;
-    disable_flag.store(false, Ordering::SeqCst);
-    match result {
-        Ok(Ok(())) => Ok(()),
-        Ok(Err(err)) => Err(anyhow::anyhow!(err)),
-        Err(join_err) => Err(anyhow::anyhow!("任务 join 出错: {}", join_err)),
-    }
+async fn set_text(
+    backend: &Arc<dyn ClipboardBackend>,
+    state: &ClipboardState,
+    text: &str,
+    disable_flag: Arc<AtomicBool>,
+) -> Result<()> {
+    let content = ClipboardContent::Text(text.to_string());
+    write_content(backend, state, content, disable_flag).await
 }
 
-async fn set_image_from_base64(data: &str, disable_flag: Arc<AtomicBool>) -> Result<()> {
-    let bytes = base64::engine::general_purpose::STANDARD
-        .decode(data)
-        .map_err(|err| anyhow::anyhow!("Base64 解码失败: {}", err))?;
+/// Write both representations of a [`CONTENT_TYPE_HTML`] payload.
+async fn set_html(
+    backend: &Arc<dyn ClipboardBackend>,
+    state: &ClipboardState,
+    data: &[u8],
+    disable_flag: Arc<AtomicBool>,
+) -> Result<()> {
+    let HtmlContent { html, text } = HtmlContent::decode(data)?;
+    let content = ClipboardContent::Html { html, text };
+    write_content(backend, state, content, disable_flag).await
+}
+
+async fn set_image_from_png(
+    backend: &Arc<dyn ClipboardBackend>,
+    state: &ClipboardState,
+    bytes: &[u8],
+    disable_flag: Arc<AtomicBool>,
+) -> Result<()> {
     let image =
-        image::load_from_memory(&bytes).map_err(|err| anyhow::anyhow!("PNG 解码失败: {}", err))?;
+        image::load_from_memory(bytes).map_err(|err| anyhow::anyhow!("PNG 解码失败: {}", err))?;
     let rgba = image.to_rgba8();
     let (w, h) = rgba.dimensions();
+    let content = ClipboardContent::Image(rgba.into_raw(), w, h);
+    write_content(backend, state, content, disable_flag).await
+}
+
+/// Write `content` with the monitor held off, and note it in `state` before
+/// letting the monitor read again, so it does not take our own write for a
+/// copy.
+async fn write_content(
+    backend: &Arc<dyn ClipboardBackend>,
+    state: &ClipboardState,
+    content: ClipboardContent,
+    disable_flag: Arc<AtomicBool>,
+) -> Result<()> {
+    let writer = backend.clone();
+    let fingerprint = content.fingerprint();
     disable_flag.store(true, Ordering::SeqCst);
-    let result = task::spawn_blocking(move || {
-        let mut cb = Clipboard::new().map_err(|e| format!("Clipboard init error: {e}"))?;
-        let data = ClipboardImage {
-            width: w as usize,
-            height: h as usize,
-            bytes: Cow::Owned(rgba.into_raw()),
-        };
-        cb.set_image(data)
-            .map_err(|e| format!("Clipboard set_image error: {e}"))?;
-        Ok::<(), String>(())
-    })
-    .await;
+    let result = task::spawn_blocking(move || writer.write(content)).await;
+    if matches!(result, Ok(Ok(()))) {
+        state.note_content(fingerprint);
+    }
     disable_flag.store(false, Ordering::SeqCst);
     match result {
-        Ok(Ok(())) => Ok(()),
-        Ok(Err(err)) => Err(anyhow::anyhow!(err)),
+        Ok(result) => result,
         Err(join_err) => Err(anyhow::anyhow!("任务 join 出错: {}", join_err)),
     }
 }
 
-fn read_clipboard_content() -> Result<ClipboardContent, String> {
-    let mut cb = Clipboard::new().map_err(|e| format!("Clipboard init error: {e}"))?;
-    if let Ok(text) = cb.get_text() {
-        return Ok(ClipboardContent::Text(text));
-    }
-    if let Ok(image) = cb.get_image() {
-        let width = image.width as u32;
-        let height = image.height as u32;
-        Ok(ClipboardContent::Image(
-            image.bytes.into_owned(),
-            width,
-            height,
-        ))
-    } else {
-        Err("剪贴板没有可识别的内容".into())
-    }
-}
-
-fn current_timestamp() -> u64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis() as u64
-}
-
 fn encode_png(bytes: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>, String> {
     if let Some(rgba) = RgbaImage::from_raw(width, height, bytes) {
         let mut cursor = Cursor::new(Vec::new());
@@ -326,7 +487,131 @@ fn encode_png(bytes: Vec<u8>, width: u32, height: u32) -> Result<Vec<u8>, String
     }
 }
 
-enum ClipboardContent {
-    Text(String),
-    Image(Vec<u8>, u32, u32),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::backend::MemoryBackend;
+
+    const QUIET: Duration = Duration::from_secs(1);
+
+    /// The monitor and the setter running on one in-memory clipboard.
+    struct SyncLoop {
+        backend: MemoryBackend,
+        state: ClipboardState,
+        /// Items received from peers.
+        tx_in: mpsc::Sender<ClipboardItem>,
+        /// Items the monitor sends out.
+        sent: broadcast::Receiver<ClipboardItem>,
+        cancel: CancellationToken,
+    }
+
+    impl SyncLoop {
+        fn spawn() -> Self {
+            let backend = MemoryBackend::new();
+            let state = ClipboardState::new("local".to_string(), SyncPolicy::default());
+            let disable_flag = Arc::new(AtomicBool::new(false));
+            let (events, mut events_rx) = mpsc::channel(64);
+            tokio::spawn(async move { while events_rx.recv().await.is_some() {} });
+            let cancel = CancellationToken::new();
+
+            let (tx_out, sent) = broadcast::channel(8);
+            tokio::spawn(start_clipboard_monitor(
+                tx_out,
+                disable_flag.clone(),
+                state.clone(),
+                Arc::new(backend.clone()),
+                1024,
+                events.clone(),
+                cancel.clone(),
+            ));
+            let (tx_in, rx_in) = mpsc::channel(8);
+            tokio::spawn(start_clipboard_setter(
+                rx_in,
+                state.clone(),
+                Arc::new(backend.clone()),
+                disable_flag,
+                events,
+                cancel.clone(),
+            ));
+            Self {
+                backend,
+                state,
+                tx_in,
+                sent,
+                cancel,
+            }
+        }
+
+        /// A text item from a peer, newer than anything seen so far.
+        fn received(&self, text: &str) -> ClipboardItem {
+            ClipboardItem::new(
+                CONTENT_TYPE_TEXT.to_string(),
+                text.as_bytes().to_vec().into(),
+                "remote".to_string(),
+                self.state.clock.now(),
+            )
+        }
+
+        async fn wait_for(&self, content: &ClipboardContent) {
+            while self.backend.read().unwrap().as_ref() != Some(content) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }
+    }
+
+    impl Drop for SyncLoop {
+        fn drop(&mut self) {
+            self.cancel.cancel();
+        }
+    }
+
+    #[tokio::test]
+    async fn applied_items_are_not_sent_back() {
+        let mut sync = SyncLoop::spawn();
+
+        sync.tx_in.send(sync.received("from a peer")).await.unwrap();
+        sync.wait_for(&ClipboardContent::Text("from a peer".to_string()))
+            .await;
+        assert!(
+            tokio::time::timeout(QUIET, sync.sent.recv()).await.is_err(),
+            "the applied item was sent back"
+        );
+        assert_eq!(sync.state.latest().unwrap().sender_device_id, "remote");
+
+        // A copy made here afterwards still goes out.
+        sync.backend
+            .write(ClipboardContent::Text("copied here".to_string()))
+            .unwrap();
+        let copied = tokio::time::timeout(QUIET, sync.sent.recv())
+            .await
+            .expect("the local copy was not sent")
+            .unwrap();
+        assert_eq!(&*copied.data, b"copied here");
+        assert!(sync.state.is_own(&copied));
+    }
+
+    #[tokio::test]
+    async fn content_applied_before_is_written_again_when_newer() {
+        let mut sync = SyncLoop::spawn();
+        let applied = ClipboardContent::Text("from a peer".to_string());
+
+        sync.tx_in.send(sync.received("from a peer")).await.unwrap();
+        sync.wait_for(&applied).await;
+        sync.backend
+            .write(ClipboardContent::Text("copied here".to_string()))
+            .unwrap();
+        tokio::time::timeout(QUIET, sync.sent.recv())
+            .await
+            .expect("the local copy was not sent")
+            .unwrap();
+
+        // The peer copies the same content again.
+        let (receipts, mut receipts_rx) = mpsc::channel(1);
+        let mut again = sync.received("from a peer");
+        again.receipts = Some(receipts);
+        sync.tx_in.send(again).await.unwrap();
+        let receipt = receipts_rx.recv().await.unwrap();
+        assert_eq!(receipt.status, DeliveryStatus::Applied);
+        assert_eq!(sync.backend.read().unwrap(), Some(applied));
+    }
 }
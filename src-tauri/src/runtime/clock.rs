@@ -0,0 +1,221 @@
+//! Hybrid logical clock ordering clipboard updates across devices.
+//!
+//! Every clipboard item is stamped with an [`Hlc`]: the wall-clock time in
+//! milliseconds plus a logical counter. Receiving an item advances the local
+//! clock past the item's stamp, so whatever is copied after seeing an update
+//! is ordered after it, even on a device whose system clock runs behind.
+//! Items with equal stamps are ordered by the sender's device id, so every
+//! device settles on the same winner.
+//!
+//! On the wire the stamp travels as the existing `timestamp` field plus a
+//! `logical` counter; senders that predate the clock are read with a
+//! counter of 0.
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex as ParkingMutex;
+
+/// A hybrid logical clock stamp. Ordered by wall-clock time, then counter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hlc {
+    /// Milliseconds since the UNIX epoch, as far as the clock has seen.
+    pub wall_ms: u64,
+    /// Events within the same `wall_ms`.
+    pub logical: u32,
+}
+
+impl Hlc {
+    pub fn new(wall_ms: u64, logical: u32) -> Self {
+        Self { wall_ms, logical }
+    }
+
+    /// The stamp for a local event when the system clock reads `now_ms`.
+    pub fn tick(self, now_ms: u64) -> Self {
+        if now_ms > self.wall_ms {
+            Self::new(now_ms, 0)
+        } else {
+            Self::new(self.wall_ms, self.logical.saturating_add(1))
+        }
+    }
+
+    /// The clock after receiving `remote` when the system clock reads
+    /// `now_ms`; later than both this clock and `remote`.
+    pub fn merge(self, remote: Hlc, now_ms: u64) -> Self {
+        let wall_ms = self.wall_ms.max(remote.wall_ms).max(now_ms);
+        let logical = if wall_ms == now_ms && wall_ms > self.wall_ms && wall_ms > remote.wall_ms {
+            0
+        } else if self.wall_ms == remote.wall_ms {
+            self.logical.max(remote.logical).saturating_add(1)
+        } else if wall_ms == self.wall_ms {
+            self.logical.saturating_add(1)
+        } else {
+            remote.logical.saturating_add(1)
+        };
+        Self::new(wall_ms, logical)
+    }
+}
+
+/// The device's clock, shared by the clipboard monitor (which stamps local
+/// copies) and the setter (which observes received items).
+#[derive(Debug, Clone, Default)]
+pub struct HybridClock {
+    last: Arc<ParkingMutex<Hlc>>,
+}
+
+impl HybridClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp a local event.
+    pub fn now(&self) -> Hlc {
+        let mut last = self.last.lock();
+        *last = last.tick(wall_clock_ms());
+        *last
+    }
+
+    /// Advance past a stamp received from another device.
+    pub fn observe(&self, remote: Hlc) {
+        let mut last = self.last.lock();
+        *last = last.merge(remote, wall_clock_ms());
+    }
+}
+
+/// System time in milliseconds since the UNIX epoch.
+pub fn wall_clock_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A device whose system clock is off by `skew_ms`, applying updates
+    /// the way the clipboard setter does.
+    struct Peer {
+        id: &'static str,
+        skew_ms: i64,
+        clock: Hlc,
+        applied: Option<(Hlc, &'static str)>,
+    }
+
+    impl Peer {
+        fn new(id: &'static str, skew_ms: i64) -> Self {
+            Self {
+                id,
+                skew_ms,
+                clock: Hlc::default(),
+                applied: None,
+            }
+        }
+
+        fn wall(&self, true_ms: u64) -> u64 {
+            true_ms.saturating_add_signed(self.skew_ms)
+        }
+
+        fn copy(&mut self, true_ms: u64) -> (Hlc, &'static str) {
+            self.clock = self.clock.tick(self.wall(true_ms));
+            (self.clock, self.id)
+        }
+
+        /// Returns whether the update replaced the applied one.
+        fn receive(&mut self, update: (Hlc, &'static str), true_ms: u64) -> bool {
+            self.clock = self.clock.merge(update.0, self.wall(true_ms));
+            if self.applied.is_some_and(|applied| update <= applied) {
+                return false;
+            }
+            self.applied = Some(update);
+            true
+        }
+    }
+
+    const T0: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn peer_behind_wins_after_seeing_the_update_it_replaces() {
+        let mut ahead = Peer::new("a", 0);
+        let mut behind = Peer::new("b", -120_000);
+        let mut third = Peer::new("c", 0);
+
+        let first = ahead.copy(T0);
+        assert!(behind.receive(first, T0 + 10));
+        assert!(third.receive(first, T0 + 10));
+
+        // One second later in real time, but two minutes earlier on b's
+        // system clock.
+        let second = behind.copy(T0 + 1_000);
+        assert!(second.0 > first.0);
+        assert!(ahead.receive(second, T0 + 1_010));
+        assert!(third.receive(second, T0 + 1_010));
+        assert_eq!(third.applied, Some(second));
+
+        // A late duplicate of the older update does not win again.
+        assert!(!third.receive(first, T0 + 2_000));
+        assert_eq!(third.applied, Some(second));
+    }
+
+    #[test]
+    fn peer_ahead_does_not_block_later_copies_elsewhere() {
+        let mut ahead = Peer::new("a", 300_000);
+        let mut accurate = Peer::new("b", 0);
+        let mut third = Peer::new("c", -60_000);
+
+        let first = ahead.copy(T0);
+        accurate.receive(first, T0 + 5);
+        third.receive(first, T0 + 5);
+
+        let second = accurate.copy(T0 + 500);
+        assert!(third.receive(second, T0 + 505));
+        let third_copy = third.copy(T0 + 900);
+        assert!(ahead.receive(third_copy, T0 + 905));
+        assert!(accurate.receive(third_copy, T0 + 905));
+    }
+
+    #[test]
+    fn stamps_stay_monotonic_when_the_wall_clock_steps_back() {
+        let mut clock = Hlc::default().tick(T0);
+        let before = clock;
+        clock = clock.tick(T0 - 5_000);
+        assert!(clock > before);
+        assert_eq!(clock, Hlc::new(T0, 1));
+        assert_eq!(clock.tick(T0 + 1), Hlc::new(T0 + 1, 0));
+    }
+
+    #[test]
+    fn merge_is_later_than_both_inputs() {
+        let local = Hlc::new(T0, 3);
+        for remote in [Hlc::new(T0, 7), Hlc::new(T0 + 10, 2), Hlc::new(T0 - 10, 9)] {
+            for now in [T0 - 100, T0, T0 + 5, T0 + 100] {
+                let merged = local.merge(remote, now);
+                assert!(
+                    merged > local && merged > remote,
+                    "{:?} {:?} {}",
+                    local,
+                    remote,
+                    now
+                );
+                assert!(merged.wall_ms >= now);
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_equal_stamps_break_ties_by_device_id() {
+        let stamp = Hlc::new(T0, 0);
+        let mut x = Peer::new("x", 0);
+        let mut y = Peer::new("y", 0);
+        // Both see the two updates in opposite orders and agree on "b".
+        x.receive((stamp, "a"), T0);
+        x.receive((stamp, "b"), T0);
+        y.receive((stamp, "b"), T0);
+        y.receive((stamp, "a"), T0);
+        assert_eq!(x.applied, Some((stamp, "b")));
+        assert_eq!(y.applied, x.applied);
+    }
+}
@@ -0,0 +1,203 @@
+//! Optional payload compression for clipboard frames.
+//!
+//! Both transports negotiate a codec before using it: LAN peers through
+//! `Capabilities::compression` in the handshake, the WebSocket server
+//! through the `compression` list in the authentication exchange. Text
+//! payloads larger than [`COMPRESSION_THRESHOLD`] are then compressed and
+//! the codec is named in the message envelope, so a receiver that never
+//! announced support never sees a compressed payload. Images are already
+//! compressed (PNG) and are always sent as-is.
+
+use std::io::Read;
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde::Serialize;
+
+use crate::runtime::config::Config;
+use crate::runtime::messages::ClipboardItem;
+
+/// Codec name for zstd, as announced and as flagged on messages.
+pub const CODEC_ZSTD: &str = "zstd";
+
+/// Payloads with at most this many bytes are never compressed.
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+/// zstd level: fast, and still shrinks logs and JSON several times over.
+const ZSTD_LEVEL: i32 = 3;
+
+/// Largest payload accepted after decompression: the biggest clipboard
+/// item the monitor will ever emit.
+const MAX_DECOMPRESSED_SIZE: u64 = Config::MAX_IMAGE_KB * 1024;
+
+/// Codecs this build can encode and decode, in order of preference.
+pub fn supported_codecs() -> Vec<String> {
+    vec![CODEC_ZSTD.to_string()]
+}
+
+/// Our preferred codec among those the other side announced.
+pub fn negotiate(offered: &[String]) -> Option<&'static str> {
+    [CODEC_ZSTD]
+        .into_iter()
+        .find(|codec| offered.iter().any(|o| o == codec))
+}
+
+/// A payload compressed with a negotiated codec.
+#[derive(Debug, Clone)]
+pub struct Compressed {
+    pub codec: &'static str,
+    pub data: Vec<u8>,
+}
+
+impl Compressed {
+    /// `data` for string wire formats, which always carry compressed
+    /// payloads as base64.
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.data)
+    }
+}
+
+/// Compress `item` with `codec` if it is a large enough text payload and
+/// compression actually makes it smaller.
+pub fn compress_item(codec: Option<&'static str>, item: &ClipboardItem) -> Option<Compressed> {
+    let codec = codec?;
+    if !ClipboardItem::is_text_type(&item.content_type) || item.data.len() <= COMPRESSION_THRESHOLD
+    {
+        return None;
+    }
+    let data = match codec {
+        CODEC_ZSTD => zstd::bulk::compress(&item.data, ZSTD_LEVEL).ok()?,
+        _ => return None,
+    };
+    (data.len() < item.data.len()).then_some(Compressed { codec, data })
+}
+
+/// Undo [`compress_item`]. The output is capped at the largest clipboard
+/// item we would ever send, so a hostile frame cannot inflate without
+/// bound.
+pub fn decompress(codec: &str, data: &[u8]) -> Result<Vec<u8>> {
+    decompress_at_most(codec, data, MAX_DECOMPRESSED_SIZE)
+}
+
+fn decompress_at_most(codec: &str, data: &[u8], max_size: u64) -> Result<Vec<u8>> {
+    match codec {
+        CODEC_ZSTD => {
+            let decoder = zstd::stream::read::Decoder::new(data)?;
+            let mut out = Vec::new();
+            decoder
+                .take(max_size + 1)
+                .read_to_end(&mut out)
+                .context("zstd decompression failed")?;
+            if out.len() as u64 > max_size {
+                return Err(anyhow!("decompressed payload exceeds {} bytes", max_size));
+            }
+            Ok(out)
+        }
+        other => Err(anyhow!("unsupported compression codec {:?}", other)),
+    }
+}
+
+/// Compression ratio of one sent clipboard payload, reported through
+/// `RuntimeEvent::Compression`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionStats {
+    /// `"server"` or `"lan"`.
+    pub transport: &'static str,
+    pub codec: String,
+    pub content_type: String,
+    pub original_bytes: usize,
+    pub compressed_bytes: usize,
+}
+
+impl CompressionStats {
+    pub fn new(transport: &'static str, item: &ClipboardItem, compressed: &Compressed) -> Self {
+        Self {
+            transport,
+            codec: compressed.codec.to_string(),
+            content_type: item.content_type.clone(),
+            original_bytes: item.data.len(),
+            compressed_bytes: compressed.data.len(),
+        }
+    }
+
+    /// Original size divided by compressed size.
+    pub fn ratio(&self) -> f64 {
+        self.original_bytes as f64 / self.compressed_bytes.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::clock::Hlc;
+    use crate::runtime::messages::{
+        ClipboardBroadcastPayload, CONTENT_TYPE_HTML, CONTENT_TYPE_IMAGE_PNG, CONTENT_TYPE_TEXT,
+    };
+
+    fn item(content_type: &str, data: Vec<u8>) -> ClipboardItem {
+        ClipboardItem::new(
+            content_type.to_string(),
+            data.into(),
+            "local".to_string(),
+            Hlc::new(1_700_000_000_000, 0),
+        )
+    }
+
+    /// A log-like text that compresses well.
+    fn log_text(bytes: usize) -> Vec<u8> {
+        "2024-01-01 INFO request handled in 3 ms\n"
+            .bytes()
+            .cycle()
+            .take(bytes)
+            .collect()
+    }
+
+    #[test]
+    fn compressed_text_round_trips_through_a_server_broadcast() {
+        for content_type in [CONTENT_TYPE_TEXT, CONTENT_TYPE_HTML] {
+            let original = item(content_type, log_text(64 * 1024));
+            let compressed = compress_item(Some(CODEC_ZSTD), &original).unwrap();
+            assert!(compressed.data.len() < original.data.len());
+
+            let broadcast = ClipboardBroadcastPayload {
+                content_type: original.content_type.clone(),
+                data: compressed.to_base64(),
+                sender_device_id: Some(original.sender_device_id.clone()),
+                item_id: Some(original.item_id),
+                timestamp: original.clock.wall_ms,
+                logical: original.clock.logical,
+                compression: Some(compressed.codec.to_string()),
+            };
+            let received = broadcast.into_item().unwrap();
+            assert_eq!(received.data, original.data);
+            assert_eq!(received.content_type, original.content_type);
+            assert_eq!(received.clock, original.clock);
+        }
+    }
+
+    #[test]
+    fn small_payloads_and_images_are_sent_as_is() {
+        let small = item(CONTENT_TYPE_TEXT, log_text(COMPRESSION_THRESHOLD));
+        assert!(compress_item(Some(CODEC_ZSTD), &small).is_none());
+
+        let image = item(CONTENT_TYPE_IMAGE_PNG, vec![0; 64 * 1024]);
+        assert!(compress_item(Some(CODEC_ZSTD), &image).is_none());
+
+        let large = item(CONTENT_TYPE_TEXT, log_text(64 * 1024));
+        assert!(compress_item(None, &large).is_none());
+    }
+
+    #[test]
+    fn decompression_stops_at_the_size_limit() {
+        let limit = 64 * 1024;
+        let fits = zstd::bulk::compress(&log_text(limit as usize), ZSTD_LEVEL).unwrap();
+        assert_eq!(
+            decompress_at_most(CODEC_ZSTD, &fits, limit).unwrap().len(),
+            limit as usize
+        );
+
+        let bomb = zstd::bulk::compress(&vec![0; limit as usize + 1], ZSTD_LEVEL).unwrap();
+        assert!(decompress_at_most(CODEC_ZSTD, &bomb, limit).is_err());
+        assert!(decompress("lz4", &fits).is_err());
+    }
+}
@@ -1,5 +1,10 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use super::lan::protocol::{DEFAULT_DISCOVERY_PORT, DEFAULT_TCP_PORT};
+use super::policy::SyncDirection;
+
 fn default_max_image_kb() -> u64 {
     512
 }
@@ -28,6 +33,18 @@ fn default_close_behavior() -> String {
     "minimize_to_tray".to_string()
 }
 
+fn default_lan_discovery() -> String {
+    "broadcast".to_string()
+}
+
+fn default_lan_discovery_port() -> u16 {
+    DEFAULT_DISCOVERY_PORT
+}
+
+fn default_lan_tcp_port() -> u16 {
+    DEFAULT_TCP_PORT
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SettingsForm {
     pub server_url: String,
@@ -46,6 +63,27 @@ pub struct SettingsForm {
     /// What happens when the user closes the main window.
     /// `"minimize_to_tray"` | `"minimize"` | `"quit"`
     pub close_behavior: String,
+    /// LAN sync group name; only devices in the same group see each other.
+    pub lan_group_name: String,
+    /// Shared secret of the LAN sync group.
+    pub lan_group_secret: String,
+    /// LAN peers dialed directly (`host:port`).
+    pub lan_static_peers: Vec<String>,
+    /// LAN discovery backend: `"broadcast"` | `"mdns"` | `"both"`
+    pub lan_discovery: String,
+    /// Interfaces used for LAN discovery (names or CIDRs, `!` excludes).
+    pub lan_interfaces: Vec<String>,
+    /// UDP port of LAN discovery beacons (1–65535).
+    pub lan_discovery_port: i32,
+    /// TCP port of the LAN host (0–65535, 0 = ephemeral).
+    pub lan_tcp_port: i32,
+    /// Relay clipboard items between LAN peers.
+    pub lan_relay: bool,
+    /// Which way clipboard content flows with all devices. Switched with
+    /// `set_sync_direction`; `save_settings` keeps the stored value.
+    pub sync_direction: SyncDirection,
+    /// Per-device sync directions, keyed by device id.
+    pub peer_sync_directions: BTreeMap<String, SyncDirection>,
 }
 
 impl From<&Config> for SettingsForm {
@@ -66,6 +104,16 @@ impl From<&Config> for SettingsForm {
             connection_mode: cfg.connection_mode.clone(),
             lan_device_name: cfg.lan_device_name.clone(),
             close_behavior: cfg.close_behavior.clone(),
+            lan_group_name: cfg.lan_group_name.clone(),
+            lan_group_secret: cfg.lan_group_secret.clone(),
+            lan_static_peers: cfg.lan_static_peers.clone(),
+            lan_discovery: cfg.lan_discovery.clone(),
+            lan_interfaces: cfg.lan_interfaces.clone(),
+            lan_discovery_port: cfg.lan_discovery_port.into(),
+            lan_tcp_port: cfg.lan_tcp_port.into(),
+            lan_relay: cfg.lan_relay,
+            sync_direction: cfg.sync_direction,
+            peer_sync_directions: cfg.peer_sync_directions.clone(),
         }
     }
 }
@@ -97,6 +145,47 @@ pub struct Config {
     /// `"minimize_to_tray"` (default) | `"minimize"` | `"quit"`
     #[serde(default = "default_close_behavior")]
     pub close_behavior: String,
+    /// LAN sync group name. Beacons and handshakes are authenticated with a
+    /// key derived from the name and `lan_group_secret`, so devices only
+    /// discover and connect to members of the same group. Both empty →
+    /// the default (open) group.
+    #[serde(default)]
+    pub lan_group_name: String,
+    /// Shared secret of the LAN sync group.
+    #[serde(default)]
+    pub lan_group_secret: String,
+    /// LAN peer addresses (`host:port`) dialed directly in addition to the
+    /// ones found by discovery, for networks that drop UDP broadcasts.
+    #[serde(default)]
+    pub lan_static_peers: Vec<String>,
+    /// How LAN peers are discovered.
+    /// `"broadcast"` (default, UDP beacons) | `"mdns"` (mDNS / DNS-SD) | `"both"`
+    #[serde(default = "default_lan_discovery")]
+    pub lan_discovery: String,
+    /// Interfaces LAN discovery runs on: names (`eth0`, `docker*`) or CIDRs
+    /// (`192.168.1.0/24`), with a leading `!` to exclude. Empty → all.
+    #[serde(default)]
+    pub lan_interfaces: Vec<String>,
+    /// UDP port discovery beacons are sent to and received on. Every member
+    /// of a sync group must use the same one.
+    #[serde(default = "default_lan_discovery_port")]
+    pub lan_discovery_port: u16,
+    /// TCP port the LAN host listens on; `0` picks an ephemeral port, which
+    /// beacons advertise. Falls back to an ephemeral port when taken.
+    #[serde(default = "default_lan_tcp_port")]
+    pub lan_tcp_port: u16,
+    /// Forward clipboard items received from one LAN peer to the others, so
+    /// peers that cannot reach each other directly still sync.
+    #[serde(default)]
+    pub lan_relay: bool,
+    /// Which way clipboard content flows with every device (see
+    /// [`super::policy`]); switched from the main window.
+    #[serde(default)]
+    pub sync_direction: SyncDirection,
+    /// Sync direction of single devices, keyed by device id; devices not
+    /// listed send and receive.
+    #[serde(default)]
+    pub peer_sync_directions: BTreeMap<String, SyncDirection>,
 }
 
 impl Default for Config {
@@ -113,6 +202,16 @@ impl Default for Config {
             connection_mode: default_connection_mode(),
             lan_device_name: default_lan_device_name(),
             close_behavior: default_close_behavior(),
+            lan_group_name: String::new(),
+            lan_group_secret: String::new(),
+            lan_static_peers: Vec::new(),
+            lan_discovery: default_lan_discovery(),
+            lan_interfaces: Vec::new(),
+            lan_discovery_port: default_lan_discovery_port(),
+            lan_tcp_port: default_lan_tcp_port(),
+            lan_relay: false,
+            sync_direction: SyncDirection::default(),
+            peer_sync_directions: BTreeMap::new(),
         }
     }
 }
@@ -0,0 +1,171 @@
+//! Delivery acknowledgements for clipboard items.
+//!
+//! Every device that receives an item reports what became of it: applied
+//! to its clipboard, dropped as stale, too large, or refused by policy. LAN
+//! peers answer with `Ack`/`Nack` frames (relays pass them back towards the
+//! origin), server clients with `clipboard_ack`/`clipboard_nack` messages.
+//!
+//! The reports for items copied on this device are tallied in a
+//! [`DeliveryTracker`] and surface as
+//! [`RuntimeEvent::Delivery`](super::RuntimeEvent::Delivery), so the UI
+//! can show how many devices an item reached.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use parking_lot::Mutex as ParkingMutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::messages::ClipboardItem;
+
+/// Items whose reports are still tallied; older ones are forgotten.
+const TRACKED_ITEMS: usize = 64;
+
+/// What became of an item on one device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Written to the clipboard, or it already held the same content.
+    Applied,
+    /// Older than the clipboard content the device already had.
+    Stale,
+    /// Over the size limit of the device or the link.
+    TooLarge,
+    /// Refused by policy: unpaired sender, unsupported content type.
+    Rejected,
+    /// The clipboard could not be written.
+    Failed,
+    /// Not sent in full because newer items took its place.
+    Superseded,
+    /// A status this build does not know, sent by a newer device.
+    #[serde(other)]
+    Unknown,
+}
+
+impl DeliveryStatus {
+    /// `true` when the item landed on the device's clipboard.
+    pub fn is_ack(self) -> bool {
+        self == DeliveryStatus::Applied
+    }
+}
+
+/// One device's report on one item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryReceipt {
+    /// Device the item was copied on.
+    pub origin: String,
+    /// [`ClipboardItem::item_id`] of the item.
+    pub item_id: u64,
+    /// Device reporting.
+    pub device_id: String,
+    pub status: DeliveryStatus,
+}
+
+impl DeliveryReceipt {
+    pub fn new(item: &ClipboardItem, device_id: &str, status: DeliveryStatus) -> Self {
+        Self {
+            origin: item.sender_device_id.clone(),
+            item_id: item.item_id,
+            device_id: device_id.to_string(),
+            status,
+        }
+    }
+}
+
+/// Where the setter sends its receipt for an item: back to the connection
+/// the item arrived on.
+pub type ReceiptSender = mpsc::Sender<DeliveryReceipt>;
+
+/// Payload of [`RuntimeEvent::Delivery`](super::RuntimeEvent::Delivery).
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryReport {
+    pub item_id: u64,
+    pub content_type: String,
+    /// Device whose report this is.
+    pub device_id: String,
+    pub status: DeliveryStatus,
+    /// Devices that applied the item so far.
+    pub delivered: usize,
+    /// Devices the item was sent to or heard from.
+    pub targets: usize,
+}
+
+#[derive(Debug)]
+struct Tally {
+    content_type: String,
+    /// Sent to one device on demand, so never relayed.
+    addressed: bool,
+    /// Status per device; `None` while the device has not answered.
+    devices: HashMap<String, Option<DeliveryStatus>>,
+}
+
+/// Reports on the most recent items copied on this device.
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryTracker {
+    inner: Arc<ParkingMutex<TrackerState>>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    items: HashMap<u64, Tally>,
+    order: VecDeque<u64>,
+}
+
+impl DeliveryTracker {
+    /// Note that `item` was sent to `device_id` (or to the server, which
+    /// fans it out to devices unknown in advance, when `None`).
+    pub fn sent(&self, item: &ClipboardItem, device_id: Option<&str>) {
+        let mut state = self.inner.lock();
+        if !state.items.contains_key(&item.item_id) {
+            state.order.push_back(item.item_id);
+            if state.order.len() > TRACKED_ITEMS {
+                if let Some(oldest) = state.order.pop_front() {
+                    state.items.remove(&oldest);
+                }
+            }
+        }
+        let tally = state.items.entry(item.item_id).or_insert_with(|| Tally {
+            content_type: item.content_type.clone(),
+            addressed: item.target.is_some(),
+            devices: HashMap::new(),
+        });
+        if let Some(device_id) = device_id {
+            tally.devices.entry(device_id.to_string()).or_insert(None);
+        }
+    }
+
+    /// Whether a tracked item may have been relayed on to other devices,
+    /// so that a peer can pass on reports in their name.
+    pub fn relayable(&self, item_id: u64) -> bool {
+        self.inner
+            .lock()
+            .items
+            .get(&item_id)
+            .is_some_and(|tally| !tally.addressed)
+    }
+
+    /// Record a device's report. Reports on items that are not tracked
+    /// (copied elsewhere, or long superseded) yield `None`.
+    pub fn report(&self, receipt: &DeliveryReceipt) -> Option<DeliveryReport> {
+        let mut state = self.inner.lock();
+        let tally = state.items.get_mut(&receipt.item_id)?;
+        tally
+            .devices
+            .insert(receipt.device_id.clone(), Some(receipt.status));
+        Some(DeliveryReport {
+            item_id: receipt.item_id,
+            content_type: tally.content_type.clone(),
+            device_id: receipt.device_id.clone(),
+            status: receipt.status,
+            delivered: tally
+                .devices
+                .values()
+                .filter(|status| status.is_some_and(DeliveryStatus::is_ack))
+                .count(),
+            targets: tally.devices.len(),
+        })
+    }
+}
@@ -0,0 +1,147 @@
+//! Persistent device identity.
+//!
+//! Each installation owns one long-lived X25519 key pair, generated on first
+//! start and stored in [`IDENTITY_FILE`] inside the application data
+//! directory (next to the `config.json` store). The `device_id` used in
+//! discovery beacons, `Hello`/`Welcome` and clipboard payloads is derived
+//! from the public key, so it survives restarts and reloads, and a LAN peer
+//! cannot claim an id without holding the matching private key (the
+//! handshake in [`super::lan::secure`] proves possession).
+
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use snow::Builder;
+
+use super::lan::secure::noise_params;
+
+/// File name of the identity inside the application data directory.
+pub const IDENTITY_FILE: &str = "device_identity.json";
+
+/// Number of public-key hash bytes (hex-encoded) that make up a device id.
+const DEVICE_ID_BYTES: usize = 16;
+
+/// Long-lived key pair identifying this device, plus the id derived from it.
+///
+/// Cheap enough to clone into every task that needs it.
+#[derive(Clone)]
+pub struct DeviceIdentity {
+    device_id: String,
+    private_key: Vec<u8>,
+    public_key: Vec<u8>,
+}
+
+/// On-disk layout of [`IDENTITY_FILE`] (base64-encoded keys).
+#[derive(Serialize, Deserialize)]
+struct IdentityFile {
+    private_key: String,
+    public_key: String,
+}
+
+impl DeviceIdentity {
+    /// Load the identity from `<data_dir>/device_identity.json`, generating
+    /// and persisting a new one on first use.
+    ///
+    /// A corrupt file is reported as an error rather than silently replaced,
+    /// since a new identity invalidates every pairing made with the old one.
+    pub fn load_or_generate(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(IDENTITY_FILE);
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let file: IdentityFile = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("parsing device identity {}", path.display()))?;
+                Self::from_keys(
+                    decode_key(&file.private_key)?,
+                    decode_key(&file.public_key)?,
+                )
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let identity = Self::generate()?;
+                let file = IdentityFile {
+                    private_key: encode_key(&identity.private_key),
+                    public_key: encode_key(&identity.public_key),
+                };
+                std::fs::create_dir_all(data_dir)
+                    .with_context(|| format!("creating {}", data_dir.display()))?;
+                write_private_file(&path, &serde_json::to_vec_pretty(&file)?)
+                    .with_context(|| format!("writing device identity {}", path.display()))?;
+                Ok(identity)
+            }
+            Err(e) => Err(e).with_context(|| format!("reading device identity {}", path.display())),
+        }
+    }
+
+    /// Generate a fresh, unpersisted identity.
+    pub fn generate() -> Result<Self> {
+        let keypair = Builder::new(noise_params())
+            .generate_keypair()
+            .map_err(|e| anyhow!("generating device key: {}", e))?;
+        Self::from_keys(keypair.private, keypair.public)
+    }
+
+    fn from_keys(private_key: Vec<u8>, public_key: Vec<u8>) -> Result<Self> {
+        if private_key.len() != 32 || public_key.len() != 32 {
+            return Err(anyhow!("device identity keys must be 32 bytes"));
+        }
+        Ok(Self {
+            device_id: device_id_from_public_key(&public_key),
+            private_key,
+            public_key,
+        })
+    }
+
+    /// Stable device id derived from the public key.
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// Raw 32-byte private key.
+    pub fn private_key(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    /// Raw 32-byte public key. Peers learn it from the handshake, so only
+    /// tests ask for it here.
+    #[cfg(test)]
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+}
+
+/// Derive a device id from a raw public key: the first
+/// [`DEVICE_ID_BYTES`] bytes of its SHA-256, hex-encoded.
+pub fn device_id_from_public_key(public_key: &[u8]) -> String {
+    digest::digest(&digest::SHA256, public_key).as_ref()[..DEVICE_ID_BYTES]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Base64 form of a key, as stored in the identity file and trust store.
+pub fn encode_key(key: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(key)
+}
+
+fn decode_key(key: &str) -> Result<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(key)
+        .context("decoding device key")
+}
+
+/// Write `contents` to `path`, readable by the current user only where the
+/// platform supports it.
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
@@ -1,26 +1,29 @@
 //! UDP broadcast discovery for LAN peers.
 //!
+//! Peers found here are recorded in the shared
+//! [`PeerRegistry`](super::registry::PeerRegistry), as are those found by
+//! the mDNS backend in [`super::mdns`], which can run instead of or
+//! alongside the broadcast beacon (`lan_discovery` in the config).
+//!
 //! This module provides two async tasks:
 //!
 //! 1. **Beacon broadcaster** — periodically sends a [`DiscoveryBeacon`] as a
-//!    UDP broadcast so that other peers on the same LAN segment can find us.
+//!    UDP broadcast so that other peers on the same LAN segment can find us,
+//!    and to the [`DISCOVERY_MULTICAST_V6`] group for IPv6-only segments.
 //!
 //! 2. **Beacon listener** — listens for incoming beacons from other peers and
-//!    maintains a shared [`DiscoveredPeers`] map that the rest of the system
-//!    can query.
+//!    records them in the registry, expiring peers that stopped announcing
+//!    themselves. One listener runs per address family.
 //!
 //! Both tasks respect a [`CancellationToken`] for clean shutdown and emit
 //! [`RuntimeEvent`]s so the UI can display discovery status.
 
 use std::{
-    collections::HashMap,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
-    sync::Arc,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use log::Level;
-use parking_lot::RwLock;
 use socket2::{Domain, Protocol, Socket, Type};
 use tokio::{
     net::UdpSocket,
@@ -29,31 +32,15 @@ use tokio::{
 };
 use tokio_util::sync::CancellationToken;
 
+use super::interfaces::{eligible_interfaces, InterfaceCache, InterfaceFilter, LocalInterface};
+use super::peer::PeerContext;
 use super::protocol::{
-    decode_beacon, encode_beacon, DiscoveredPeer, DiscoveryBeacon, DEFAULT_DISCOVERY_PORT,
-    DISCOVERY_INTERVAL_SECS,
+    decode_beacon, encode_beacon, peer_ip, DiscoveryBeacon, DEFAULT_DISCOVERY_PORT,
+    DISCOVERY_INTERVAL_SECS, DISCOVERY_MULTICAST_V6,
 };
+use super::registry::emit_peer_list;
 use crate::runtime::{RuntimeEvent, RuntimeLogEvent};
 
-// ────────────────────────────────────────────────────────────────────────────
-// Shared peer map
-// ────────────────────────────────────────────────────────────────────────────
-
-/// Thread-safe container for discovered peers, keyed by `device_id`.
-///
-/// Wrapped in an `Arc<RwLock<…>>` so that the broadcaster, listener, and
-/// connection manager can all access it without contention issues.
-pub type DiscoveredPeers = Arc<RwLock<HashMap<String, DiscoveredPeer>>>;
-
-/// Create a new, empty peer map.
-pub fn new_peer_map() -> DiscoveredPeers {
-    Arc::new(RwLock::new(HashMap::new()))
-}
-
-/// How many seconds before a peer that has not re-announced is considered
-/// stale and removed from the map.
-const PEER_EXPIRY_SECS: u64 = 15;
-
 // ────────────────────────────────────────────────────────────────────────────
 // Beacon broadcaster
 // ────────────────────────────────────────────────────────────────────────────
@@ -61,24 +48,34 @@ const PEER_EXPIRY_SECS: u64 = 15;
 /// Periodically broadcasts a discovery beacon on the LAN.
 ///
 /// The socket is bound to `0.0.0.0:0` (ephemeral port) with `SO_BROADCAST`
-/// enabled, and the beacon is sent to `255.255.255.255:<discovery_port>`.
+/// enabled, and the beacon is sent to the subnet-directed broadcast address
+/// of every eligible interface (see [`super::interfaces`]), or to
+/// `255.255.255.255:<discovery_port>` when none is found and no interface
+/// filter is configured. When the host has IPv6, every beacon is also sent
+/// from `[::]:0` to `[DISCOVERY_MULTICAST_V6]:<discovery_port>` on each
+/// eligible interface with a link-local address.
 ///
 /// # Arguments
 ///
-/// * `device_id`      — unique identifier for this peer (UUID v4).
-/// * `device_name`    — human-friendly label (e.g. hostname).
+/// * `ctx`            — our identity, sync group and event channel.
 /// * `tcp_port`       — the TCP port we are listening on for peer connections.
 /// * `discovery_port` — UDP port to broadcast on (use `0` for the default).
-/// * `events`         — channel to emit runtime events for logging / UI.
+/// * `filter`         — interfaces beacons may be sent on.
 /// * `cancel`         — token to signal graceful shutdown.
 pub async fn run_beacon_broadcaster(
-    device_id: String,
-    device_name: String,
+    ctx: PeerContext,
     tcp_port: u16,
     discovery_port: u16,
-    events: mpsc::Sender<RuntimeEvent>,
+    filter: InterfaceFilter,
     cancel: CancellationToken,
 ) {
+    let PeerContext {
+        device_id,
+        device_name,
+        group,
+        events,
+        ..
+    } = ctx;
     let port = if discovery_port == 0 {
         DEFAULT_DISCOVERY_PORT
     } else {
@@ -110,12 +107,34 @@ pub async fn run_beacon_broadcaster(
 
     let broadcast_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::BROADCAST, port));
 
+    // IPv6 has no broadcast; beacons also go to a link-local multicast
+    // group. Optional, since IPv6 may be disabled on the host.
+    let socket_v6 = match UdpSocket::bind(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0)).await {
+        Ok(s) => Some(s),
+        Err(e) => {
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Warn,
+                    format!(
+                        "LAN discovery broadcaster: IPv6 unavailable, sending IPv4 beacons only: {}",
+                        e
+                    ),
+                )))
+                .await;
+            None
+        }
+    };
+    let mut v6_send_failed = false;
+    let mut interfaces = InterfaceCache::new(filter);
+
     let _ = events
         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
             Level::Info,
             format!(
-                "LAN discovery broadcaster started (port={}, tcp_port={})",
-                port, tcp_port
+                "LAN discovery broadcaster started (port={}, tcp_port={}, group={})",
+                port,
+                tcp_port,
+                group.display_name()
             ),
         )))
         .await;
@@ -133,8 +152,28 @@ pub async fn run_beacon_broadcaster(
                     tcp_port,
                     seq,
                 };
-                let packet = encode_beacon(&beacon);
-                if let Err(e) = socket.send_to(&packet, broadcast_addr).await {
+                let packet = encode_beacon(&beacon, &group);
+                let (targets, targets_v6) = beacon_targets(&mut interfaces, broadcast_addr, port);
+
+                // Rebind only when every target failed; a single interface
+                // going down must not disturb the others.
+                let mut last_error = None;
+                for target in &targets {
+                    if let Err(e) = socket.send_to(&packet, target).await {
+                        last_error = Some(e);
+                    }
+                }
+                if targets.len() > 1 {
+                    if let Some(e) = last_error.take() {
+                        let _ = events
+                            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                Level::Debug,
+                                format!("LAN beacon send failed on some interfaces: {}", e),
+                            )))
+                            .await;
+                    }
+                }
+                if let Some(e) = last_error {
                     let _ = events
                         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                             Level::Warn,
@@ -172,6 +211,29 @@ pub async fn run_beacon_broadcaster(
                         }
                     }
                 }
+                if let Some(socket_v6) = &socket_v6 {
+                    let mut result = Ok(());
+                    for target in &targets_v6 {
+                        if let Err(e) = socket_v6.send_to(&packet, target).await {
+                            result = Err(e);
+                        }
+                    }
+                    match result {
+                        Ok(()) => v6_send_failed = false,
+                        // Hosts without an IPv6 route fail on every tick;
+                        // report it once until it recovers.
+                        Err(e) if !v6_send_failed => {
+                            v6_send_failed = true;
+                            let _ = events
+                                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                                    Level::Debug,
+                                    format!("LAN IPv6 beacon send failed: {}", e),
+                                )))
+                                .await;
+                        }
+                        Err(_) => {}
+                    }
+                }
                 seq = seq.wrapping_add(1);
             }
         }
@@ -192,31 +254,45 @@ pub async fn run_beacon_broadcaster(
 /// Listens for discovery beacons from LAN peers and maintains `peers`.
 ///
 /// Beacons whose `device_id` matches our own are silently ignored (we don't
-/// want to discover ourselves). Stale peers that haven't sent a beacon within
-/// [`PEER_EXPIRY_SECS`] seconds are pruned on every receive cycle.
+/// want to discover ourselves), as are beacons not tagged with our sync
+/// group's key. Stale peers that haven't sent a beacon within
+/// the registry's expiry window are pruned on every receive cycle.
 ///
-/// Whenever the peer map changes (a new peer appears, an existing peer's
-/// fields are updated, or a stale peer is removed) a
+/// Whenever the peer list changes (a new peer appears, an existing peer's
+/// fields are updated, or a stale peer expires) a
 /// [`RuntimeEvent::LanPeersChanged`] event is emitted so the frontend can
-/// refresh its peer list.
+/// refresh its peer list. Each entry carries its [`PeerAccess`] status so
+/// unknown peers show up as pending.
+///
+/// Each peer is tagged with the interface its beacons arrive on. With an
+/// interface filter configured, beacons arriving on any other interface
+/// are ignored.
 ///
 /// # Arguments
 ///
-/// * `own_device_id` — our device id, used to filter self-beacons.
-/// * `peers`         — shared map that will be updated in-place.
-/// * `socket`        — a pre-bound UDP socket (created via
-///                     [`bind_reusable_udp`] by the caller so that bind
-///                     failures can be surfaced before any tasks are
-///                     spawned).
-/// * `events`        — channel to emit runtime events.
-/// * `cancel`        — token to signal graceful shutdown.
+/// * `ctx`    — our device id (to filter self-beacons), the peer registry
+///   updated in-place, the access lists used to tag each peer's status,
+///   the sync group whose key beacons must carry, and the event channel.
+/// * `socket` — a pre-bound UDP socket (created via [`bind_reusable_udp`]
+///   by the caller so that bind failures can be surfaced before any tasks
+///   are spawned).
+/// * `filter` — interfaces beacons are accepted from.
+/// * `cancel` — token to signal graceful shutdown.
 pub async fn run_beacon_listener(
-    own_device_id: String,
-    peers: DiscoveredPeers,
+    ctx: PeerContext,
     socket: UdpSocket,
-    events: mpsc::Sender<RuntimeEvent>,
+    filter: InterfaceFilter,
     cancel: CancellationToken,
 ) {
+    let PeerContext {
+        device_id: own_device_id,
+        group,
+        events,
+        trust,
+        registry,
+        ..
+    } = ctx;
+    let mut interfaces = InterfaceCache::new(filter);
     let _ = events
         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
             Level::Info,
@@ -232,38 +308,45 @@ pub async fn run_beacon_listener(
             result = socket.recv_from(&mut buf) => {
                 match result {
                     Ok((len, src_addr)) => {
-                        if let Some(beacon) = decode_beacon(&buf[..len]) {
+                        if let Some(beacon) = decode_beacon(&buf[..len], &group) {
                             // Ignore our own beacons.
                             if beacon.device_id == own_device_id {
                                 continue;
                             }
 
+                            let interface = interfaces.interface_of(&src_addr);
+                            if interface.is_none() && !interfaces.filter().is_empty() {
+                                continue;
+                            }
+
                             let now = now_unix_secs();
-                            let ip = src_addr.ip().to_string();
+                            let ip = peer_ip(&src_addr);
 
-                            let changed = upsert_peer(&peers, &beacon, &ip, now);
+                            let changed =
+                                registry.upsert_discovered(&beacon, &ip, interface.as_deref(), now);
 
                             if changed {
                                 let _ = events
                                     .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                                         Level::Info,
                                         format!(
-                                            "LAN peer discovered/updated: {} ({}) at {}:{}",
+                                            "LAN peer discovered/updated: {} ({}) at {}:{} via {}",
                                             beacon.device_name,
                                             beacon.device_id,
                                             ip,
                                             beacon.tcp_port,
+                                            interface.as_deref().unwrap_or("unknown interface"),
                                         ),
                                     )))
                                     .await;
 
-                                emit_peer_list(&peers, &events).await;
+                                emit_peer_list(&registry, &trust, &events).await;
                             }
 
-                            // Prune stale peers.
-                            let pruned = prune_stale_peers(&peers, now);
+                            // Expire peers that stopped announcing.
+                            let pruned = registry.prune_discovered(now);
                             if pruned > 0 {
-                                emit_peer_list(&peers, &events).await;
+                                emit_peer_list(&registry, &trust, &events).await;
                             }
                         }
                     }
@@ -302,14 +385,15 @@ pub async fn run_beacon_listener(
 /// The function is `pub` within the crate so that the parent module can
 /// pre-bind the socket and pass it to [`run_beacon_listener`], allowing
 /// bind failures to be surfaced before any background tasks are spawned.
+///
+/// IPv6 sockets are bound `IPV6_V6ONLY`, so an IPv4 and an IPv6 listener
+/// can share the discovery port.
 pub async fn bind_reusable_udp(
-    port: u16,
+    addr: SocketAddr,
     events: &mpsc::Sender<RuntimeEvent>,
 ) -> Option<UdpSocket> {
-    let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port);
-
     // Create a socket2 socket so we can set options *before* binding.
-    let socket = match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)) {
+    let socket = match Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP)) {
         Ok(s) => s,
         Err(e) => {
             let _ = events
@@ -353,6 +437,20 @@ pub async fn bind_reusable_udp(
         }
     }
 
+    if addr.is_ipv6() {
+        if let Err(e) = socket.set_only_v6(true) {
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Warn,
+                    format!(
+                        "LAN discovery listener: IPV6_V6ONLY failed (non-fatal): {}",
+                        e
+                    ),
+                )))
+                .await;
+        }
+    }
+
     // Set non-blocking *before* converting to a tokio socket.
     socket.set_nonblocking(true).ok();
 
@@ -360,10 +458,7 @@ pub async fn bind_reusable_udp(
         let _ = events
             .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                 Level::Error,
-                format!(
-                    "LAN discovery listener: bind failed on port {}: {}",
-                    port, e
-                ),
+                format!("LAN discovery listener: bind failed on {}: {}", addr, e),
             )))
             .await;
         return None;
@@ -385,62 +480,107 @@ pub async fn bind_reusable_udp(
     }
 }
 
-/// Insert or update a peer entry. Returns `true` when the peer map was
-/// meaningfully changed (new peer, or an existing peer's `device_name`,
-/// `addr`, or `tcp_port` differs from the stored entry).
-fn upsert_peer(peers: &DiscoveredPeers, beacon: &DiscoveryBeacon, ip: &str, now: u64) -> bool {
-    let mut writer = peers.write();
-
-    let new_entry = DiscoveredPeer {
-        device_id: beacon.device_id.clone(),
-        device_name: beacon.device_name.clone(),
-        addr: ip.to_string(),
-        tcp_port: beacon.tcp_port,
-        last_seen: now,
-    };
-
-    if let Some(existing) = writer.get(&beacon.device_id) {
-        let changed = existing.device_name != new_entry.device_name
-            || existing.addr != new_entry.addr
-            || existing.tcp_port != new_entry.tcp_port;
+/// Bind the IPv6 discovery listener on `[::]:<port>` and join
+/// [`DISCOVERY_MULTICAST_V6`] on every eligible interface with a link-local
+/// address (the default interface when there is none).
+///
+/// Interfaces that appear later are not joined until LAN mode restarts.
+/// Returns `None` (after logging) when the host has no usable IPv6; LAN
+/// mode then runs IPv4-only.
+pub async fn bind_multicast_udp_v6(
+    port: u16,
+    filter: &InterfaceFilter,
+    events: &mpsc::Sender<RuntimeEvent>,
+) -> Option<UdpSocket> {
+    let addr = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0));
+    let socket = bind_reusable_udp(addr, events).await?;
 
-        // Always update `last_seen`, but only report a change when
-        // user-visible fields actually differ.
-        writer.insert(beacon.device_id.clone(), new_entry);
-        changed
-    } else {
-        writer.insert(beacon.device_id.clone(), new_entry);
-        true // brand-new peer
+    let mut indices = link_local_indices(&eligible_interfaces(filter));
+    if indices.is_empty() && filter.is_empty() {
+        indices.push(0);
+    }
+    let mut joined = 0;
+    for index in indices {
+        match socket.join_multicast_v6(&DISCOVERY_MULTICAST_V6, index) {
+            Ok(()) => joined += 1,
+            Err(e) => {
+                let _ = events
+                    .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                        Level::Debug,
+                        format!(
+                            "LAN discovery listener: joining {} on interface {} failed: {}",
+                            DISCOVERY_MULTICAST_V6, index, e
+                        ),
+                    )))
+                    .await;
+            }
+        }
+    }
+    if joined == 0 {
+        let _ = events
+            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                Level::Warn,
+                format!(
+                    "LAN discovery listener: could not join {}, IPv6 discovery disabled",
+                    DISCOVERY_MULTICAST_V6
+                ),
+            )))
+            .await;
+        return None;
     }
+    Some(socket)
 }
 
-/// Removes peers that haven't been seen within [`PEER_EXPIRY_SECS`] and
-/// returns the number of entries removed.
-fn prune_stale_peers(peers: &DiscoveredPeers, now: u64) -> usize {
-    let mut writer = peers.write();
-    let before = writer.len();
-    writer.retain(|_, peer| now.saturating_sub(peer.last_seen) < PEER_EXPIRY_SECS);
-    before - writer.len()
+/// Where to send this round of beacons: subnet-directed IPv4 broadcasts
+/// and per-interface IPv6 multicasts, falling back to `fallback` and the
+/// default interface when nothing eligible is found and no filter is set.
+fn beacon_targets(
+    interfaces: &mut InterfaceCache,
+    fallback: SocketAddr,
+    port: u16,
+) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
+    let unfiltered = interfaces.filter().is_empty();
+    let current = interfaces.get();
+
+    let mut targets: Vec<SocketAddr> = Vec::new();
+    for broadcast in current.iter().filter_map(|iface| iface.broadcast) {
+        let target = SocketAddr::V4(SocketAddrV4::new(broadcast, port));
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    if targets.is_empty() && unfiltered {
+        targets.push(fallback);
+    }
+
+    let mut indices = link_local_indices(current);
+    if indices.is_empty() && unfiltered {
+        indices.push(0);
+    }
+    let targets_v6 = indices
+        .into_iter()
+        .map(|index| SocketAddr::V6(SocketAddrV6::new(DISCOVERY_MULTICAST_V6, port, 0, index)))
+        .collect();
+
+    (targets, targets_v6)
 }
 
-/// Emit the current peer list as a JSON-serialised
-/// [`RuntimeEvent::LanPeersChanged`] so the frontend can refresh the
-/// displayed peer list.
-async fn emit_peer_list(peers: &DiscoveredPeers, events: &mpsc::Sender<RuntimeEvent>) {
-    let list: Vec<DiscoveredPeer> = peers.read().values().cloned().collect();
-    let json = serde_json::to_string(&list).unwrap_or_else(|_| "[]".into());
-    let _ = events.send(RuntimeEvent::LanPeersChanged(json)).await;
+/// Distinct indices of the interfaces with a link-local IPv6 address.
+fn link_local_indices(interfaces: &[LocalInterface]) -> Vec<u32> {
+    let mut indices: Vec<u32> = interfaces
+        .iter()
+        .filter(|iface| iface.is_ipv6_link_local() && iface.index != 0)
+        .map(|iface| iface.index)
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
 }
 
 /// Returns the current UNIX timestamp in seconds.
-fn now_unix_secs() -> u64 {
+pub fn now_unix_secs() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_secs()
 }
-
-/// Return a snapshot of all currently discovered peers.
-pub fn get_discovered_peers(peers: &DiscoveredPeers) -> Vec<DiscoveredPeer> {
-    peers.read().values().cloned().collect()
-}
@@ -0,0 +1,170 @@
+//! Shared-secret sync groups.
+//!
+//! A sync group is a name plus a secret configured identically on every
+//! device of a team. Both are folded into one HMAC key ([`SyncGroup`]) that
+//! is used twice:
+//!
+//! - every discovery beacon carries a tag over its payload, and
+//!   [`super::protocol::decode_beacon`] drops beacons whose tag does not
+//!   verify, so devices of other groups never show up as peers;
+//! - `Hello`/`Welcome` carry a proof over the sender's device id and the
+//!   handshake hash of the encrypted session (see [`super::secure`]), so a
+//!   TCP session is refused unless both ends know the same secret and a
+//!   proof cannot be replayed into another session.
+//!
+//! Devices that leave both fields empty form the default group, which
+//! behaves like an open network as before.
+
+use base64::Engine;
+use ring::hmac;
+
+use crate::runtime::config::Config;
+
+/// Domain-separation label for the group key derivation.
+const GROUP_KEY_LABEL: &[u8] = b"RSCV_LAN_GROUP/1";
+
+/// Length in bytes of the tag appended to each beacon.
+pub const BEACON_TAG_LEN: usize = 32;
+
+/// HMAC key shared by all members of a sync group.
+///
+/// Cheap enough to clone into every task that needs it.
+#[derive(Clone)]
+pub struct SyncGroup {
+    name: String,
+    key: hmac::Key,
+}
+
+impl SyncGroup {
+    /// Derive the group key from a name and secret.
+    pub fn new(name: &str, secret: &str) -> Self {
+        let label = hmac::Key::new(hmac::HMAC_SHA256, GROUP_KEY_LABEL);
+        // Length-prefix the name so ("ab", "c") and ("a", "bc") differ.
+        let mut material = Vec::with_capacity(8 + name.len() + secret.len());
+        material.extend_from_slice(&(name.len() as u64).to_be_bytes());
+        material.extend_from_slice(name.as_bytes());
+        material.extend_from_slice(secret.as_bytes());
+        let derived = hmac::sign(&label, &material);
+        Self {
+            name: name.to_string(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref()),
+        }
+    }
+
+    /// The group configured in `lan_group_name` / `lan_group_secret`.
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.lan_group_name.trim(), &config.lan_group_secret)
+    }
+
+    /// Group name for log messages (`"default"` when unset).
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            "default"
+        } else {
+            &self.name
+        }
+    }
+
+    /// Tag for a beacon payload.
+    pub fn beacon_tag(&self, payload: &[u8]) -> [u8; BEACON_TAG_LEN] {
+        let tag = hmac::sign(&self.key, &beacon_message(payload));
+        let mut out = [0u8; BEACON_TAG_LEN];
+        out.copy_from_slice(tag.as_ref());
+        out
+    }
+
+    /// `true` when `tag` was produced by a member of this group.
+    pub fn verify_beacon(&self, payload: &[u8], tag: &[u8]) -> bool {
+        // `hmac::verify` compares in constant time.
+        hmac::verify(&self.key, &beacon_message(payload), tag).is_ok()
+    }
+
+    /// Base64 membership proof sent in `Hello`/`Welcome` by `device_id`,
+    /// bound to the session's handshake hash `binding`.
+    pub fn member_proof(&self, device_id: &str, binding: &str) -> String {
+        let tag = hmac::sign(&self.key, member_message(device_id, binding).as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(tag.as_ref())
+    }
+
+    /// Check a proof produced by [`member_proof`](Self::member_proof).
+    pub fn verify_member_proof(&self, device_id: &str, binding: &str, proof: &str) -> bool {
+        let Ok(tag) = base64::engine::general_purpose::STANDARD.decode(proof) else {
+            return false;
+        };
+        hmac::verify(
+            &self.key,
+            member_message(device_id, binding).as_bytes(),
+            &tag,
+        )
+        .is_ok()
+    }
+}
+
+fn beacon_message(payload: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(7 + payload.len());
+    msg.extend_from_slice(b"beacon|");
+    msg.extend_from_slice(payload);
+    msg
+}
+
+fn member_message(device_id: &str, binding: &str) -> String {
+    format!("member|{}|{}", device_id, binding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = br#"{"device_id":"a1b2"}"#;
+
+    #[test]
+    fn beacons_verify_only_within_the_group() {
+        let group = SyncGroup::new("office", "s3cret");
+        let tag = group.beacon_tag(PAYLOAD);
+        assert!(SyncGroup::new("office", "s3cret").verify_beacon(PAYLOAD, &tag));
+
+        assert!(!SyncGroup::new("office", "other").verify_beacon(PAYLOAD, &tag));
+        assert!(!SyncGroup::new("home", "s3cret").verify_beacon(PAYLOAD, &tag));
+        // The name and secret are not just concatenated.
+        assert!(!SyncGroup::new("offices", "3cret").verify_beacon(PAYLOAD, &tag));
+
+        let mut tampered = tag;
+        tampered[0] ^= 1;
+        assert!(!group.verify_beacon(PAYLOAD, &tampered));
+        assert!(!group.verify_beacon(br#"{"device_id":"a1b3"}"#, &tag));
+        assert!(!group.verify_beacon(PAYLOAD, &tag[..16]));
+    }
+
+    #[test]
+    fn member_proofs_verify_only_within_the_group_and_session() {
+        let group = SyncGroup::new("office", "s3cret");
+        let proof = group.member_proof("a1b2", "handshake-hash");
+        assert!(SyncGroup::new("office", "s3cret").verify_member_proof(
+            "a1b2",
+            "handshake-hash",
+            &proof
+        ));
+
+        assert!(!SyncGroup::new("office", "other").verify_member_proof(
+            "a1b2",
+            "handshake-hash",
+            &proof
+        ));
+        assert!(!SyncGroup::new("home", "s3cret").verify_member_proof(
+            "a1b2",
+            "handshake-hash",
+            &proof
+        ));
+        // Neither another device nor another session can reuse the proof.
+        assert!(!group.verify_member_proof("c3d4", "handshake-hash", &proof));
+        assert!(!group.verify_member_proof("a1b2", "other-session", &proof));
+
+        let mut tampered = base64::engine::general_purpose::STANDARD
+            .decode(&proof)
+            .unwrap();
+        tampered[0] ^= 1;
+        let tampered = base64::engine::general_purpose::STANDARD.encode(tampered);
+        assert!(!group.verify_member_proof("a1b2", "handshake-hash", &tampered));
+        assert!(!group.verify_member_proof("a1b2", "handshake-hash", "not base64!"));
+    }
+}
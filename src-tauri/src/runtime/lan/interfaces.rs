@@ -0,0 +1,303 @@
+//! Local network interfaces used for LAN discovery.
+//!
+//! On hosts with Docker bridges, VPN adapters and Wi-Fi at once, the limited
+//! broadcast (`255.255.255.255`) leaves through one arbitrary interface.
+//! Discovery therefore enumerates the interfaces, sends a subnet-directed
+//! broadcast (and an IPv6 multicast) on each eligible one, and tags every
+//! peer with the interface its beacon arrived on.
+//!
+//! `lan_interfaces` in the config narrows the set. Each entry is an
+//! interface name (`eth0`, or a prefix such as `docker*`) or a CIDR
+//! (`192.168.1.0/24`); a leading `!` excludes matches instead. When any
+//! include entry is present, only interfaces matching one are used.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use if_addrs::IfAddr;
+
+use crate::runtime::config::Config;
+
+/// How long an interface enumeration is reused before the next one
+/// (seconds), so adapters coming and going are picked up.
+const INTERFACE_REFRESH_SECS: u64 = 10;
+
+// ────────────────────────────────────────────────────────────────────────────
+// Filter
+// ────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Name(String),
+    Prefix(String),
+    Cidr(IpAddr, u8),
+}
+
+/// One `lan_interfaces` entry.
+#[derive(Debug, Clone)]
+pub struct InterfaceRule {
+    exclude: bool,
+    pattern: Pattern,
+}
+
+impl InterfaceRule {
+    /// Parse `eth0`, `docker*`, `10.0.0.0/8` or `fd00::/8`, each optionally
+    /// prefixed with `!`.
+    pub fn parse(entry: &str) -> Result<Self> {
+        let entry = entry.trim();
+        let (exclude, body) = match entry.strip_prefix('!') {
+            Some(rest) => (true, rest.trim()),
+            None => (false, entry),
+        };
+        let pattern = if let Some((net, len)) = body.split_once('/') {
+            let net: IpAddr = net
+                .parse()
+                .map_err(|_| anyhow!("invalid network {:?}", net))?;
+            let max_len = if net.is_ipv4() { 32 } else { 128 };
+            let len = len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| anyhow!("invalid prefix length {:?}", len))?;
+            Pattern::Cidr(net, len)
+        } else if let Some(prefix) = body.strip_suffix('*') {
+            Pattern::Prefix(prefix.to_string())
+        } else {
+            Pattern::Name(body.to_string())
+        };
+        if matches!(&pattern, Pattern::Name(name) if name.is_empty()) {
+            return Err(anyhow!("empty interface name"));
+        }
+        Ok(Self { exclude, pattern })
+    }
+
+    fn matches(&self, name: &str, ip: IpAddr) -> bool {
+        match &self.pattern {
+            Pattern::Name(n) => n == name,
+            Pattern::Prefix(p) => name.starts_with(p.as_str()),
+            Pattern::Cidr(net, len) => in_prefix(ip, *net, *len),
+        }
+    }
+}
+
+/// The interface selection configured in `lan_interfaces`.
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceFilter {
+    rules: Vec<InterfaceRule>,
+}
+
+impl InterfaceFilter {
+    /// Entries are validated when settings are saved; any that still fail
+    /// to parse are ignored.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            rules: config
+                .lan_interfaces
+                .iter()
+                .filter_map(|entry| InterfaceRule::parse(entry).ok())
+                .collect(),
+        }
+    }
+
+    /// `true` when every interface is allowed.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the interface `name` with address `ip` may be used.
+    pub fn allows(&self, name: &str, ip: IpAddr) -> bool {
+        let mut includes = self.rules.iter().filter(|r| !r.exclude).peekable();
+        let included = includes.peek().is_none() || includes.any(|r| r.matches(name, ip));
+        included && !self.rules.iter().any(|r| r.exclude && r.matches(name, ip))
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Interfaces
+// ────────────────────────────────────────────────────────────────────────────
+
+/// One address of a local interface.
+#[derive(Debug, Clone)]
+pub struct LocalInterface {
+    pub name: String,
+    /// OS interface index, used as the IPv6 scope id (`0` when unknown).
+    pub index: u32,
+    pub ip: IpAddr,
+    pub prefix_len: u8,
+    /// Subnet-directed broadcast address (IPv4 only).
+    pub broadcast: Option<Ipv4Addr>,
+}
+
+impl LocalInterface {
+    /// Whether a datagram from `src` came in through this interface: a
+    /// link-local IPv6 source by its scope id, anything else by subnet.
+    pub fn contains(&self, src: &SocketAddr) -> bool {
+        match src {
+            SocketAddr::V6(v6) if v6.ip().is_unicast_link_local() => {
+                self.ip.is_ipv6() && self.index != 0 && self.index == v6.scope_id()
+            }
+            _ => in_prefix(src.ip().to_canonical(), self.ip, self.prefix_len),
+        }
+    }
+
+    /// `true` for the link-local IPv6 address IPv6 beacons are sent from.
+    pub fn is_ipv6_link_local(&self) -> bool {
+        matches!(self.ip, IpAddr::V6(v6) if v6.is_unicast_link_local())
+    }
+}
+
+/// Non-loopback interface addresses allowed by `filter`. Enumeration
+/// errors yield an empty list.
+pub fn eligible_interfaces(filter: &InterfaceFilter) -> Vec<LocalInterface> {
+    if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|iface| !iface.is_loopback() && filter.allows(&iface.name, iface.ip()))
+        .map(|iface| {
+            let (prefix_len, broadcast) = match &iface.addr {
+                IfAddr::V4(v4) => (v4.prefixlen, v4.broadcast),
+                IfAddr::V6(v6) => (v6.prefixlen, None),
+            };
+            LocalInterface {
+                ip: iface.ip(),
+                index: iface.index.unwrap_or(0),
+                name: iface.name,
+                prefix_len,
+                broadcast,
+            }
+        })
+        .collect()
+}
+
+/// The eligible interfaces, re-enumerated every
+/// [`INTERFACE_REFRESH_SECS`].
+pub struct InterfaceCache {
+    filter: InterfaceFilter,
+    interfaces: Vec<LocalInterface>,
+    refreshed: Option<Instant>,
+}
+
+impl InterfaceCache {
+    pub fn new(filter: InterfaceFilter) -> Self {
+        Self {
+            filter,
+            interfaces: Vec::new(),
+            refreshed: None,
+        }
+    }
+
+    pub fn filter(&self) -> &InterfaceFilter {
+        &self.filter
+    }
+
+    pub fn get(&mut self) -> &[LocalInterface] {
+        let stale = self
+            .refreshed
+            .is_none_or(|at| at.elapsed() >= Duration::from_secs(INTERFACE_REFRESH_SECS));
+        if stale {
+            self.interfaces = eligible_interfaces(&self.filter);
+            self.refreshed = Some(Instant::now());
+        }
+        &self.interfaces
+    }
+
+    /// Name of the eligible interface a datagram from `src` came in
+    /// through. With a filter configured, a datagram on none of them is to
+    /// be ignored.
+    pub fn interface_of(&mut self, src: &SocketAddr) -> Option<String> {
+        self.get()
+            .iter()
+            .find(|iface| iface.contains(src))
+            .map(|iface| iface.name.clone())
+    }
+}
+
+/// Whether `ip` lies within `net/len`. Addresses of different families
+/// never match.
+fn in_prefix(ip: IpAddr, net: IpAddr, len: u8) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(len)).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(len)).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn configured(entries: &[&str]) -> InterfaceFilter {
+        InterfaceFilter::from_config(&Config {
+            lan_interfaces: entries.iter().map(|entry| entry.to_string()).collect(),
+            ..Config::default()
+        })
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn an_empty_filter_allows_every_interface() {
+        let filter = configured(&[]);
+        assert!(filter.is_empty());
+        assert!(filter.allows("eth0", ip("192.168.1.20")));
+        assert!(filter.allows("docker0", ip("fd00::1")));
+    }
+
+    #[test]
+    fn ipv4_ranges_select_interfaces_by_address() {
+        let filter = configured(&["192.168.1.0/24"]);
+        assert!(filter.allows("eth0", ip("192.168.1.20")));
+        assert!(!filter.allows("eth0", ip("192.168.2.20")));
+        assert!(!filter.allows("eth0", ip("fe80::1")));
+
+        let filter = configured(&["!172.16.0.0/12"]);
+        assert!(filter.allows("wlan0", ip("10.0.0.5")));
+        assert!(!filter.allows("docker0", ip("172.17.0.1")));
+
+        assert!(configured(&["0.0.0.0/0"]).allows("eth0", ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn ipv6_ranges_select_interfaces_by_address() {
+        let filter = configured(&["fd00::/8"]);
+        assert!(filter.allows("tun0", ip("fd12:3456::1")));
+        assert!(!filter.allows("eth0", ip("2001:db8::1")));
+        assert!(!filter.allows("eth0", ip("10.0.0.5")));
+    }
+
+    #[test]
+    fn names_and_prefixes_select_interfaces() {
+        let filter = configured(&["eth0", "wl*", "!wlan1"]);
+        assert!(filter.allows("eth0", ip("10.0.0.5")));
+        assert!(filter.allows("wlp3s0", ip("10.0.0.6")));
+        assert!(!filter.allows("wlan1", ip("10.0.0.7")));
+        assert!(!filter.allows("eth1", ip("10.0.0.8")));
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        for entry in [
+            "192.168.1.0/33",
+            "fd00::/129",
+            "192.168.1/24",
+            "10.0.0.0/abc",
+            "",
+            "!",
+        ] {
+            assert!(InterfaceRule::parse(entry).is_err(), "{:?}", entry);
+        }
+        // Invalid entries that slipped into the config are ignored.
+        assert!(configured(&["192.168.1.0/33"]).is_empty());
+    }
+}
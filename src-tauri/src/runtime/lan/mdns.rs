@@ -0,0 +1,254 @@
+//! Multicast DNS / DNS-SD discovery for LAN peers.
+//!
+//! An alternative to the UDP broadcast beacon of [`super::discovery`] for
+//! networks that filter broadcast or hosts with several NICs, where the
+//! limited broadcast leaves through one arbitrary interface. mDNS is sent
+//! on every interface and is usually let through where broadcast is not.
+//!
+//! Every peer registers a [`MDNS_SERVICE_TYPE`] service whose SRV record
+//! points at its TCP host port and whose TXT record carries the device id
+//! and name plus a sync group tag, and browses for the same service type.
+//! Resolved services are verified like UDP beacons and fed into the same
+//! [`super::registry::PeerRegistry`], so the connector does not
+//! care which backend found a peer. Peers are tagged with, and filtered
+//! by, the local interface their address belongs to, as for beacons (see
+//! [`super::interfaces`]).
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+};
+
+use base64::Engine;
+use log::Level;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use tokio::{
+    sync::mpsc,
+    time::{interval, Duration},
+};
+use tokio_util::sync::CancellationToken;
+
+use super::discovery::now_unix_secs;
+use super::group::SyncGroup;
+use super::interfaces::{InterfaceCache, InterfaceFilter};
+use super::peer::PeerContext;
+use super::protocol::{DiscoveryBeacon, DISCOVERY_INTERVAL_SECS, MDNS_SERVICE_TYPE};
+use super::registry::emit_peer_list;
+use crate::runtime::{RuntimeEvent, RuntimeLogEvent};
+
+/// TXT keys of the advertised service.
+const TXT_DEVICE_ID: &str = "id";
+const TXT_DEVICE_NAME: &str = "name";
+const TXT_GROUP_TAG: &str = "tag";
+
+/// Advertise this device over mDNS and browse for other peers.
+///
+/// Resolved peers are re-announced into the peer registry every
+/// [`DISCOVERY_INTERVAL_SECS`] until their service goes away, so they
+/// expire the same way as peers that stop sending UDP beacons.
+///
+/// The device id doubles as the service instance name; identity, sync
+/// group, peer registry and trust store come from `ctx`. Peers whose address is
+/// on no interface allowed by `filter` are ignored when a filter is set.
+pub async fn run_mdns_discovery(
+    ctx: PeerContext,
+    tcp_port: u16,
+    filter: InterfaceFilter,
+    cancel: CancellationToken,
+) {
+    let PeerContext {
+        device_id,
+        device_name,
+        group,
+        events,
+        trust,
+        registry,
+        ..
+    } = ctx;
+    let daemon = match ServiceDaemon::new() {
+        Ok(d) => d,
+        Err(e) => {
+            emit_log(
+                &events,
+                Level::Error,
+                format!("LAN mDNS discovery failed to start: {}", e),
+            )
+            .await;
+            return;
+        }
+    };
+
+    let tag = service_tag(&group, &device_id, tcp_port, &device_name);
+    let properties = [
+        (TXT_DEVICE_ID, device_id.as_str()),
+        (TXT_DEVICE_NAME, device_name.as_str()),
+        (TXT_GROUP_TAG, tag.as_str()),
+    ];
+    let host_name = format!("rscv-{}.local.", device_id);
+    let registered = ServiceInfo::new(
+        MDNS_SERVICE_TYPE,
+        &device_id,
+        &host_name,
+        "",
+        tcp_port,
+        &properties[..],
+    )
+    .map(ServiceInfo::enable_addr_auto)
+    .and_then(|info| daemon.register(info));
+    if let Err(e) = registered {
+        emit_log(
+            &events,
+            Level::Error,
+            format!("LAN mDNS service registration failed: {}", e),
+        )
+        .await;
+        let _ = daemon.shutdown();
+        return;
+    }
+
+    let browser = match daemon.browse(MDNS_SERVICE_TYPE) {
+        Ok(b) => b,
+        Err(e) => {
+            emit_log(
+                &events,
+                Level::Error,
+                format!("LAN mDNS browse failed: {}", e),
+            )
+            .await;
+            let _ = daemon.shutdown();
+            return;
+        }
+    };
+
+    emit_log(
+        &events,
+        Level::Info,
+        format!(
+            "LAN mDNS discovery started (service={}, tcp_port={}, group={})",
+            MDNS_SERVICE_TYPE,
+            tcp_port,
+            group.display_name()
+        ),
+    )
+    .await;
+
+    // Resolved services by full name, refreshed into `registry` on every tick.
+    let mut resolved: HashMap<String, ResolvedPeer> = HashMap::new();
+    let mut interfaces = InterfaceCache::new(filter);
+    let mut tick = interval(Duration::from_secs(DISCOVERY_INTERVAL_SECS));
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tick.tick() => {
+                let now = now_unix_secs();
+                for peer in resolved.values() {
+                    registry.upsert_discovered(&peer.beacon, &peer.ip, peer.interface.as_deref(), now);
+                }
+                if registry.prune_discovered(now) > 0 {
+                    emit_peer_list(&registry, &trust, &events).await;
+                }
+            }
+            event = browser.recv_async() => {
+                let Ok(event) = event else {
+                    emit_log(&events, Level::Warn, "LAN mDNS daemon stopped unexpectedly").await;
+                    break;
+                };
+                match event {
+                    ServiceEvent::ServiceResolved(info) => {
+                        let Some((beacon, addr)) = peer_from_service(&info, &group) else {
+                            continue;
+                        };
+                        if beacon.device_id == device_id {
+                            continue;
+                        }
+                        let interface = interfaces.interface_of(&SocketAddr::new(addr, beacon.tcp_port));
+                        if interface.is_none() && !interfaces.filter().is_empty() {
+                            continue;
+                        }
+                        let ip = addr.to_string();
+                        if registry.upsert_discovered(&beacon, &ip, interface.as_deref(), now_unix_secs()) {
+                            emit_log(
+                                &events,
+                                Level::Info,
+                                format!(
+                                    "LAN peer discovered/updated via mDNS: {} ({}) at {}:{}",
+                                    beacon.device_name, beacon.device_id, ip, beacon.tcp_port,
+                                ),
+                            )
+                            .await;
+                            emit_peer_list(&registry, &trust, &events).await;
+                        }
+                        resolved.insert(
+                            info.get_fullname().to_string(),
+                            ResolvedPeer { beacon, ip, interface },
+                        );
+                    }
+                    ServiceEvent::ServiceRemoved(_, fullname) => {
+                        // The peer expires once no longer refreshed.
+                        resolved.remove(&fullname);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let _ = daemon.shutdown();
+    emit_log(&events, Level::Debug, "LAN mDNS discovery stopped").await;
+}
+
+/// A resolved service, re-announced into the registry on every tick.
+struct ResolvedPeer {
+    beacon: DiscoveryBeacon,
+    ip: String,
+    interface: Option<String>,
+}
+
+/// Base64 group tag over the advertised identity and port.
+fn service_tag(group: &SyncGroup, device_id: &str, tcp_port: u16, device_name: &str) -> String {
+    let tag = group.beacon_tag(&service_payload(device_id, tcp_port, device_name));
+    base64::engine::general_purpose::STANDARD.encode(tag)
+}
+
+/// Bytes covered by the TXT record tag. The `mdns|` prefix keeps them apart
+/// from UDP beacon payloads, which are JSON.
+fn service_payload(device_id: &str, tcp_port: u16, device_name: &str) -> Vec<u8> {
+    format!("mdns|{}|{}|{}", device_id, tcp_port, device_name).into_bytes()
+}
+
+/// The beacon and address a resolved service stands for, if it carries a
+/// valid tag for our sync group.
+fn peer_from_service(info: &ServiceInfo, group: &SyncGroup) -> Option<(DiscoveryBeacon, IpAddr)> {
+    let device_id = info.get_property_val_str(TXT_DEVICE_ID)?;
+    let device_name = info.get_property_val_str(TXT_DEVICE_NAME).unwrap_or("");
+    let tag = base64::engine::general_purpose::STANDARD
+        .decode(info.get_property_val_str(TXT_GROUP_TAG)?)
+        .ok()?;
+    let tcp_port = info.get_port();
+    if !group.verify_beacon(&service_payload(device_id, tcp_port, device_name), &tag) {
+        return None;
+    }
+    // Prefer IPv4 when the peer has several addresses. Resolved addresses
+    // carry no scope id, so link-local IPv6 ones cannot be dialed.
+    let ip = info
+        .get_addresses()
+        .iter()
+        .filter(|ip| !matches!(ip, IpAddr::V6(v6) if v6.is_unicast_link_local()))
+        .min_by_key(|ip| !ip.is_ipv4())?;
+    Some((
+        DiscoveryBeacon {
+            device_id: device_id.to_string(),
+            device_name: device_name.to_string(),
+            tcp_port,
+            seq: 0,
+        },
+        *ip,
+    ))
+}
+
+async fn emit_log(events: &mpsc::Sender<RuntimeEvent>, level: Level, message: impl Into<String>) {
+    let _ = events
+        .send(RuntimeEvent::Log(RuntimeLogEvent::new(level, message)))
+        .await;
+}
@@ -15,7 +15,7 @@
 //!  │  │ broadcaster  │  │  (discovery) │  │  (accepts incoming)  │ │
 //!  │  └──────────────┘  └──────┬───────┘  └──────────────────────┘ │
 //!  │                           │                                    │
-//!  │                    DiscoveredPeers                              │
+//!  │                     PeerRegistry                                 │
 //!  │                           │                                    │
 //!  │                  ┌────────▼─────────┐                          │
 //!  │                  │  peer_connector  │  (connects to new peers) │
@@ -38,44 +38,105 @@
 //! exactly one TCP session is established between any two peers without
 //! any additional negotiation protocol.
 //!
+//! Where UDP broadcast does not get through, `lan_static_peers` lists
+//! addresses that are dialed right away. Their device id is only known
+//! after the handshake, so the same rule is applied then: a session dialed
+//! by the greater id replaces any other, and one dialed the other way is
+//! only kept while it is the only session with that peer.
+//!
 //! ## Security Notice
 //!
-//! **LAN mode currently has no authentication or encryption.** Any device
-//! on the same network segment can discover and exchange clipboard data
-//! with this peer. A shared-secret / pairing mechanism is planned for a
-//! future release. Until then, only use LAN mode on trusted networks.
+//! Any device on the same network segment can discover this peer and open a
+//! TCP session with it, but clipboard data is only exchanged with peers that
+//! have been **paired** by entering a PIN shown on the other device (see
+//! [`pairing`]) or approved by hand. Unknown peers stay *pending* until the
+//! user pairs, approves or blocks them; blocked peers are never dialed and
+//! their sessions are refused. Both lists are persisted in the trust store
+//! next to the config file. The PIN is checked with a password-authenticated
+//! key exchange, so it cannot be cracked from a recorded pairing.
+//!
+//! Every TCP session is encrypted and mutually authenticated with a Noise
+//! handshake keyed by a long-lived per-device key (see [`secure`]); each
+//! pairing pins the remote's key. Discovery beacons are plain UDP but are
+//! tagged with the sync group key (see [`group`]): devices only discover and
+//! connect to members of the same group.
+//!
+//! ## Discovery backends
+//!
+//! Peers are found by UDP broadcast beacons ([`discovery`]), by mDNS /
+//! DNS-SD ([`mdns`]) or both, as chosen by `lan_discovery` in the config.
+//! Either backend feeds the same [`registry::PeerRegistry`].
+//!
+//! LAN mode is dual-stack: beacons are broadcast over IPv4 and multicast to
+//! a link-local IPv6 group, and the TCP host listens on `[::]` (falling
+//! back to `0.0.0.0` where IPv6 is unavailable), so IPv6-only networks
+//! work too. Link-local peers are dialed with their scope id.
+//!
+//! ## Relaying
+//!
+//! Not every pair of peers may reach each other. With `lan_relay` set, a
+//! peer forwards clipboard items between its sessions; items carry their
+//! origin and a hop count and are delivered once per peer (see [`relay`]).
+//!
+//! ## Peer registry
+//!
+//! [`registry::PeerRegistry`] merges what discovery knows about each peer
+//! with the state of its connection: which task dials it, how far the
+//! handshake got, the session's role and health, and link statistics. It
+//! drives the peer list shown in the UI and the connector's dial claims,
+//! which it clears when a peer goes away so the peer is redialed once it
+//! reappears.
 
 pub mod discovery;
+pub mod group;
+pub mod interfaces;
+pub mod mdns;
+pub mod pairing;
 pub mod peer;
 pub mod protocol;
+pub mod registry;
+pub mod relay;
+pub mod secure;
+pub mod transfer;
 
 use std::{
-    collections::HashSet,
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr},
+    path::Path,
     sync::{atomic::AtomicBool, Arc},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use log::Level;
 use parking_lot::Mutex as ParkingMutex;
 use tokio::{
-    net::TcpListener,
-    sync::{broadcast, mpsc},
+    sync::{broadcast, mpsc, oneshot},
     task::JoinHandle,
     time::{sleep, Duration},
 };
 use tokio_util::sync::CancellationToken;
-use uuid::Uuid;
 
 use discovery::{
-    bind_reusable_udp, get_discovered_peers, new_peer_map, run_beacon_broadcaster,
-    run_beacon_listener, DiscoveredPeers,
+    bind_multicast_udp_v6, bind_reusable_udp, run_beacon_broadcaster, run_beacon_listener,
 };
-use peer::{run_tcp_client, run_tcp_host_on_listener};
-use protocol::{DiscoveredPeer, DEFAULT_DISCOVERY_PORT, DEFAULT_TCP_PORT};
+use group::SyncGroup;
+use interfaces::InterfaceFilter;
+use mdns::run_mdns_discovery;
+use pairing::{PairingState, PeerAccess, TrustStore};
+use peer::{
+    bind_tcp_host, emit_access_changed, new_session_handles, run_tcp_client,
+    run_tcp_host_on_listener, DialTarget, PeerContext, SessionCommand, SessionHandles,
+};
+use protocol::DEFAULT_DISCOVERY_PORT;
+use registry::{link_stats_json, run_link_stats_publisher, PeerRegistry};
+use relay::new_seen_items;
 
-use super::clipboard::{start_clipboard_monitor, start_clipboard_setter};
+use super::backend::ClipboardBackend;
+use super::clipboard::{start_clipboard_monitor, start_clipboard_setter, ClipboardState};
 use super::config::Config;
-use super::messages::{ClipboardBroadcastPayload, ClipboardUpdate};
+use super::identity::DeviceIdentity;
+use super::messages::ClipboardItem;
+use super::policy::SyncPolicy;
 use super::{RuntimeEvent, RuntimeLogEvent};
 
 // ────────────────────────────────────────────────────────────────────────────
@@ -88,9 +149,46 @@ const CONNECTOR_SCAN_INTERVAL_SECS: u64 = 4;
 
 /// Security warning emitted when LAN mode starts. This is surfaced in the
 /// log stream so it appears in both the UI log panel and the log file.
-const LAN_SECURITY_WARNING: &str = "⚠ LAN mode has no authentication or encryption. \
-     Only use on trusted networks. Any device on this network segment \
-     can discover this peer and exchange clipboard data.";
+const LAN_SECURITY_WARNING: &str = "⚠ LAN mode announces this device to \
+     everyone on the network segment. Peer traffic is encrypted and \
+     clipboard data is only exchanged with paired or approved peers.";
+
+/// Which discovery backends run, from `lan_discovery` in the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoveryMode {
+    /// UDP broadcast beacons only (the default).
+    Broadcast,
+    /// mDNS / DNS-SD only.
+    Mdns,
+    /// Both, for mixed networks.
+    Both,
+}
+
+impl DiscoveryMode {
+    pub fn from_config(config: &Config) -> Self {
+        match config.lan_discovery.as_str() {
+            "mdns" => DiscoveryMode::Mdns,
+            "both" => DiscoveryMode::Both,
+            _ => DiscoveryMode::Broadcast,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiscoveryMode::Broadcast => "broadcast",
+            DiscoveryMode::Mdns => "mdns",
+            DiscoveryMode::Both => "both",
+        }
+    }
+
+    fn broadcast(self) -> bool {
+        self != DiscoveryMode::Mdns
+    }
+
+    fn mdns(self) -> bool {
+        self != DiscoveryMode::Broadcast
+    }
+}
 
 // ────────────────────────────────────────────────────────────────────────────
 // Public entry point
@@ -111,6 +209,154 @@ pub struct LanTasks {
     /// Shared with the connector task via `Arc` so it can push new handles
     /// as peers are discovered.
     pub dynamic_handles: Arc<ParkingMutex<Vec<JoinHandle<()>>>>,
+    /// Handle for UI-driven operations on the running LAN mode.
+    pub control: LanControl,
+}
+
+/// Cheap-to-clone handle the [`RuntimeWorker`](super::RuntimeWorker) uses to
+/// drive pairing and the access lists on a running LAN mode.
+#[derive(Clone)]
+pub struct LanControl {
+    trust: TrustStore,
+    pairing: PairingState,
+    sessions: SessionHandles,
+    registry: PeerRegistry,
+    clipboard: ClipboardState,
+    events: mpsc::Sender<RuntimeEvent>,
+}
+
+impl LanControl {
+    /// Generate a PIN for a remote device to enter. Any previously shown PIN
+    /// is invalidated.
+    pub fn begin_pairing(&self) -> Result<String> {
+        self.pairing.begin()
+    }
+
+    /// Pair with the connected peer `device_id` using the PIN it displays.
+    ///
+    /// Resolves once the remote has accepted (both sides then hold a trust
+    /// record) or rejected the request.
+    pub async fn pair_with_peer(&self, device_id: &str, pin: &str) -> Result<()> {
+        let pin = pin.trim();
+        if pin.is_empty() {
+            return Err(anyhow!("PIN cannot be empty"));
+        }
+        let session = self.session(device_id)?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        session
+            .send(SessionCommand::Pair {
+                pin: pin.to_string(),
+                reply: reply_tx,
+            })
+            .await
+            .map_err(|_| anyhow!("session with {} has closed", device_id))?;
+
+        reply_rx
+            .await
+            .map_err(|_| anyhow!("session with {} closed during pairing", device_id))?
+    }
+
+    /// Trust a pending peer without a PIN. The peer must be connected so
+    /// that the key it authenticated with can be pinned.
+    pub async fn approve_peer(&self, device_id: &str) -> Result<()> {
+        let session = self.session(device_id)?;
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        session
+            .send(SessionCommand::Approve { reply: reply_tx })
+            .await
+            .map_err(|_| anyhow!("session with {} has closed", device_id))?;
+
+        reply_rx
+            .await
+            .map_err(|_| anyhow!("session with {} closed during approval", device_id))?
+    }
+
+    /// Remove a peer from the trusted or blocked list, returning it to
+    /// pending.
+    pub async fn revoke_peer(&self, device_id: &str) -> Result<()> {
+        if !self.trust.revoke(device_id)? {
+            return Err(anyhow!("peer {} is neither trusted nor blocked", device_id));
+        }
+        emit_access_changed(&self.trust, &self.registry, &self.events).await;
+        Ok(())
+    }
+
+    /// Block a peer and end any live session with it.
+    pub async fn block_peer(&self, device_id: &str) -> Result<()> {
+        let name = self
+            .registry
+            .device_name(device_id)
+            .or_else(|| self.trust.known_name(device_id))
+            .unwrap_or_default();
+        self.trust.block(device_id, &name)?;
+
+        if let Ok(session) = self.session(device_id) {
+            let _ = session
+                .send(SessionCommand::Close {
+                    reason: "peer blocked",
+                })
+                .await;
+        }
+        emit_access_changed(&self.trust, &self.registry, &self.events).await;
+        Ok(())
+    }
+
+    /// Send the current clipboard content to the connected, trusted peer
+    /// `device_id` alone; it is queued on that peer's session and not
+    /// relayed any further.
+    pub async fn send_clipboard_to(&self, device_id: &str) -> Result<()> {
+        if self.trust.access(device_id) != PeerAccess::Trusted {
+            return Err(anyhow!("peer {} is not paired", device_id));
+        }
+        if !self.clipboard.policy.sends_to(device_id) {
+            return Err(anyhow!(
+                "the sync direction does not allow sending to {}",
+                device_id
+            ));
+        }
+        let outbox = self
+            .sessions
+            .lock()
+            .get(device_id)
+            .map(|h| h.outbox.clone())
+            .ok_or_else(|| anyhow!("peer {} is not connected", device_id))?;
+        let item = self
+            .clipboard
+            .addressed_to(device_id)
+            .ok_or_else(|| anyhow!("the clipboard is empty"))?;
+        outbox
+            .send(item)
+            .await
+            .map_err(|_| anyhow!("session with {} has closed", device_id))
+    }
+
+    /// JSON snapshot for the device manager:
+    /// `{ "peers": [...], "trusted": [...], "blocked": [...] }`, where each
+    /// discovered or connected peer carries its access status.
+    pub fn devices_json(&self) -> String {
+        serde_json::json!({
+            "peers": self.registry.peer_list(&self.trust),
+            "trusted": self.trust.list(),
+            "blocked": self.trust.blocked(),
+        })
+        .to_string()
+    }
+
+    /// JSON-serialised link statistics of every peer we held a session
+    /// with (see [`registry`]).
+    pub fn link_stats_json(&self) -> String {
+        link_stats_json(&self.registry)
+    }
+
+    fn session(&self, device_id: &str) -> Result<mpsc::Sender<SessionCommand>> {
+        self.sessions
+            .lock()
+            .get(device_id)
+            .map(|h| h.commands.clone())
+            .ok_or_else(|| anyhow!("peer {} is not connected", device_id))
+    }
 }
 
 impl LanTasks {
@@ -148,9 +394,12 @@ impl LanTasks {
 
 /// Start all LAN mode tasks.
 ///
-/// Ports are always the built-in defaults ([`DEFAULT_DISCOVERY_PORT`] and
-/// [`DEFAULT_TCP_PORT`]) so that all peers on the same LAN segment agree
-/// without any user configuration.
+/// Ports come from `lan_discovery_port` and `lan_tcp_port`, which default
+/// to [`DEFAULT_DISCOVERY_PORT`] and [`protocol::DEFAULT_TCP_PORT`] so that
+/// all peers on the same LAN segment agree without any user configuration.
+/// The discovery port is bound with address reuse and so can be shared by
+/// several instances on one machine; a TCP port that is already taken is
+/// replaced by an ephemeral one, which beacons advertise.
 ///
 /// This function is **async** and performs the critical socket binds (UDP
 /// listener, TCP host) *before* spawning background tasks. If either bind
@@ -159,9 +408,14 @@ impl LanTasks {
 /// # Arguments
 ///
 /// * `config`              — application configuration (only `max_image_kb`
-///                            and `lan_device_name` are read here).
-/// * `device_name_override` — optional human-friendly name; if `None` or
-///                            empty the system hostname is used.
+///                            and the `lan_*` fields are read here; an
+///                            empty `lan_device_name` announces the system
+///                            hostname).
+/// * `data_dir`            — application data directory holding the trust
+///                            store.
+/// * `identity`            — persistent device identity to announce.
+/// * `policy`              — sync directions, shared with the worker.
+/// * `backend`             — clipboard the monitor and setter work on.
 /// * `events`              — runtime event channel shared with the main
 ///                            `RuntimeWorker`.
 /// * `cancel`              — parent cancellation token; we derive child
@@ -169,27 +423,36 @@ impl LanTasks {
 ///
 /// # Errors
 ///
-/// Returns an error if the UDP discovery socket or the TCP host listener
-/// cannot be bound. The caller should treat this as a startup failure and
-/// *not* transition to `Connected` state.
+/// Returns an error if the trust store cannot be read, or if the UDP
+/// discovery socket or the TCP host listener cannot be bound. The caller
+/// should treat this as a startup failure and *not* transition to
+/// `Connected` state.
 pub async fn start_lan_mode(
     config: &Config,
-    device_name_override: Option<String>,
+    data_dir: &Path,
+    identity: DeviceIdentity,
+    policy: SyncPolicy,
+    backend: Arc<dyn ClipboardBackend>,
     events: mpsc::Sender<RuntimeEvent>,
     cancel: CancellationToken,
 ) -> Result<LanTasks> {
-    let device_id = Uuid::new_v4().to_string();
-    let device_name = device_name_override
-        .filter(|s| !s.is_empty())
-        .unwrap_or_else(|| {
-            hostname::get()
-                .ok()
-                .and_then(|h| h.into_string().ok())
-                .unwrap_or_else(|| format!("RustSyncCV-{}", &device_id[..8]))
-        });
-
-    let discovery_port = DEFAULT_DISCOVERY_PORT;
-    let tcp_port = DEFAULT_TCP_PORT;
+    let device_id = identity.device_id().to_string();
+    let device_name = if config.lan_device_name.is_empty() {
+        hostname::get()
+            .ok()
+            .and_then(|h| h.into_string().ok())
+            .unwrap_or_else(|| format!("RustSyncCV-{}", &device_id[..8]))
+    } else {
+        config.lan_device_name.clone()
+    };
+
+    let discovery_port = match config.lan_discovery_port {
+        0 => DEFAULT_DISCOVERY_PORT,
+        port => port,
+    };
+    let group = SyncGroup::from_config(config);
+    let discovery = DiscoveryMode::from_config(config);
+    let interface_filter = InterfaceFilter::from_config(config);
 
     // ── Security warning ─────────────────────────────────────────────────
     let _ = events
@@ -199,23 +462,59 @@ pub async fn start_lan_mode(
         )))
         .await;
 
+    let trust = TrustStore::load(data_dir).context("failed to load LAN trust store")?;
+    let _ = events
+        .send(RuntimeEvent::LanTrustChanged(trust.to_json()))
+        .await;
+
     // ── Pre-bind critical sockets ────────────────────────────────────────
     // We bind *before* spawning any tasks so that the caller can report a
     // clean startup error instead of silently failing in the background.
 
-    let udp_listener_socket = bind_reusable_udp(discovery_port, &events)
-        .await
-        .ok_or_else(|| {
+    // IPv4 discovery is required; IPv6 is added when the host supports it.
+    let mut udp_listener_sockets = Vec::new();
+    if discovery.broadcast() {
+        let v4_addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, discovery_port));
+        let socket = bind_reusable_udp(v4_addr, &events).await.ok_or_else(|| {
             anyhow::anyhow!(
                 "failed to bind UDP discovery listener on port {}",
                 discovery_port
             )
         })?;
+        udp_listener_sockets.push(socket);
+        udp_listener_sockets
+            .extend(bind_multicast_udp_v6(discovery_port, &interface_filter, &events).await);
+    }
 
-    let tcp_bind_addr = format!("0.0.0.0:{}", tcp_port);
-    let tcp_listener = TcpListener::bind(&tcp_bind_addr)
-        .await
-        .context(format!("failed to bind TCP host on {}", tcp_bind_addr))?;
+    let tcp_listener = match bind_tcp_host(config.lan_tcp_port).await {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == ErrorKind::AddrInUse && config.lan_tcp_port != 0 => {
+            let _ = events
+                .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                    Level::Warn,
+                    format!(
+                        "LAN TCP port {} is in use, falling back to an ephemeral port",
+                        config.lan_tcp_port
+                    ),
+                )))
+                .await;
+            bind_tcp_host(0)
+                .await
+                .context("failed to bind TCP host on an ephemeral port")?
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context(format!(
+                "failed to bind TCP host on port {}",
+                config.lan_tcp_port
+            )))
+        }
+    };
+    // The port actually bound, advertised in beacons (differs from the
+    // configured one when that was 0 or taken).
+    let tcp_port = tcp_listener
+        .local_addr()
+        .context("failed to read TCP host address")?
+        .port();
 
     let _ = events
         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
@@ -229,11 +528,35 @@ pub async fn start_lan_mode(
 
     // ── Shared channels — same pattern as the existing WebSocket runtime ─
     let disable_flag = Arc::new(AtomicBool::new(false));
-    let (tx_out, _) = broadcast::channel::<ClipboardUpdate>(100);
-    let (tx_in, rx_in) = mpsc::channel::<ClipboardBroadcastPayload>(100);
-
-    // Discovered peers map — shared between listener and connector.
-    let peers = new_peer_map();
+    let clipboard = ClipboardState::new(device_id.clone(), policy);
+    let (tx_out, _) = broadcast::channel::<ClipboardItem>(100);
+    let (tx_in, rx_in) = mpsc::channel::<ClipboardItem>(100);
+
+    // Context shared by every peer session (host and client side).
+    let peer_ctx = PeerContext {
+        device_id: device_id.clone(),
+        device_name: device_name.clone(),
+        identity,
+        group: group.clone(),
+        tx_out: tx_out.clone(),
+        tx_in: tx_in.clone(),
+        events: events.clone(),
+        trust: trust.clone(),
+        pairing: PairingState::default(),
+        sessions: new_session_handles(),
+        relay: config.lan_relay,
+        seen: new_seen_items(),
+        clipboard: clipboard.clone(),
+        registry: PeerRegistry::default(),
+    };
+    let control = LanControl {
+        trust,
+        pairing: peer_ctx.pairing.clone(),
+        sessions: peer_ctx.sessions.clone(),
+        registry: peer_ctx.registry.clone(),
+        clipboard: clipboard.clone(),
+        events: events.clone(),
+    };
 
     // Dynamic client handles — shared between connector and LanTasks.
     let dynamic_handles: Arc<ParkingMutex<Vec<JoinHandle<()>>>> =
@@ -246,11 +569,12 @@ pub async fn start_lan_mode(
         let ev = events.clone();
         let ct = cancel.clone();
         let df = disable_flag.clone();
-        let did = device_id.clone();
+        let state = clipboard.clone();
+        let cb = backend.clone();
         let max_kb = config.max_image_kb;
         let tx = tx_out.clone();
         handles.push(tokio::spawn(async move {
-            start_clipboard_monitor(tx, df, did, max_kb, ev, ct).await;
+            start_clipboard_monitor(tx, df, state, cb, max_kb, ev, ct).await;
         }));
     }
 
@@ -260,57 +584,82 @@ pub async fn start_lan_mode(
         let ct = cancel.clone();
         let df = disable_flag.clone();
         handles.push(tokio::spawn(async move {
-            start_clipboard_setter(rx_in, df, ev, ct).await;
+            start_clipboard_setter(rx_in, clipboard, backend, df, ev, ct).await;
         }));
     }
 
     // ── 3. UDP beacon broadcaster ────────────────────────────────────────
-    {
-        let did = device_id.clone();
-        let dname = device_name.clone();
-        let ev = events.clone();
+    if discovery.broadcast() {
+        let pc = peer_ctx.clone();
+        let filter = interface_filter.clone();
         let ct = cancel.clone();
         handles.push(tokio::spawn(async move {
-            run_beacon_broadcaster(did, dname, tcp_port, discovery_port, ev, ct).await;
+            run_beacon_broadcaster(pc, tcp_port, discovery_port, filter, ct).await;
         }));
     }
 
     // ── 4. UDP beacon listener (using pre-bound socket) ──────────────────
-    {
-        let did = device_id.clone();
-        let pm = peers.clone();
-        let ev = events.clone();
+    for udp_listener_socket in udp_listener_sockets {
+        let pc = peer_ctx.clone();
+        let filter = interface_filter.clone();
         let ct = cancel.clone();
         handles.push(tokio::spawn(async move {
-            run_beacon_listener(did, pm, udp_listener_socket, ev, ct).await;
+            run_beacon_listener(pc, udp_listener_socket, filter, ct).await;
         }));
     }
 
     // ── 5. TCP host listener (using pre-bound listener) ──────────────────
     {
-        let did = device_id.clone();
-        let dname = device_name.clone();
-        let tx = tx_out.clone();
-        let ti = tx_in.clone();
-        let ev = events.clone();
+        let pc = peer_ctx.clone();
         let ct = cancel.clone();
         handles.push(tokio::spawn(async move {
-            run_tcp_host_on_listener(did, dname, tcp_listener, tx, ti, ev, ct).await;
+            run_tcp_host_on_listener(pc, tcp_listener, ct).await;
         }));
     }
 
     // ── 6. Peer connector (server-decided: higher device_id connects) ───
     {
-        let own_id = device_id.clone();
-        let own_name = device_name.clone();
-        let pm = peers.clone();
-        let tx = tx_out.clone();
-        let ti = tx_in.clone();
-        let ev = events.clone();
+        let pc = peer_ctx.clone();
         let ct = cancel.clone();
         let dh = dynamic_handles.clone();
         handles.push(tokio::spawn(async move {
-            run_peer_connector(own_id, own_name, pm, tx, ti, ev, ct, dh).await;
+            run_peer_connector(pc, ct, dh).await;
+        }));
+    }
+
+    // ── 7. mDNS / DNS-SD discovery ───────────────────────────────────────
+    if discovery.mdns() {
+        let pc = peer_ctx.clone();
+        let filter = interface_filter.clone();
+        let ct = cancel.clone();
+        handles.push(tokio::spawn(async move {
+            run_mdns_discovery(pc, tcp_port, filter, ct).await;
+        }));
+    }
+
+    // ── 8. Link statistics ───────────────────────────────────────────────
+    {
+        let registry = peer_ctx.registry.clone();
+        let ev = events.clone();
+        let ct = cancel.clone();
+        handles.push(tokio::spawn(async move {
+            run_link_stats_publisher(registry, ev, ct).await;
+        }));
+    }
+
+    // ── 9. Static peers (dialed without waiting for discovery) ───────────
+    for addr in &config.lan_static_peers {
+        let _ = events
+            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                Level::Info,
+                format!("LAN static peer: dialing {}", addr),
+            )))
+            .await;
+        let pc = peer_ctx.clone();
+        let addr = addr.clone();
+        let ct = cancel.clone();
+        handles.push(tokio::spawn(async move {
+            run_tcp_client(DialTarget::Static { claimed: None }, addr, pc, ct).await;
         }));
     }
 
@@ -319,8 +668,13 @@ pub async fn start_lan_mode(
         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
             Level::Info,
             format!(
-                "LAN mode started — id={}, name={}, discovery_port={}, tcp_port={}",
-                device_id, device_name, discovery_port, tcp_port,
+                "LAN mode started — id={}, name={}, group={}, discovery={}, discovery_port={}, tcp_port={}",
+                device_id,
+                device_name,
+                group.display_name(),
+                discovery.as_str(),
+                discovery_port,
+                tcp_port,
             ),
         )))
         .await;
@@ -335,6 +689,7 @@ pub async fn start_lan_mode(
         cancel,
         handles,
         dynamic_handles,
+        control,
     })
 }
 
@@ -342,29 +697,30 @@ pub async fn start_lan_mode(
 // Peer connector task
 // ────────────────────────────────────────────────────────────────────────────
 
-/// Periodically scans the discovered-peers map and initiates TCP connections
+/// Periodically scans the discovered peers and initiates TCP connections
 /// to peers that we should connect to according to the **server-decided**
 /// rule: we only connect to peer B if `our_device_id > B.device_id`
 /// (lexicographic). The peer with the smaller id acts as the passive
 /// acceptor (host).
 ///
-/// Once a connection is initiated to a peer, the peer's `device_id` is added
-/// to a local `connected` set so we don't spawn duplicate client tasks.
+/// Blocked peers are never dialed. Once a connection is initiated to a peer,
+/// the peer's `device_id` is claimed in [`PeerContext::registry`] so we
+/// don't spawn duplicate client tasks; peers already reached through a
+/// static address are claimed there too and skipped. The claim carries the
+/// client task's cancel token: when the peer expires from discovery the
+/// registry stops the task and drops the claim, and the peer is dialed
+/// again once it is rediscovered.
 ///
-/// Every spawned client [`JoinHandle`] is recorded in `dynamic_handles` so
+/// Every running client [`JoinHandle`] is kept in `dynamic_handles` so
 /// that [`LanTasks::shutdown`] / [`LanTasks::abort`] can deterministically
-/// stop them.
+/// stop them; handles of finished tasks are pruned as new ones are added.
 async fn run_peer_connector(
-    own_device_id: String,
-    own_device_name: String,
-    peers: DiscoveredPeers,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
+    ctx: PeerContext,
     cancel: CancellationToken,
     dynamic_handles: Arc<ParkingMutex<Vec<JoinHandle<()>>>>,
 ) {
-    let mut connected: HashSet<String> = HashSet::new();
+    let own_device_id = ctx.device_id.clone();
+    let events = ctx.events.clone();
 
     loop {
         tokio::select! {
@@ -376,20 +732,21 @@ async fn run_peer_connector(
             break;
         }
 
-        let current_peers: Vec<DiscoveredPeer> = get_discovered_peers(&peers);
-
-        for peer in current_peers {
+        for peer in ctx.registry.discovered() {
             // Server-decided rule: only the side with the *greater* id
             // initiates the connection.
             if own_device_id <= peer.device_id {
                 continue;
             }
 
-            if connected.contains(&peer.device_id) {
+            let ct = cancel.child_token();
+            if ctx.trust.is_blocked(&peer.device_id)
+                || !ctx.registry.claim_dial(&peer.device_id, Some(ct.clone()))
+            {
                 continue;
             }
 
-            let addr = format!("{}:{}", peer.addr, peer.tcp_port);
+            let addr = peer.dial_addr();
 
             let _ = events
                 .send(RuntimeEvent::Log(RuntimeLogEvent::new(
@@ -401,18 +758,12 @@ async fn run_peer_connector(
                 )))
                 .await;
 
-            connected.insert(peer.device_id.clone());
-
-            let did = own_device_id.clone();
-            let dname = own_device_name.clone();
-            let tx = tx_out.clone();
-            let ti = tx_in.clone();
+            let pc = ctx.clone();
             let ev = events.clone();
-            let ct = cancel.child_token();
             let peer_id = peer.device_id.clone();
 
             let handle = tokio::spawn(async move {
-                run_tcp_client(addr, did, dname, tx, ti, ev.clone(), ct).await;
+                run_tcp_client(DialTarget::Discovered(peer_id.clone()), addr, pc, ct).await;
                 let _ = ev
                     .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                         Level::Debug,
@@ -421,8 +772,12 @@ async fn run_peer_connector(
                     .await;
             });
 
-            // Track the handle so shutdown can await/abort it.
-            dynamic_handles.lock().push(handle);
+            // Track the handle so shutdown can await/abort it, dropping
+            // those of tasks that have ended since a peer is redialed after
+            // every expiry.
+            let mut handles = dynamic_handles.lock();
+            handles.retain(|h| !h.is_finished());
+            handles.push(handle);
         }
     }
 
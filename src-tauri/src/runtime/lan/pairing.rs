@@ -0,0 +1,632 @@
+//! PIN-based pairing and the persisted trust store for LAN peers.
+//!
+//! The trust store doubles as the LAN access list: every discovered peer is
+//! either **trusted** (paired, or approved by hand), **blocked** (never
+//! dialed, sessions refused) or **pending** (neither; sessions carry only
+//! heartbeats and pairing until the user decides). See [`PeerAccess`].
+//!
+//! A TCP session between two peers is established as soon as discovery
+//! finds them, but clipboard frames are only relayed once both sides hold a
+//! trust record for each other. Trust is created by pairing:
+//!
+//! 1. Device **A** shows a short numeric PIN ([`PairingState::begin`]).
+//! 2. The user types that PIN on device **B**. Both sides run a
+//!    [`PinExchange`] keyed by the PIN, both device ids and the handshake
+//!    hash of the encrypted session (see [`super::secure`]): B sends its
+//!    share in `PairStart`, A answers with its own in `PairShare`.
+//! 3. B proves it derived the same key with `PairConfirm`. A checks the
+//!    confirmation against its pending PIN, records B as trusted and
+//!    answers with a `PairAccept` carrying its own confirmation, which B
+//!    checks in turn before recording A as trusted.
+//!
+//! Each trust record pins the remote's device public key as authenticated
+//! by the session handshake; a peer presenting a different key under a
+//! known `device_id` is treated as unpaired.
+//!
+//! Nothing on the wire lets anyone test PIN guesses offline, the way a
+//! hash or MAC keyed by the PIN would. Whoever does not know the PIN, a
+//! man-in-the-middle included, learns whether one guess was right per
+//! exchange, and only by running it with the other side. Because the
+//! exchange is bound to the handshake hash, a MITM holding two sessions
+//! has to guess in each. A PIN is single-use, expires after
+//! [`PAIRING_PIN_TTL_SECS`] and is discarded after
+//! [`MAX_PAIRING_ATTEMPTS`] wrong guesses.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use curve25519_dalek::{
+    ristretto::{CompressedRistretto, RistrettoPoint},
+    scalar::Scalar,
+    traits::Identity,
+};
+use parking_lot::{Mutex, RwLock};
+use ring::{
+    digest, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+// ────────────────────────────────────────────────────────────────────────────
+// Constants
+// ────────────────────────────────────────────────────────────────────────────
+
+/// File name of the trust store inside the application data directory
+/// (next to the `config.json` store).
+pub const TRUST_STORE_FILE: &str = "lan_trust.json";
+
+/// Number of decimal digits in a pairing PIN.
+pub const PAIRING_PIN_DIGITS: u32 = 6;
+
+/// How long a displayed PIN stays valid (seconds).
+pub const PAIRING_PIN_TTL_SECS: u64 = 120;
+
+/// Wrong guesses tolerated before the pending PIN is discarded.
+pub const MAX_PAIRING_ATTEMPTS: u32 = 3;
+
+/// Maximum time (seconds) the requesting side waits for the exchange to
+/// complete after sending `PairStart`.
+pub const PAIRING_REPLY_TIMEOUT_SECS: u64 = 15;
+
+/// Domain-separation labels of the [`PinExchange`] generator and key.
+const EXCHANGE_LABEL_GENERATOR: &str = "RSCV-PAIR-GEN";
+const EXCHANGE_LABEL_KEY: &str = "RSCV-PAIR-KEY";
+
+/// Domain-separation labels of the key confirmations so the requester's
+/// can never be replayed as the accepting side's.
+const PROOF_LABEL_REQUEST: &str = "RSCV-PAIR-REQ";
+const PROOF_LABEL_ACCEPT: &str = "RSCV-PAIR-ACK";
+
+// ────────────────────────────────────────────────────────────────────────────
+// Trust store
+// ────────────────────────────────────────────────────────────────────────────
+
+/// A peer we have successfully paired with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedPeer {
+    pub device_id: String,
+    pub device_name: String,
+    /// Base64 device public key pinned at pairing. Records written before
+    /// keys existed have none and must be paired again.
+    #[serde(default)]
+    pub public_key: String,
+    /// Unix timestamp (secs) of the pairing.
+    pub paired_at: u64,
+}
+
+/// A peer the user has blocked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedPeer {
+    pub device_id: String,
+    pub device_name: String,
+    /// Unix timestamp (secs) of the block.
+    pub blocked_at: u64,
+}
+
+/// Where a peer stands in the access list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeerAccess {
+    /// Paired or approved; clipboard data flows.
+    Trusted,
+    /// Unknown; waiting for the user to pair, approve or block it.
+    #[default]
+    Pending,
+    /// Never dialed; its sessions are refused.
+    Blocked,
+}
+
+/// On-disk layout of [`TRUST_STORE_FILE`]; also the JSON carried by
+/// `RuntimeEvent::LanTrustChanged`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrustFile {
+    #[serde(default)]
+    trusted: Vec<TrustedPeer>,
+    #[serde(default)]
+    blocked: Vec<BlockedPeer>,
+}
+
+#[derive(Default)]
+struct AccessLists {
+    trusted: HashMap<String, TrustedPeer>,
+    blocked: HashMap<String, BlockedPeer>,
+}
+
+/// Persisted allow (trusted) and block lists, keyed by `device_id`.
+///
+/// Cheap to clone; all clones share the same lists and backing file.
+#[derive(Clone)]
+pub struct TrustStore {
+    path: PathBuf,
+    lists: Arc<RwLock<AccessLists>>,
+}
+
+impl TrustStore {
+    /// Load the trust store from `<data_dir>/lan_trust.json`.
+    ///
+    /// A missing file yields an empty store; a corrupt file is reported as
+    /// an error rather than silently discarding the user's pairings.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(TRUST_STORE_FILE);
+        let file: TrustFile = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("parsing trust store {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => TrustFile::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading trust store {}", path.display()))
+            }
+        };
+
+        let lists = AccessLists {
+            trusted: file
+                .trusted
+                .into_iter()
+                .map(|p| (p.device_id.clone(), p))
+                .collect(),
+            blocked: file
+                .blocked
+                .into_iter()
+                .map(|p| (p.device_id.clone(), p))
+                .collect(),
+        };
+
+        Ok(Self {
+            path,
+            lists: Arc::new(RwLock::new(lists)),
+        })
+    }
+
+    /// Access-list status of `device_id`.
+    pub fn access(&self, device_id: &str) -> PeerAccess {
+        let lists = self.lists.read();
+        if lists.blocked.contains_key(device_id) {
+            PeerAccess::Blocked
+        } else if lists.trusted.contains_key(device_id) {
+            PeerAccess::Trusted
+        } else {
+            PeerAccess::Pending
+        }
+    }
+
+    /// Returns `true` when `device_id` is on the block list.
+    pub fn is_blocked(&self, device_id: &str) -> bool {
+        self.lists.read().blocked.contains_key(device_id)
+    }
+
+    /// Returns `true` when `device_id` has been paired and presented the
+    /// same `public_key` (base64) it was paired with.
+    pub fn is_trusted(&self, device_id: &str, public_key: &str) -> bool {
+        self.lists
+            .read()
+            .trusted
+            .get(device_id)
+            .is_some_and(|p| !p.public_key.is_empty() && p.public_key == public_key)
+    }
+
+    /// Returns `true` when `device_id` has a trust record whose pinned key
+    /// differs from `public_key`.
+    pub fn is_key_mismatch(&self, device_id: &str, public_key: &str) -> bool {
+        self.lists
+            .read()
+            .trusted
+            .get(device_id)
+            .is_some_and(|p| p.public_key != public_key)
+    }
+
+    /// Record (or refresh) a trusted peer, lifting any block, and persist
+    /// the store. When the store cannot be saved the lists are left as
+    /// they were.
+    pub fn trust(&self, device_id: &str, device_name: &str, public_key: &str) -> Result<()> {
+        let (blocked, trusted) = {
+            let mut lists = self.lists.write();
+            let blocked = lists.blocked.remove(device_id);
+            let trusted = lists.trusted.insert(
+                device_id.to_string(),
+                TrustedPeer {
+                    device_id: device_id.to_string(),
+                    device_name: device_name.to_string(),
+                    public_key: public_key.to_string(),
+                    paired_at: now_unix_secs(),
+                },
+            );
+            (blocked, trusted)
+        };
+        self.save().inspect_err(|_| {
+            let mut lists = self.lists.write();
+            match trusted {
+                Some(old) => lists.trusted.insert(device_id.to_string(), old),
+                None => lists.trusted.remove(device_id),
+            };
+            if let Some(old) = blocked {
+                lists.blocked.insert(device_id.to_string(), old);
+            }
+        })
+    }
+
+    /// Block a peer, dropping any trust record, and persist the store.
+    pub fn block(&self, device_id: &str, device_name: &str) -> Result<()> {
+        {
+            let mut lists = self.lists.write();
+            lists.trusted.remove(device_id);
+            lists.blocked.insert(
+                device_id.to_string(),
+                BlockedPeer {
+                    device_id: device_id.to_string(),
+                    device_name: device_name.to_string(),
+                    blocked_at: now_unix_secs(),
+                },
+            );
+        }
+        self.save()
+    }
+
+    /// Forget a peer entirely (trusted or blocked), returning it to
+    /// [`PeerAccess::Pending`]. Returns `false` if it was on neither list.
+    pub fn revoke(&self, device_id: &str) -> Result<bool> {
+        let removed = {
+            let mut lists = self.lists.write();
+            let trusted = lists.trusted.remove(device_id).is_some();
+            let blocked = lists.blocked.remove(device_id).is_some();
+            trusted || blocked
+        };
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Name recorded for `device_id` on either list.
+    pub fn known_name(&self, device_id: &str) -> Option<String> {
+        let lists = self.lists.read();
+        lists
+            .trusted
+            .get(device_id)
+            .map(|p| p.device_name.clone())
+            .or_else(|| lists.blocked.get(device_id).map(|p| p.device_name.clone()))
+    }
+
+    /// Snapshot of all trusted peers.
+    pub fn list(&self) -> Vec<TrustedPeer> {
+        self.lists.read().trusted.values().cloned().collect()
+    }
+
+    /// Snapshot of all blocked peers.
+    pub fn blocked(&self) -> Vec<BlockedPeer> {
+        self.lists.read().blocked.values().cloned().collect()
+    }
+
+    /// JSON-serialised `{ "trusted": [...], "blocked": [...] }`, as carried
+    /// by `RuntimeEvent::LanTrustChanged`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.snapshot()).unwrap_or_else(|_| "{}".into())
+    }
+
+    fn snapshot(&self) -> TrustFile {
+        TrustFile {
+            trusted: self.list(),
+            blocked: self.blocked(),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = &self.path;
+        let file = self.snapshot();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        let json = serde_json::to_vec_pretty(&file)?;
+        std::fs::write(path, json).with_context(|| format!("writing {}", path.display()))
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Pending PIN
+// ────────────────────────────────────────────────────────────────────────────
+
+struct PendingPin {
+    pin: String,
+    expires_at: Instant,
+    failed_attempts: u32,
+}
+
+/// Outcome of a requester's `PairConfirm` for the pending PIN.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinCheck {
+    /// The confirmation matched; the PIN has been consumed.
+    Accepted,
+    /// The confirmation did not match; the PIN stays valid unless attempts
+    /// ran out.
+    WrongPin,
+    /// No PIN is currently displayed, it expired, or it was replaced since
+    /// the exchange started.
+    NoPendingPin,
+}
+
+/// The PIN currently shown on this device, if any.
+///
+/// Shared by every peer session so that whichever session completes the
+/// exchange with the matching PIN can consume it.
+#[derive(Clone, Default)]
+pub struct PairingState {
+    pending: Arc<Mutex<Option<PendingPin>>>,
+}
+
+impl PairingState {
+    /// Generate a fresh PIN, replacing any previous one, and return it for
+    /// display.
+    pub fn begin(&self) -> Result<String> {
+        let pin = generate_pin()?;
+        *self.pending.lock() = Some(PendingPin {
+            pin: pin.clone(),
+            expires_at: Instant::now() + Duration::from_secs(PAIRING_PIN_TTL_SECS),
+            failed_attempts: 0,
+        });
+        Ok(pin)
+    }
+
+    /// The displayed PIN, to answer a `PairStart` with, unless it expired.
+    pub fn current(&self) -> Option<String> {
+        let mut guard = self.pending.lock();
+        if guard
+            .as_ref()
+            .is_some_and(|p| Instant::now() >= p.expires_at)
+        {
+            *guard = None;
+        }
+        guard.as_ref().map(|p| p.pin.clone())
+    }
+
+    /// Settle an exchange answered with `pin`, once the requester's
+    /// confirmation was checked: consume the PIN if it was `confirmed`,
+    /// otherwise count a wrong guess.
+    pub fn settle(&self, pin: &str, confirmed: bool) -> PinCheck {
+        let mut guard = self.pending.lock();
+        let Some(pending) = guard
+            .as_mut()
+            .filter(|p| p.pin == pin && Instant::now() < p.expires_at)
+        else {
+            return PinCheck::NoPendingPin;
+        };
+        if confirmed {
+            *guard = None;
+            return PinCheck::Accepted;
+        }
+
+        pending.failed_attempts += 1;
+        if pending.failed_attempts >= MAX_PAIRING_ATTEMPTS {
+            *guard = None;
+        }
+        PinCheck::WrongPin
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// PIN exchange
+// ────────────────────────────────────────────────────────────────────────────
+
+/// One side of the PIN exchange: a CPace-style password-authenticated key
+/// exchange over ristretto255.
+///
+/// Both sides hash the PIN, both device ids and the session's handshake
+/// hash to a generator point and send a random multiple of it (their
+/// share). Combining the remote share with its own secret gives each side
+/// the same [`PairingKey`] only if both used the same PIN on the same
+/// session. A share is indistinguishable from a random point, whatever
+/// the PIN.
+pub struct PinExchange {
+    secret: Scalar,
+    share: CompressedRistretto,
+    /// We typed the PIN, rather than display it.
+    requester: bool,
+    /// Device ids and handshake hash the exchange is bound to.
+    session: [u8; 64],
+}
+
+impl PinExchange {
+    /// Start an exchange in which `requester_id` entered the `pin` shown
+    /// on `displayer_id`, over the session whose base64 handshake hash is
+    /// `binding`. `requester` says which of the two we are.
+    pub fn start(
+        pin: &str,
+        requester_id: &str,
+        displayer_id: &str,
+        binding: &str,
+        requester: bool,
+    ) -> Result<Self> {
+        let session = hash_parts(&[
+            requester_id.as_bytes(),
+            displayer_id.as_bytes(),
+            binding.as_bytes(),
+        ]);
+        let generator = RistrettoPoint::from_uniform_bytes(&hash_parts(&[
+            EXCHANGE_LABEL_GENERATOR.as_bytes(),
+            pin.as_bytes(),
+            &session,
+        ]));
+        let mut bytes = [0u8; 64];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| anyhow!("system RNG unavailable"))?;
+        let secret = Scalar::from_bytes_mod_order_wide(&bytes);
+        Ok(Self {
+            share: (generator * secret).compress(),
+            secret,
+            requester,
+            session,
+        })
+    }
+
+    /// Our share (base64), for `PairStart` or `PairShare`.
+    pub fn share(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.share.as_bytes())
+    }
+
+    /// Derive the key from the remote's base64 share.
+    pub fn finish(self, remote_share: &str) -> Result<PairingKey> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(remote_share)
+            .context("pairing share is not base64")?;
+        let remote = CompressedRistretto::from_slice(&bytes)
+            .map_err(|_| anyhow!("pairing share has the wrong length"))?;
+        let shared = remote
+            .decompress()
+            .ok_or_else(|| anyhow!("pairing share is not a valid point"))?
+            * self.secret;
+        // A low-order share would force a key independent of the PIN.
+        if shared == RistrettoPoint::identity() {
+            return Err(anyhow!("pairing share is not a valid point"));
+        }
+        let (requester_share, displayer_share) = if self.requester {
+            (self.share, remote)
+        } else {
+            (remote, self.share)
+        };
+        let key = hash_parts(&[
+            EXCHANGE_LABEL_KEY.as_bytes(),
+            &self.session,
+            shared.compress().as_bytes(),
+            requester_share.as_bytes(),
+            displayer_share.as_bytes(),
+        ]);
+        Ok(PairingKey(hmac::Key::new(hmac::HMAC_SHA256, &key)))
+    }
+}
+
+/// The key both sides of a [`PinExchange`] derive. Each proves it holds it
+/// with a confirmation before the other trusts it.
+pub struct PairingKey(hmac::Key);
+
+impl PairingKey {
+    /// Confirmation sent by the requester in `PairConfirm`.
+    pub fn request_proof(&self) -> String {
+        self.proof(PROOF_LABEL_REQUEST)
+    }
+
+    /// Confirmation sent by the displaying side in `PairAccept`.
+    pub fn accept_proof(&self) -> String {
+        self.proof(PROOF_LABEL_ACCEPT)
+    }
+
+    /// Check a requester's `PairConfirm` confirmation.
+    pub fn verify_request_proof(&self, proof: &str) -> bool {
+        self.verify(PROOF_LABEL_REQUEST, proof)
+    }
+
+    /// Check the displaying side's `PairAccept` confirmation.
+    pub fn verify_accept_proof(&self, proof: &str) -> bool {
+        self.verify(PROOF_LABEL_ACCEPT, proof)
+    }
+
+    fn proof(&self, label: &str) -> String {
+        let tag = hmac::sign(&self.0, label.as_bytes());
+        base64::engine::general_purpose::STANDARD.encode(tag.as_ref())
+    }
+
+    fn verify(&self, label: &str, proof: &str) -> bool {
+        let Ok(tag) = base64::engine::general_purpose::STANDARD.decode(proof) else {
+            return false;
+        };
+        // `hmac::verify` compares in constant time.
+        hmac::verify(&self.0, label.as_bytes(), &tag).is_ok()
+    }
+}
+
+/// SHA-512 over length-prefixed `parts`, so no two lists of parts hash
+/// the same input.
+fn hash_parts(parts: &[&[u8]]) -> [u8; 64] {
+    let mut ctx = digest::Context::new(&digest::SHA512);
+    for part in parts {
+        ctx.update(&(part.len() as u64).to_be_bytes());
+        ctx.update(part);
+    }
+    let mut out = [0u8; 64];
+    out.copy_from_slice(ctx.finish().as_ref());
+    out
+}
+
+fn generate_pin() -> Result<String> {
+    let mut bytes = [0u8; 4];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow::anyhow!("system RNG unavailable"))?;
+    let modulus = 10u32.pow(PAIRING_PIN_DIGITS);
+    let value = u32::from_be_bytes(bytes) % modulus;
+    Ok(format!(
+        "{:0width$}",
+        value,
+        width = PAIRING_PIN_DIGITS as usize
+    ))
+}
+
+fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the exchange between a requester who typed `typed` and a
+    /// displaying side showing `shown`, returning both keys.
+    fn exchange(typed: &str, shown: &str, binding: &str) -> (PairingKey, PairingKey) {
+        let requester = PinExchange::start(typed, "b", "a", binding, true).unwrap();
+        let displayer = PinExchange::start(shown, "b", "a", binding, false).unwrap();
+        let requester_share = requester.share();
+        let displayer_share = displayer.share();
+        (
+            requester.finish(&displayer_share).unwrap(),
+            displayer.finish(&requester_share).unwrap(),
+        )
+    }
+
+    #[test]
+    fn same_pin_confirms_both_ways() {
+        let (requester, displayer) = exchange("123456", "123456", "session");
+        assert!(displayer.verify_request_proof(&requester.request_proof()));
+        assert!(requester.verify_accept_proof(&displayer.accept_proof()));
+        // Confirmations are bound to their direction.
+        assert!(!requester.verify_accept_proof(&requester.request_proof()));
+    }
+
+    #[test]
+    fn wrong_pin_or_other_session_fails_confirmation() {
+        let (requester, displayer) = exchange("123456", "654321", "session");
+        assert!(!displayer.verify_request_proof(&requester.request_proof()));
+
+        let requester = PinExchange::start("123456", "b", "a", "one", true).unwrap();
+        let displayer = PinExchange::start("123456", "b", "a", "other", false).unwrap();
+        let requester_share = requester.share();
+        let requester = requester.finish(&displayer.share()).unwrap();
+        let displayer = displayer.finish(&requester_share).unwrap();
+        assert!(!displayer.verify_request_proof(&requester.request_proof()));
+    }
+
+    #[test]
+    fn identity_share_is_rejected() {
+        let exchange = PinExchange::start("123456", "b", "a", "session", false).unwrap();
+        let identity = base64::engine::general_purpose::STANDARD
+            .encode(CompressedRistretto::identity().as_bytes());
+        assert!(exchange.finish(&identity).is_err());
+    }
+
+    #[test]
+    fn wrong_confirmations_use_up_the_pin() {
+        let pairing = PairingState::default();
+        let pin = pairing.begin().unwrap();
+        for _ in 0..MAX_PAIRING_ATTEMPTS {
+            assert_eq!(pairing.settle(&pin, false), PinCheck::WrongPin);
+        }
+        assert_eq!(pairing.current(), None);
+        assert_eq!(pairing.settle(&pin, true), PinCheck::NoPendingPin);
+    }
+}
@@ -15,32 +15,243 @@
 //! Both tasks share the same [`run_peer_session`] function for the steady-state
 //! loop so that heartbeat, clipboard send/receive, and error handling are
 //! written exactly once (DRY).
+//!
+//! Every connection first runs the encrypted handshake from
+//! [`super::secure`] (the client initiates), and all frames, including
+//! `Hello`/`Welcome`, travel over the resulting channel. `Hello`/`Welcome`
+//! also carry each side's protocol version range and [`Capabilities`];
+//! the session only sends content the remote supports, and a version
+//! mismatch is reported once instead of reconnecting in a loop.
+//!
+//! Client tasks dial either a discovered peer or a static address from the
+//! config ([`DialTarget`]); a static address only reveals which device is
+//! behind it in the handshake. However a session came about, only one per
+//! peer is kept (see [`run_peer_session`]).
+//!
+//! Sessions are opened with any discovered peer, but `Clipboard` frames are
+//! only relayed in either direction once the remote is in the
+//! [`TrustStore`] with the device key it presented in the handshake. Until
+//! then the session carries heartbeats and the pairing exchange only (see
+//! [`super::pairing`]). Trusted peers then exchange clipboard content in the
+//! directions the sync policy allows (see [`crate::runtime::policy`]).
+//!
+//! Items carry their origin across the mesh; with relaying enabled, an
+//! item accepted from one session is forwarded to the others (see
+//! [`super::relay`]).
 
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{anyhow, Context, Result};
 use log::Level;
+use parking_lot::Mutex as ParkingMutex;
+use socket2::{Domain, Protocol, Socket, Type};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
-    sync::{broadcast, mpsc},
+    sync::{broadcast, mpsc, oneshot},
+    task::JoinHandle,
     time::{interval, sleep, timeout, Duration, Instant},
 };
 use tokio_util::sync::CancellationToken;
 
+use super::group::SyncGroup;
+use super::pairing::{
+    PairingKey, PairingState, PinCheck, PinExchange, TrustStore, PAIRING_REPLY_TIMEOUT_SECS,
+};
 use super::protocol::{
-    encode_peer_message, PeerMessage, HANDSHAKE_TIMEOUT_SECS, HEARTBEAT_INTERVAL_SECS,
-    HEARTBEAT_TIMEOUT_SECS, INITIAL_RECONNECT_DELAY_SECS, MAX_FRAME_SIZE, MAX_RECONNECT_DELAY_SECS,
+    decode_peer_message, encode_peer_message, Capabilities, HandshakeInfo, IncompatibleProtocol,
+    ItemOrigin, PeerMessage, HANDSHAKE_TIMEOUT_SECS, HEARTBEAT_INTERVAL_SECS,
+    HEARTBEAT_TIMEOUT_SECS, INCOMPATIBLE_RETRY_SECS, INITIAL_RECONNECT_DELAY_SECS,
+    MAX_RECONNECT_DELAY_SECS,
 };
+use super::registry::{emit_peer_list, LinkState, PeerRegistry, SessionRole};
+use super::relay::{self, SharedSeenItems};
+use super::secure::{secure_accept, secure_connect, SecureReader, SecureStream, SecureWriter};
+use super::transfer::{IncomingTransfer, OutgoingTransfer, CHUNK_SIZE, MAX_QUEUED_TRANSFERS};
+use crate::runtime::clipboard::ClipboardState;
+use crate::runtime::clock::Hlc;
+use crate::runtime::compression::{self, CompressionStats};
+use crate::runtime::delivery::{DeliveryReceipt, DeliveryStatus, ReceiptSender};
+use crate::runtime::identity::{device_id_from_public_key, encode_key, DeviceIdentity};
 use crate::runtime::messages::{
-    ClipboardBroadcastPayload, ClipboardUpdate, CONTENT_TYPE_IMAGE_PNG, CONTENT_TYPE_TEXT,
+    ClipboardItem, CONTENT_TYPE_HTML, CONTENT_TYPE_IMAGE_PNG, CONTENT_TYPE_TEXT,
 };
 use crate::runtime::{RuntimeEvent, RuntimeLogEvent};
 
+// ────────────────────────────────────────────────────────────────────────────
+// Shared session context
+// ────────────────────────────────────────────────────────────────────────────
+
+/// Requests delivered to a single live peer session from outside (UI
+/// commands routed through [`super::LanControl`]).
+pub enum SessionCommand {
+    /// Pair with the remote using the PIN it is currently displaying.
+    Pair {
+        pin: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Trust the remote without a PIN, pinning the key it presented in
+    /// this session's handshake.
+    Approve { reply: oneshot::Sender<Result<()>> },
+    /// End the session, logging `reason`.
+    Close { reason: &'static str },
+    /// Send a message on another session's behalf (a delivery receipt
+    /// passed back towards the item's origin).
+    Send(PeerMessage),
+}
+
+/// A live session as seen from outside its task.
+pub struct SessionHandle {
+    pub commands: mpsc::Sender<SessionCommand>,
+    /// Items sent to this peer alone (see [`ClipboardItem::target`]).
+    pub outbox: mpsc::Sender<ClipboardItem>,
+    /// The session was dialed by the side with the greater device id.
+    preferred: bool,
+}
+
+/// Handles of all live sessions, keyed by remote `device_id`.
+pub type SessionHandles = Arc<ParkingMutex<HashMap<String, SessionHandle>>>;
+
+/// Create a new, empty session handle map.
+pub fn new_session_handles() -> SessionHandles {
+    Arc::new(ParkingMutex::new(HashMap::new()))
+}
+
+/// What a client task dials.
+pub enum DialTarget {
+    /// A discovered peer, claimed in [`PeerContext::registry`] by the
+    /// connector before the task was spawned.
+    Discovered(String),
+    /// A static address from the config. The device behind it is learned in
+    /// the handshake and claimed then.
+    Static { claimed: Option<String> },
+}
+
+impl DialTarget {
+    fn device_id(&self) -> Option<&str> {
+        match self {
+            DialTarget::Discovered(id) => Some(id),
+            DialTarget::Static { claimed } => claimed.as_deref(),
+        }
+    }
+
+    /// Claim `remote` for a static target, so the connector and other
+    /// static entries leave it to this task. Fails when another task
+    /// already dials it.
+    fn claim(&mut self, registry: &PeerRegistry, remote: &RemotePeer) -> Result<()> {
+        let DialTarget::Static { claimed } = self else {
+            return Ok(());
+        };
+        if claimed.as_deref() == Some(remote.device_id.as_str()) {
+            return Ok(());
+        }
+        if let Some(old) = claimed.take() {
+            registry.release_dial(&old);
+        }
+        if !registry.claim_dial(&remote.device_id, None) {
+            return Err(DuplicateSession(format!(
+                "{} ({}) is already dialed at another address",
+                remote.device_name, remote.device_id
+            ))
+            .into());
+        }
+        *claimed = Some(remote.device_id.clone());
+        Ok(())
+    }
+
+    /// Give up the claim taken by [`claim`](Self::claim).
+    fn release(&mut self, registry: &PeerRegistry) {
+        if let DialTarget::Static { claimed: Some(id) } = self {
+            registry.release_dial(id);
+        }
+    }
+}
+
+/// A session not needed because another one with the same peer is live.
+#[derive(Debug)]
+struct DuplicateSession(String);
+
+impl std::fmt::Display for DuplicateSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for DuplicateSession {}
+
+/// Everything a peer session needs besides its socket and cancel token.
+///
+/// Cloned into every host/client task; all fields are cheap handles onto
+/// shared state.
+#[derive(Clone)]
+pub struct PeerContext {
+    /// Our device id (derived from `identity`).
+    pub device_id: String,
+    /// Human-friendly device name (displayed on the remote).
+    pub device_name: String,
+    /// Persistent key pair authenticating this device in the handshake.
+    pub identity: DeviceIdentity,
+    /// Sync group both ends of a session must belong to.
+    pub group: SyncGroup,
+    /// Broadcast channel carrying outbound clipboard updates (from our local
+    /// clipboard monitor).
+    pub tx_out: broadcast::Sender<ClipboardItem>,
+    /// Channel to forward inbound clipboard payloads into the local
+    /// clipboard setter.
+    pub tx_in: mpsc::Sender<ClipboardItem>,
+    /// Runtime event sink for logging / UI.
+    pub events: mpsc::Sender<RuntimeEvent>,
+    /// Paired peers; gates clipboard relay.
+    pub trust: TrustStore,
+    /// The PIN currently displayed on this device, if any.
+    pub pairing: PairingState,
+    /// Command channels of live sessions.
+    pub sessions: SessionHandles,
+    /// Forward items received from one peer to the other sessions.
+    pub relay: bool,
+    /// Items already delivered, shared by all sessions.
+    pub seen: SharedSeenItems,
+    /// Newest local clipboard item, sent when a session starts.
+    pub clipboard: ClipboardState,
+    /// Discovery, dial claims, session state and link statistics of every
+    /// peer; its peer list is re-emitted whenever the trust store changes.
+    pub registry: PeerRegistry,
+}
+
 // ────────────────────────────────────────────────────────────────────────────
 // Public API — Host
 // ────────────────────────────────────────────────────────────────────────────
 
+/// Bind the TCP host on `port` for both address families.
+///
+/// Prefers a dual-stack `[::]` socket (`IPV6_V6ONLY` off, which is not the
+/// default on Windows) and falls back to `0.0.0.0` when the host has no
+/// IPv6.
+pub async fn bind_tcp_host(port: u16) -> io::Result<TcpListener> {
+    match bind_dual_stack(port) {
+        Ok(listener) => Ok(listener),
+        Err(_) => TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))).await,
+    }
+}
+
+fn bind_dual_stack(port: u16) -> io::Result<TcpListener> {
+    let addr = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    // Same as `TcpListener::bind`: lets a restarted host rebind at once.
+    #[cfg(not(target_os = "windows"))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
 /// Accept peer connections on a **pre-bound** [`TcpListener`].
 ///
 /// The caller is responsible for binding the listener (so that bind failures
@@ -50,24 +261,15 @@ use crate::runtime::{RuntimeEvent, RuntimeLogEvent};
 ///
 /// # Arguments
 ///
-/// * `device_id`   — our unique peer identifier.
-/// * `device_name` — human-friendly device name (displayed on the remote).
-/// * `listener`    — a pre-bound TCP listener.
-/// * `tx_out`      — broadcast channel carrying outbound clipboard updates
-///                    (from our local clipboard monitor).
-/// * `tx_in`       — channel to forward inbound clipboard payloads into the
-///                    local clipboard setter.
-/// * `events`      — runtime event sink for logging / UI.
-/// * `cancel`      — token to signal graceful shutdown.
+/// * `ctx`      — shared session context (identity, channels, trust).
+/// * `listener` — a pre-bound TCP listener.
+/// * `cancel`   — token to signal graceful shutdown.
 pub async fn run_tcp_host_on_listener(
-    device_id: String,
-    device_name: String,
+    ctx: PeerContext,
     listener: TcpListener,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
     cancel: CancellationToken,
 ) {
+    let events = ctx.events.clone();
     emit_log(
         &events,
         Level::Info,
@@ -87,28 +289,38 @@ pub async fn run_tcp_host_on_listener(
             accepted = listener.accept() => {
                 match accepted {
                     Ok((stream, peer_addr)) => {
+                        // IPv4 clients of the dual-stack socket show up as
+                        // `::ffff:a.b.c.d`.
+                        let peer_addr =
+                            SocketAddr::new(peer_addr.ip().to_canonical(), peer_addr.port());
                         emit_log(
                             &events,
                             Level::Info,
                             format!("LAN host accepted connection from {}", peer_addr),
                         ).await;
 
-                        let did = device_id.clone();
-                        let dname = device_name.clone();
-                        let tx_o = tx_out.clone();
-                        let tx_i = tx_in.clone();
+                        let session_ctx = ctx.clone();
                         let ev = events.clone();
                         let ct = cancel.child_token();
 
                         tokio::spawn(async move {
-                            if let Err(e) = host_session(
-                                stream, did, dname, tx_o, tx_i, ev.clone(), ct,
-                            ).await {
-                                emit_log(
-                                    &ev,
-                                    Level::Warn,
-                                    format!("LAN host session with {} ended: {}", peer_addr, e),
-                                ).await;
+                            match host_session(stream, session_ctx, ct).await {
+                                Err(e) if is_incompatible(&e) => report_incompatible(&ev, &e).await,
+                                Err(e) if is_duplicate(&e) => {
+                                    emit_log(
+                                        &ev,
+                                        Level::Debug,
+                                        format!("LAN host session with {} not needed: {}", peer_addr, e),
+                                    ).await;
+                                }
+                                Err(e) => {
+                                    emit_log(
+                                        &ev,
+                                        Level::Warn,
+                                        format!("LAN host session with {} ended: {}", peer_addr, e),
+                                    ).await;
+                                }
+                                Ok(()) => {}
                             }
                         });
                     }
@@ -136,39 +348,57 @@ pub async fn run_tcp_host_on_listener(
 /// The function enters an outer loop that keeps attempting to establish a TCP
 /// connection. Once connected it performs the `Hello`/`Welcome` handshake and
 /// delegates to [`run_peer_session`]. When the session ends (error or remote
-/// disconnect) it waits with exponential back-off before retrying.
+/// disconnect) it waits with exponential back-off before retrying. A peer
+/// with an incompatible protocol version is reported once as a
+/// [`RuntimeEvent::Error`] and then only retried every
+/// [`INCOMPATIBLE_RETRY_SECS`].
+///
+/// While the peer is blocked no connection is attempted; the task idles
+/// instead of exiting so that unblocking it takes effect without a restart.
+/// A connection that turns out to duplicate a live session is retried only
+/// every [`MAX_RECONNECT_DELAY_SECS`], in case that session goes away.
 ///
 /// # Arguments
 ///
-/// * `peer_addr`   — `"<ip>:<port>"` of the remote host.
-/// * `device_id`   — our unique peer identifier.
-/// * `device_name` — our human-friendly device name.
-/// * `tx_out`      — broadcast channel carrying outbound clipboard updates.
-/// * `tx_in`       — channel to forward inbound clipboard payloads.
-/// * `events`      — runtime event sink.
-/// * `cancel`      — token to signal graceful shutdown.
+/// * `target`    — the discovered peer or static address being dialed.
+/// * `peer_addr` — `"<host>:<port>"` of the remote host.
+/// * `ctx`       — shared session context (identity, channels, trust).
+/// * `cancel`    — token to signal graceful shutdown.
 pub async fn run_tcp_client(
+    mut target: DialTarget,
     peer_addr: String,
-    device_id: String,
-    device_name: String,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
+    ctx: PeerContext,
     cancel: CancellationToken,
 ) {
+    let events = ctx.events.clone();
     let mut delay_secs = INITIAL_RECONNECT_DELAY_SECS;
+    let mut incompatible_reported = false;
 
     while !cancel.is_cancelled() {
+        if target
+            .device_id()
+            .is_some_and(|id| ctx.trust.is_blocked(id))
+        {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = sleep(Duration::from_secs(MAX_RECONNECT_DELAY_SECS)) => continue,
+            }
+        }
+
         emit_log(
             &events,
             Level::Info,
             format!("LAN client connecting to {} …", peer_addr),
         )
         .await;
+        if let Some(id) = target.device_id() {
+            ctx.registry.set_connecting(id, LinkState::Connecting);
+        }
 
+        let mut retry_secs = None;
         let connect_result = tokio::select! {
             _ = cancel.cancelled() => break,
-            r = TcpStream::connect(&peer_addr) => r,
+            r = connect_peer(&peer_addr) => r,
         };
 
         match connect_result {
@@ -182,25 +412,43 @@ pub async fn run_tcp_client(
 
                 // Reset back-off on successful connect.
                 delay_secs = INITIAL_RECONNECT_DELAY_SECS;
+                if let Some(id) = target.device_id() {
+                    ctx.registry.set_connecting(id, LinkState::Handshaking);
+                }
 
-                let result = client_session(
-                    stream,
-                    device_id.clone(),
-                    device_name.clone(),
-                    tx_out.clone(),
-                    tx_in.clone(),
-                    events.clone(),
-                    cancel.child_token(),
-                )
-                .await;
+                let result =
+                    client_session(stream, ctx.clone(), &mut target, cancel.child_token()).await;
 
-                if let Err(e) = result {
-                    emit_log(
-                        &events,
-                        Level::Warn,
-                        format!("LAN client session with {} ended: {}", peer_addr, e),
-                    )
-                    .await;
+                if let (Some(id), Err(e)) = (target.device_id(), &result) {
+                    ctx.registry.connect_failed(id, &e.to_string());
+                }
+                match result {
+                    Err(e) if is_incompatible(&e) => {
+                        if !incompatible_reported {
+                            report_incompatible(&events, &e).await;
+                            incompatible_reported = true;
+                        }
+                        retry_secs = Some(INCOMPATIBLE_RETRY_SECS);
+                    }
+                    Err(e) if is_duplicate(&e) => {
+                        emit_log(
+                            &events,
+                            Level::Debug,
+                            format!("LAN client session with {} not needed: {}", peer_addr, e),
+                        )
+                        .await;
+                        retry_secs = Some(MAX_RECONNECT_DELAY_SECS);
+                    }
+                    Err(e) => {
+                        incompatible_reported = false;
+                        emit_log(
+                            &events,
+                            Level::Warn,
+                            format!("LAN client session with {} ended: {}", peer_addr, e),
+                        )
+                        .await;
+                    }
+                    Ok(()) => incompatible_reported = false,
                 }
             }
             Err(e) => {
@@ -210,6 +458,9 @@ pub async fn run_tcp_client(
                     format!("LAN client connect to {} failed: {}", peer_addr, e),
                 )
                 .await;
+                if let Some(id) = target.device_id() {
+                    ctx.registry.connect_failed(id, &e.to_string());
+                }
             }
         }
 
@@ -217,40 +468,70 @@ pub async fn run_tcp_client(
             break;
         }
 
+        let wait_secs = retry_secs.unwrap_or(delay_secs);
         emit_log(
             &events,
             Level::Info,
-            format!("LAN client reconnecting in {}s …", delay_secs),
+            format!("LAN client reconnecting in {}s …", wait_secs),
         )
         .await;
 
         tokio::select! {
             _ = cancel.cancelled() => break,
-            _ = sleep(Duration::from_secs(delay_secs)) => {},
+            _ = sleep(Duration::from_secs(wait_secs)) => {},
         }
 
         // Exponential back-off with ceiling.
         delay_secs = (delay_secs * 2).min(MAX_RECONNECT_DELAY_SECS);
     }
 
+    target.release(&ctx.registry);
+
     emit_log(&events, Level::Debug, "LAN client reconnect loop stopped").await;
 }
 
+/// Connect to `"<host>:<port>"`, where an IPv6 host is bracketed and may
+/// carry a zone as a number (`[fe80::1%3]`) or interface name
+/// (`[fe80::1%eth0]`). Names are left to the system resolver.
+async fn connect_peer(peer_addr: &str) -> io::Result<TcpStream> {
+    if let Ok(addr) = peer_addr.parse::<SocketAddr>() {
+        return TcpStream::connect(addr).await;
+    }
+    let (host, port) = peer_addr
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid peer address"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    TcpStream::connect((host, port)).await
+}
+
 // ────────────────────────────────────────────────────────────────────────────
 // Session entry-points (host / client handshake wrappers)
 // ────────────────────────────────────────────────────────────────────────────
 
-/// Host-side session: wait for `Hello`, reply with `Welcome`, then enter
-/// the shared session loop.
+/// Host-side session: answer the encrypted handshake, wait for `Hello`,
+/// reply with `Welcome`, then enter the shared session loop.
 async fn host_session(
-    mut stream: TcpStream,
-    device_id: String,
-    device_name: String,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
+    stream: TcpStream,
+    ctx: PeerContext,
     cancel: CancellationToken,
 ) -> Result<()> {
+    // ── Encrypted handshake (cancel-aware + timeout-guarded) ─────────────
+    let secure = tokio::select! {
+        _ = cancel.cancelled() => {
+            return Err(anyhow!("cancelled during key exchange"));
+        }
+        result = timeout(
+            Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+            secure_accept(stream, &ctx.identity),
+        ) => {
+            result
+                .map_err(|_| anyhow!("timed out during key exchange with client"))?
+                .context("key exchange with client")?
+        }
+    };
+    let (link, mut reader, mut writer) = split_secure(secure);
+
     // ── Wait for Hello (cancel-aware + timeout-guarded) ──────────────────
     let hello = tokio::select! {
         _ = cancel.cancelled() => {
@@ -258,7 +539,7 @@ async fn host_session(
         }
         result = timeout(
             Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
-            read_peer_message(&mut stream),
+            read_peer_message(&mut reader),
         ) => {
             result
                 .map_err(|_| anyhow!("timed out waiting for Hello from client"))?
@@ -266,11 +547,8 @@ async fn host_session(
         }
     };
 
-    let (remote_id, remote_name) = match hello {
-        PeerMessage::Hello {
-            device_id: rid,
-            device_name: rname,
-        } => (rid, rname),
+    let (remote, hello) = match hello {
+        PeerMessage::Hello(info) => (link.identify(&info, &ctx)?, info),
         other => {
             return Err(anyhow!(
                 "expected Hello from client, got {:?}",
@@ -279,44 +557,67 @@ async fn host_session(
         }
     };
 
+    if ctx.trust.is_blocked(&remote.device_id) {
+        return Err(anyhow!(
+            "refusing blocked peer {} ({})",
+            remote.device_name,
+            remote.device_id
+        ));
+    }
+
     emit_log(
-        &events,
+        &ctx.events,
         Level::Info,
         format!(
-            "LAN host handshake: remote peer {} ({})",
-            remote_name, remote_id
+            "LAN host handshake: remote peer {} ({}), protocol v{}",
+            remote.device_name, remote.device_id, hello.protocol_version
         ),
     )
     .await;
 
     // ── Send Welcome ─────────────────────────────────────────────────────
-    let welcome = PeerMessage::Welcome {
-        device_id: device_id.clone(),
-        device_name: device_name.clone(),
-    };
-    write_peer_message(&mut stream, &welcome).await?;
+    // Sent before the version check so an incompatible client learns our
+    // version instead of just seeing the connection drop.
+    let welcome = PeerMessage::Welcome(local_handshake(&ctx, &remote));
+    write_peer_message(&mut writer, &welcome).await?;
+    hello.check_version()?;
 
     // ── Enter shared session loop ────────────────────────────────────────
-    run_peer_session(stream, tx_out, tx_in, events, cancel).await
+    run_peer_session(reader, writer, remote, ctx, false, cancel).await
 }
 
-/// Client-side session: send `Hello`, wait for `Welcome`, then enter the
-/// shared session loop.
+/// Client-side session: initiate the encrypted handshake, send `Hello`,
+/// wait for `Welcome`, then enter the shared session loop.
 async fn client_session(
-    mut stream: TcpStream,
-    device_id: String,
-    device_name: String,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
+    stream: TcpStream,
+    ctx: PeerContext,
+    target: &mut DialTarget,
     cancel: CancellationToken,
 ) -> Result<()> {
-    // ── Send Hello ───────────────────────────────────────────────────────
-    let hello = PeerMessage::Hello {
-        device_id: device_id.clone(),
-        device_name: device_name.clone(),
+    // ── Encrypted handshake (cancel-aware + timeout-guarded) ─────────────
+    let secure = tokio::select! {
+        _ = cancel.cancelled() => {
+            return Err(anyhow!("cancelled during key exchange"));
+        }
+        result = timeout(
+            Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+            secure_connect(stream, &ctx.identity),
+        ) => {
+            result
+                .map_err(|_| anyhow!("timed out during key exchange with host"))?
+                .context("key exchange with host")?
+        }
     };
-    write_peer_message(&mut stream, &hello).await?;
+    let (link, mut reader, mut writer) = split_secure(secure);
+
+    // ── Send Hello ───────────────────────────────────────────────────────
+    let hello = PeerMessage::Hello(HandshakeInfo::local(
+        ctx.device_id.clone(),
+        ctx.device_name.clone(),
+        ctx.group
+            .member_proof(&ctx.device_id, &link.channel_binding),
+    ));
+    write_peer_message(&mut writer, &hello).await?;
 
     // ── Wait for Welcome (cancel-aware + timeout-guarded) ────────────────
     let welcome = tokio::select! {
@@ -325,7 +626,7 @@ async fn client_session(
         }
         result = timeout(
             Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
-            read_peer_message(&mut stream),
+            read_peer_message(&mut reader),
         ) => {
             result
                 .map_err(|_| anyhow!("timed out waiting for Welcome from host"))?
@@ -333,11 +634,12 @@ async fn client_session(
         }
     };
 
-    let (remote_id, remote_name) = match welcome {
-        PeerMessage::Welcome {
-            device_id: rid,
-            device_name: rname,
-        } => (rid, rname),
+    let (remote, welcome) = match welcome {
+        PeerMessage::Welcome(info) => {
+            let remote = link.identify(&info, &ctx)?;
+            info.check_version()?;
+            (remote, info)
+        }
         other => {
             return Err(anyhow!(
                 "expected Welcome from host, got {:?}",
@@ -346,28 +648,139 @@ async fn client_session(
         }
     };
 
+    // Claimed first so that a blocked static peer is not redialed.
+    target.claim(&ctx.registry, &remote)?;
+    if ctx.trust.is_blocked(&remote.device_id) {
+        return Err(anyhow!(
+            "refusing blocked peer {} ({})",
+            remote.device_name,
+            remote.device_id
+        ));
+    }
+
     emit_log(
-        &events,
+        &ctx.events,
         Level::Info,
         format!(
-            "LAN client handshake OK: remote peer {} ({})",
-            remote_name, remote_id
+            "LAN client handshake OK: remote peer {} ({}), protocol v{}",
+            remote.device_name, remote.device_id, welcome.protocol_version
         ),
     )
     .await;
 
     // ── Enter shared session loop ────────────────────────────────────────
-    run_peer_session(stream, tx_out, tx_in, events, cancel).await
+    run_peer_session(reader, writer, remote, ctx, true, cancel).await
+}
+
+/// What the encrypted handshake established about a connection, before
+/// `Hello`/`Welcome` tell us who claims to be on the other end.
+struct SecureLink {
+    /// Device id derived from the authenticated remote key.
+    derived_id: String,
+    public_key: String,
+    channel_binding: String,
+}
+
+impl SecureLink {
+    /// Accept the remote's claimed identity only if `device_id` is the one
+    /// its handshake key derives to and `group_proof` shows it knows our
+    /// sync group secret. The session uses the capabilities both sides
+    /// support.
+    fn identify(&self, info: &HandshakeInfo, ctx: &PeerContext) -> Result<RemotePeer> {
+        let group = &ctx.group;
+        let device_id = &info.device_id;
+        let device_name = &info.device_name;
+        if *device_id != self.derived_id {
+            return Err(anyhow!(
+                "peer claimed device id {} but its key belongs to {}",
+                device_id,
+                self.derived_id
+            ));
+        }
+        if *device_id == ctx.device_id {
+            return Err(anyhow!("connected to this device itself"));
+        }
+        if !group.verify_member_proof(device_id, &self.channel_binding, &info.group_proof) {
+            return Err(anyhow!(
+                "peer {} ({}) is not in sync group {}",
+                device_name,
+                device_id,
+                group.display_name()
+            ));
+        }
+        Ok(RemotePeer {
+            device_id: device_id.clone(),
+            device_name: device_name.clone(),
+            public_key: self.public_key.clone(),
+            channel_binding: self.channel_binding.clone(),
+            capabilities: Capabilities::local().negotiate(&info.capabilities),
+        })
+    }
+}
+
+/// Our `Welcome`, with the group proof bound to `remote`'s session.
+fn local_handshake(ctx: &PeerContext, remote: &RemotePeer) -> HandshakeInfo {
+    HandshakeInfo::local(
+        ctx.device_id.clone(),
+        ctx.device_name.clone(),
+        ctx.group
+            .member_proof(&ctx.device_id, &remote.channel_binding),
+    )
+}
+
+/// The remote end of an established session.
+struct RemotePeer {
+    device_id: String,
+    device_name: String,
+    /// Base64 device public key authenticated by the handshake.
+    public_key: String,
+    /// Base64 handshake hash; the pairing exchange and group proofs are
+    /// bound to it.
+    channel_binding: String,
+    /// Features both sides support.
+    capabilities: Capabilities,
+}
+
+fn split_secure(secure: SecureStream) -> (SecureLink, SecureReader, SecureWriter) {
+    let link = SecureLink {
+        derived_id: device_id_from_public_key(secure.remote_public_key()),
+        public_key: encode_key(secure.remote_public_key()),
+        channel_binding: encode_key(secure.handshake_hash()),
+    };
+    let (reader, writer) = secure.into_split();
+    (link, reader, writer)
 }
 
 // ────────────────────────────────────────────────────────────────────────────
 // Shared session loop (DRY — used by both host and client)
 // ────────────────────────────────────────────────────────────────────────────
 
+/// A pairing we started with `PairStart` and are waiting to hear back
+/// about.
+struct OutgoingPairing {
+    stage: PairingStage,
+    deadline: Instant,
+    reply: oneshot::Sender<Result<()>>,
+}
+
+enum PairingStage {
+    /// Waiting for the remote's `PairShare`.
+    AwaitingShare(PinExchange),
+    /// Confirmed our key, waiting for `PairAccept`.
+    AwaitingAccept(PairingKey),
+}
+
+/// A `PairStart` we answered with the PIN displayed here, waiting for the
+/// remote's `PairConfirm`.
+struct IncomingPairing {
+    pin: String,
+    key: PairingKey,
+}
+
 /// Bidirectional clipboard relay with heartbeat keep-alive.
 ///
 /// This function is role-agnostic — it works identically whether we are the
-/// TCP host or the TCP client. It runs three concurrent concerns via
+/// TCP host or the TCP client. It runs six concurrent concerns via
 /// `tokio::select!`:
 ///
 /// 1. **Heartbeat tick** — sends a `Ping` every [`HEARTBEAT_INTERVAL_SECS`]
@@ -375,40 +788,187 @@ async fn client_session(
 ///    [`HEARTBEAT_TIMEOUT_SECS`] the connection is considered dead.
 ///
 /// 2. **Outbound clipboard** — listens on `tx_out` for local clipboard
-///    changes and forwards them as `PeerMessage::Clipboard`.
+///    changes and forwards them as `PeerMessage::Clipboard` if the remote
+///    is paired, or starts a chunked transfer for large payloads.
+///
+/// 3. **Addressed clipboard** — items queued on this session's outbox
+///    because they were sent to this peer alone; sent the same way.
+///
+/// 4. **Outbound transfer** — sends the next chunk of a transfer in
+///    flight, one per loop iteration, then starts the next one waiting
+///    (see [`super::transfer`]).
+///
+/// 5. **Session commands** — requests routed to this session by
+///    `remote_id` (pair with a PIN, approve, close, send a relayed
+///    receipt), and receipts for items received in this session.
 ///
-/// 3. **Inbound read** — reads frames from the TCP stream and dispatches:
-///    - `Ping`      → reply with `Pong`
-///    - `Pong`      → update last-pong timestamp
-///    - `Clipboard` → forward payload into `tx_in`
+/// 6. **Inbound read** — frames decoded by a dedicated reader task (reads
+///    are not cancel-safe, so they never race inside the `select!`):
+///    - `Ping`        → reply with `Pong`
+///    - `Pong`        → update last-pong timestamp
+///    - `Clipboard`   → forward payload into `tx_in` if the remote is paired
+///    - `Transfer*`   → reassemble a chunked payload, then forward it
+///    - `Ack`/`Nack`  → tally a delivery receipt, or pass it on towards
+///      the item's origin
+///    - `Pair*`       → run the PIN pairing exchange
 ///    - anything else → log and ignore
+///
+/// Only one session per peer is kept. The connector applies the device-id
+/// tie-break before dialing, but a static peer is dialed before its id is
+/// known, so the rule is enforced again here once the handshake is done:
+/// a session dialed by the side with the greater id (`initiated` tells
+/// which side we are) is preferred and is never replaced by one dialed the
+/// other way, which is refused instead. Any other new session replaces the
+/// live one, as a redial does after the old connection silently died. Both
+/// ends judge a connection the same way, so they keep the same one.
 async fn run_peer_session(
-    stream: TcpStream,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
-    events: mpsc::Sender<RuntimeEvent>,
+    reader: SecureReader,
+    writer: SecureWriter,
+    remote: RemotePeer,
+    ctx: PeerContext,
+    initiated: bool,
+    cancel: CancellationToken,
+) -> Result<()> {
+    let remote_id = remote.device_id.clone();
+    let remote_name = remote.device_name.clone();
+    let (cmd_tx, mut cmd_rx) = mpsc::channel::<SessionCommand>(8);
+    let (outbox_tx, mut outbox_rx) = mpsc::channel::<ClipboardItem>(8);
+    let preferred = initiated == (ctx.device_id > remote_id);
+    let replaced = {
+        let mut sessions = ctx.sessions.lock();
+        if !preferred && sessions.get(&remote_id).is_some_and(|live| live.preferred) {
+            return Err(DuplicateSession(format!(
+                "already connected to {} ({})",
+                remote_name, remote_id
+            ))
+            .into());
+        }
+        sessions.insert(
+            remote_id.clone(),
+            SessionHandle {
+                commands: cmd_tx.clone(),
+                outbox: outbox_tx,
+                preferred,
+            },
+        )
+    };
+    if let Some(old) = replaced {
+        let _ = old
+            .commands
+            .send(SessionCommand::Close {
+                reason: "replaced by a new session",
+            })
+            .await;
+    }
+
+    if ctx.trust.is_key_mismatch(&remote_id, &remote.public_key) {
+        emit_log(
+            &ctx.events,
+            Level::Warn,
+            format!(
+                "LAN peer {} ({}) presented a different device key than when it was paired — pair again to trust it",
+                remote_name, remote_id
+            ),
+        )
+        .await;
+    } else if !ctx.trust.is_trusted(&remote_id, &remote.public_key) {
+        emit_log(
+            &ctx.events,
+            Level::Warn,
+            format!(
+                "LAN peer {} ({}) is pending — clipboard sync disabled until it is paired or approved",
+                remote_name, remote_id
+            ),
+        )
+        .await;
+    }
+
+    let role = if initiated {
+        SessionRole::Client
+    } else {
+        SessionRole::Host
+    };
+    let session = ctx.registry.session_started(
+        &remote_id,
+        &remote_name,
+        role,
+        reader.peer_addr(),
+        writer.byte_counter(),
+        reader.byte_counter(),
+    );
+    emit_peer_list(&ctx.registry, &ctx.trust, &ctx.events).await;
+
+    let result = peer_session_loop(
+        reader,
+        writer,
+        &remote,
+        &ctx,
+        &mut cmd_rx,
+        &mut outbox_rx,
+        cancel,
+    )
+    .await;
+
+    let error = result.as_ref().err().map(|e| e.to_string());
+    ctx.registry.session_ended(&remote_id, session, error);
+    emit_peer_list(&ctx.registry, &ctx.trust, &ctx.events).await;
+
+    // Only unregister if a newer session for the same peer hasn't replaced us.
+    let mut sessions = ctx.sessions.lock();
+    if sessions
+        .get(&remote_id)
+        .is_some_and(|h| h.commands.same_channel(&cmd_tx))
+    {
+        sessions.remove(&remote_id);
+    }
+    result
+}
+
+/// The steady-state loop of [`run_peer_session`], split out so the session
+/// can always unregister itself however the loop exits.
+async fn peer_session_loop(
+    reader: SecureReader,
+    writer: SecureWriter,
+    remote: &RemotePeer,
+    ctx: &PeerContext,
+    cmd_rx: &mut mpsc::Receiver<SessionCommand>,
+    outbox_rx: &mut mpsc::Receiver<ClipboardItem>,
     cancel: CancellationToken,
 ) -> Result<()> {
-    let (reader_half, writer_half) = stream.into_split();
+    let events = &ctx.events;
+    let remote_id = remote.device_id.as_str();
+    let remote_name = remote.device_name.as_str();
 
-    // Wrap the writer in a mutex so both heartbeat and clipboard-send can
-    // use it without requiring `split` ownership gymnastics.
-    let writer = std::sync::Arc::new(tokio::sync::Mutex::new(writer_half));
+    let (inbound_tx, mut inbound_rx) = mpsc::channel::<Result<PeerMessage>>(16);
+    let _reader_task = AbortOnDrop(tokio::spawn(read_loop(reader, inbound_tx)));
 
-    let mut rx_updates = tx_out.subscribe();
+    let mut rx_updates = ctx.tx_out.subscribe();
     let mut heartbeat_tick = interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS));
-    let mut last_pong = Instant::now();
+    // The clipboard setter reports here what became of items this session
+    // delivered.
+    let (receipt_tx, mut receipt_rx) = mpsc::channel::<DeliveryReceipt>(16);
+    let mut session = Session {
+        writer,
+        remote,
+        ctx,
+        last_pong: Instant::now(),
+        outgoing_pairing: None,
+        incoming_pairing: None,
+        next_transfer_id: 1,
+        outgoing_transfer: None,
+        queued_transfers: VecDeque::new(),
+        incoming_transfer: None,
+        receipts: receipt_tx,
+    };
 
-    // We need a mutable reference to `reader_half` across iterations, so
-    // we wrap it in an Option to allow taking ownership in the select loop.
-    let reader = std::sync::Arc::new(tokio::sync::Mutex::new(reader_half));
+    session.catch_up().await?;
 
     loop {
         // Check heartbeat timeout *before* entering the select so we don't
         // wait a full tick if we're already past the deadline.
-        if last_pong.elapsed() > Duration::from_secs(HEARTBEAT_TIMEOUT_SECS) {
+        if session.last_pong.elapsed() > Duration::from_secs(HEARTBEAT_TIMEOUT_SECS) {
             emit_log(
-                &events,
+                events,
                 Level::Warn,
                 "LAN peer heartbeat timeout — closing session",
             )
@@ -416,51 +976,61 @@ async fn run_peer_session(
             return Err(anyhow!("heartbeat timeout"));
         }
 
+        // Give up on a pairing request the remote never answered. Checked
+        // here so the heartbeat tick bounds how late we notice.
+        if session
+            .outgoing_pairing
+            .as_ref()
+            .is_some_and(|p| Instant::now() >= p.deadline)
+        {
+            if let Some(p) = session.outgoing_pairing.take() {
+                let _ = p.reply.send(Err(anyhow!(
+                    "{} did not answer the pairing request",
+                    remote_name
+                )));
+            }
+        }
+
+        // Drop a chunked transfer the remote stopped feeding.
+        if session
+            .incoming_transfer
+            .as_ref()
+            .is_some_and(|t| t.is_idle())
+        {
+            if let Some(t) = session.incoming_transfer.take() {
+                emit_log(
+                    events,
+                    Level::Warn,
+                    format!(
+                        "LAN transfer {} from {} stalled — dropped",
+                        t.id(),
+                        remote_name
+                    ),
+                )
+                .await;
+            }
+        }
+
         tokio::select! {
             // ── Cancellation ─────────────────────────────────────────────
             _ = cancel.cancelled() => {
-                emit_log(&events, Level::Debug, "LAN peer session cancelled").await;
+                emit_log(events, Level::Debug, "LAN peer session cancelled").await;
                 return Ok(());
             }
 
             // ── Heartbeat tick ───────────────────────────────────────────
             _ = heartbeat_tick.tick() => {
-                let ts = now_millis();
-                let ping = PeerMessage::Ping { ts };
-                let frame = encode_peer_message(&ping);
-                let mut w = writer.lock().await;
-                if let Err(e) = w.write_all(&frame).await {
-                    return Err(anyhow!("failed to send ping: {}", e));
-                }
+                let ping = PeerMessage::Ping { ts: now_millis() };
+                session.send(&ping).await.map_err(|e| anyhow!("failed to send ping: {}", e))?;
             }
 
             // ── Outbound clipboard ───────────────────────────────────────
             outbound = rx_updates.recv() => {
                 match outbound {
-                    Ok(update) => {
-                        let msg = PeerMessage::Clipboard {
-                            content_type: update.payload.content_type.clone(),
-                            data: update.payload.data.clone(),
-                            timestamp: update.payload.timestamp,
-                        };
-                        let frame = encode_peer_message(&msg);
-                        let mut w = writer.lock().await;
-                        if let Err(e) = w.write_all(&frame).await {
-                            return Err(anyhow!("failed to send clipboard: {}", e));
-                        }
-                        emit_log(
-                            &events,
-                            Level::Debug,
-                            format!(
-                                "LAN sent clipboard ({}) {} bytes",
-                                update.payload.content_type,
-                                update.payload.data.len()
-                            ),
-                        ).await;
-                    }
+                    Ok(update) => session.send_clipboard(&update).await?,
                     Err(broadcast::error::RecvError::Lagged(n)) => {
                         emit_log(
-                            &events,
+                            events,
                             Level::Warn,
                             format!("LAN outbound clipboard lagged by {} messages", n),
                         ).await;
@@ -472,63 +1042,72 @@ async fn run_peer_session(
                 }
             }
 
+            // ── Addressed clipboard ──────────────────────────────────────
+            Some(update) = outbox_rx.recv() => {
+                emit_log(
+                    events,
+                    Level::Info,
+                    format!(
+                        "LAN sending clipboard ({}) to {} only",
+                        update.content_type, remote_name
+                    ),
+                ).await;
+                session.send_clipboard(&update).await?;
+            }
+
+            // ── Outbound transfer: one chunk per iteration ──────────────
+            _ = std::future::ready(()), if session.is_sending() => {
+                session.send_next_chunk().await?;
+            }
+
+            // ── Session commands ─────────────────────────────────────────
+            Some(cmd) = cmd_rx.recv() => {
+                match cmd {
+                    SessionCommand::Pair { pin, reply } => {
+                        session.request_pairing(pin, reply).await?;
+                    }
+                    SessionCommand::Approve { reply } => {
+                        let trusted = record_trust(ctx, remote, "approval").await;
+                        let caught_up = trusted.is_ok();
+                        let _ = reply.send(trusted);
+                        if caught_up {
+                            session.catch_up().await?;
+                        }
+                    }
+                    SessionCommand::Close { reason } => {
+                        emit_log(
+                            events,
+                            Level::Info,
+                            format!(
+                                "LAN closing session with {} ({}): {}",
+                                remote_name, remote_id, reason
+                            ),
+                        ).await;
+                        return Ok(());
+                    }
+                    SessionCommand::Send(msg) => {
+                        session
+                            .send(&msg)
+                            .await
+                            .map_err(|e| anyhow!("failed to send {}: {}", msg_type_name(&msg), e))?;
+                    }
+                }
+            }
+
+            // ── Delivery receipts ────────────────────────────────────────
+            Some(receipt) = receipt_rx.recv() => {
+                session
+                    .send(&PeerMessage::receipt(receipt))
+                    .await
+                    .map_err(|e| anyhow!("failed to send delivery receipt: {}", e))?;
+            }
+
             // ── Inbound read ─────────────────────────────────────────────
-            inbound = async {
-                let mut r = reader.lock().await;
-                read_peer_message_from_reader(&mut *r).await
-            } => {
-                match inbound {
+            inbound = inbound_rx.recv() => {
+                match inbound.unwrap_or_else(|| Err(anyhow!("reader task stopped"))) {
                     Ok(msg) => {
-                        match msg {
-                            PeerMessage::Ping { ts } => {
-                                let pong = PeerMessage::Pong { ts };
-                                let frame = encode_peer_message(&pong);
-                                let mut w = writer.lock().await;
-                                if let Err(e) = w.write_all(&frame).await {
-                                    return Err(anyhow!("failed to send pong: {}", e));
-                                }
-                            }
-                            PeerMessage::Pong { .. } => {
-                                last_pong = Instant::now();
-                            }
-                            PeerMessage::Clipboard { content_type, data, timestamp } => {
-                                let payload = ClipboardBroadcastPayload {
-                                    content_type: content_type.clone(),
-                                    data,
-                                    timestamp,
-                                };
-                                let _ = tx_in.send(payload).await;
-
-                                let ct_label = match content_type.as_str() {
-                                    CONTENT_TYPE_TEXT => "text",
-                                    CONTENT_TYPE_IMAGE_PNG => "image",
-                                    other => other,
-                                };
-                                emit_log(
-                                    &events,
-                                    Level::Info,
-                                    format!("LAN received clipboard ({})", ct_label),
-                                ).await;
-
-                                let _ = events
-                                    .send(RuntimeEvent::ClipboardReceived {
-                                        content_type,
-                                    })
-                                    .await;
-                            }
-                            // Handshake messages arriving after the session has
-                            // started are unexpected but not fatal — just log.
-                            other => {
-                                emit_log(
-                                    &events,
-                                    Level::Warn,
-                                    format!(
-                                        "LAN peer session: unexpected message type {:?}",
-                                        msg_type_name(&other)
-                                    ),
-                                ).await;
-                            }
-                        }
+                        ctx.registry.record_activity(remote_id);
+                        session.handle_message(msg).await?;
                     }
                     Err(e) => {
                         // Connection closed or read error.
@@ -540,75 +1119,1179 @@ async fn run_peer_session(
     }
 }
 
-// ────────────────────────────────────────────────────────────────────────────
-// Frame I/O helpers
-// ────────────────────────────────────────────────────────────────────────────
-
-/// Write a single length-prefixed JSON frame to `stream`.
-async fn write_peer_message(stream: &mut TcpStream, msg: &PeerMessage) -> Result<()> {
-    let frame = encode_peer_message(msg);
-    stream.write_all(&frame).await.context("write_peer_message")
+/// A live session's writer and the state the arms of
+/// [`peer_session_loop`] share.
+struct Session<'a> {
+    writer: SecureWriter,
+    remote: &'a RemotePeer,
+    ctx: &'a PeerContext,
+    last_pong: Instant,
+    outgoing_pairing: Option<OutgoingPairing>,
+    incoming_pairing: Option<IncomingPairing>,
+    next_transfer_id: u64,
+    outgoing_transfer: Option<OutgoingTransfer>,
+    /// Large items waiting for `outgoing_transfer` to finish.
+    queued_transfers: VecDeque<ClipboardItem>,
+    incoming_transfer: Option<IncomingTransfer>,
+    /// Handed to the clipboard setter with every item delivered from this
+    /// session.
+    receipts: ReceiptSender,
 }
 
-/// Read a single length-prefixed JSON frame from a `TcpStream`.
-async fn read_peer_message(stream: &mut TcpStream) -> Result<PeerMessage> {
-    // Read 4-byte length prefix.
-    let mut len_buf = [0u8; 4];
-    stream
-        .read_exact(&mut len_buf)
-        .await
-        .context("reading frame length")?;
-    let len = u32::from_be_bytes(len_buf);
-
-    if len > MAX_FRAME_SIZE {
-        return Err(anyhow!(
-            "frame too large: {} bytes (max {})",
-            len,
-            MAX_FRAME_SIZE
-        ));
+impl Session<'_> {
+    async fn send(&mut self, msg: &PeerMessage) -> Result<()> {
+        send_message(&mut self.writer, msg).await
     }
 
-    let mut payload = vec![0u8; len as usize];
-    stream
-        .read_exact(&mut payload)
-        .await
-        .context("reading frame payload")?;
-
-    serde_json::from_slice(&payload).context("deserialising PeerMessage")
-}
-
-/// Same as [`read_peer_message`] but works with an
-/// [`tokio::net::tcp::OwnedReadHalf`] obtained via `stream.into_split()`.
-async fn read_peer_message_from_reader(
-    reader: &mut tokio::net::tcp::OwnedReadHalf,
-) -> Result<PeerMessage> {
-    let mut len_buf = [0u8; 4];
-    reader
-        .read_exact(&mut len_buf)
-        .await
-        .context("reading frame length")?;
-    let len = u32::from_be_bytes(len_buf);
-
-    if len > MAX_FRAME_SIZE {
-        return Err(anyhow!(
-            "frame too large: {} bytes (max {})",
-            len,
-            MAX_FRAME_SIZE
-        ));
+    async fn log(&self, level: Level, message: impl Into<String>) {
+        emit_log(&self.ctx.events, level, message).await;
     }
 
-    let mut payload = vec![0u8; len as usize];
-    reader
-        .read_exact(&mut payload)
-        .await
-        .context("reading frame payload")?;
-
-    serde_json::from_slice(&payload).context("deserialising PeerMessage")
-}
-
-// ────────────────────────────────────────────────────────────────────────────
-// Tiny helpers
-// ────────────────────────────────────────────────────────────────────────────
+    /// Handle a frame read from the remote (see [`run_peer_session`]).
+    async fn handle_message(&mut self, msg: PeerMessage) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        let remote_id = remote.device_id.as_str();
+        match msg {
+            PeerMessage::Ping { ts } => {
+                let pong = PeerMessage::Pong { ts };
+                self.send(&pong)
+                    .await
+                    .map_err(|e| anyhow!("failed to send pong: {}", e))?;
+            }
+            PeerMessage::Pong { ts } => {
+                self.last_pong = Instant::now();
+                ctx.registry
+                    .record_rtt(remote_id, now_millis().saturating_sub(ts));
+            }
+            PeerMessage::Clipboard {
+                content_type,
+                data,
+                timestamp,
+                logical,
+                compression,
+                origin,
+            } => {
+                let clock = Hlc::new(timestamp, logical);
+                let item = ClipboardItem::from_wire(
+                    content_type,
+                    data,
+                    compression.as_deref(),
+                    remote_id.to_string(),
+                    clock,
+                );
+                deliver_decoded(ctx, remote, item, origin, &self.receipts).await;
+            }
+            PeerMessage::ClipboardBinary {
+                content_type,
+                timestamp,
+                logical,
+                compression,
+                origin,
+                data,
+            } => {
+                let clock = Hlc::new(timestamp, logical);
+                let item = ClipboardItem::from_bytes(
+                    content_type,
+                    data,
+                    compression.as_deref(),
+                    remote_id.to_string(),
+                    clock,
+                );
+                deliver_decoded(ctx, remote, item, origin, &self.receipts).await;
+            }
+            PeerMessage::TransferBegin {
+                transfer_id,
+                content_type,
+                timestamp,
+                logical,
+                total_len,
+                digest,
+                binary,
+                compression,
+                origin,
+            } => {
+                let transfer = IncomingTransfer::new(
+                    transfer_id,
+                    content_type,
+                    Hlc::new(timestamp, logical),
+                    total_len,
+                    digest,
+                    binary,
+                    compression,
+                );
+                self.begin_transfer(transfer_id, transfer, origin).await?;
+            }
+            PeerMessage::TransferChunk {
+                transfer_id,
+                index,
+                data,
+            } => {
+                let data = data.as_bytes();
+                self.push_chunk(transfer_id, index, data, false).await;
+            }
+            PeerMessage::TransferChunkBinary {
+                transfer_id,
+                index,
+                data,
+            } => {
+                self.push_chunk(transfer_id, index, &data, true).await;
+            }
+            PeerMessage::TransferEnd { transfer_id } => self.end_transfer(transfer_id).await,
+            PeerMessage::TransferAbort {
+                transfer_id,
+                reason,
+            } => self.abort_transfer(transfer_id, &reason).await,
+            PeerMessage::Ack(receipt) | PeerMessage::Nack(receipt) => {
+                route_receipt(ctx, remote, receipt).await;
+            }
+            PeerMessage::PairStart { share } => self.answer_pairing(&share).await?,
+            PeerMessage::PairShare { share } => self.confirm_pairing(&share).await?,
+            PeerMessage::PairConfirm { proof } => self.check_pairing(&proof).await?,
+            PeerMessage::PairAccept { proof } => self.finish_pairing(&proof).await?,
+            PeerMessage::PairReject { reason } => {
+                if let Some(pending) = self.outgoing_pairing.take() {
+                    let _ = pending.reply.send(Err(anyhow!(
+                        "{} rejected pairing: {}",
+                        remote.device_name,
+                        reason
+                    )));
+                }
+            }
+            PeerMessage::Unknown => {
+                self.log(
+                    Level::Debug,
+                    format!(
+                        "LAN ignored unknown message type from {}",
+                        remote.device_name
+                    ),
+                )
+                .await;
+            }
+            // Handshake messages arriving after the session has
+            // started are unexpected but not fatal — just log.
+            other => {
+                self.log(
+                    Level::Warn,
+                    format!(
+                        "LAN peer session: unexpected message type {:?}",
+                        msg_type_name(&other)
+                    ),
+                )
+                .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start pairing with the PIN the remote displays by sending our share
+    /// of the PIN exchange, unless a pairing with the remote is already
+    /// under way.
+    async fn request_pairing(
+        &mut self,
+        pin: String,
+        reply: oneshot::Sender<Result<()>>,
+    ) -> Result<()> {
+        let remote_id = self.remote.device_id.as_str();
+        let remote_name = self.remote.device_name.as_str();
+        if self.outgoing_pairing.is_some() {
+            let _ = reply.send(Err(anyhow!(
+                "pairing with {} already in progress",
+                remote_name
+            )));
+            return Ok(());
+        }
+        if !self.remote.capabilities.pin_exchange {
+            let _ = reply.send(Err(anyhow!(
+                "{} runs an older version that cannot pair with this device — update it",
+                remote_name
+            )));
+            return Ok(());
+        }
+        let binding = self.remote.channel_binding.as_str();
+        let exchange = match PinExchange::start(&pin, &self.ctx.device_id, remote_id, binding, true)
+        {
+            Ok(exchange) => exchange,
+            Err(e) => {
+                let _ = reply.send(Err(e));
+                return Ok(());
+            }
+        };
+        let start = PeerMessage::PairStart {
+            share: exchange.share(),
+        };
+        if let Err(e) = self.send(&start).await {
+            let _ = reply.send(Err(anyhow!("failed to send pairing request: {}", e)));
+            return Err(anyhow!("failed to send pairing request: {}", e));
+        }
+        self.log(
+            Level::Info,
+            format!(
+                "LAN pairing request sent to {} ({})",
+                remote_name, remote_id
+            ),
+        )
+        .await;
+        self.outgoing_pairing = Some(OutgoingPairing {
+            stage: PairingStage::AwaitingShare(exchange),
+            deadline: Instant::now() + Duration::from_secs(PAIRING_REPLY_TIMEOUT_SECS),
+            reply,
+        });
+        Ok(())
+    }
+
+    /// Answer a `PairStart` with our share of an exchange keyed by the PIN
+    /// displayed here. Whether the remote used the same PIN is only known
+    /// from its `PairConfirm`.
+    async fn answer_pairing(&mut self, remote_share: &str) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        let remote_id = remote.device_id.as_str();
+        let Some(pin) = ctx.pairing.current() else {
+            return self
+                .reject_pairing("no pairing PIN is being displayed")
+                .await;
+        };
+        let binding = remote.channel_binding.as_str();
+        let answer = PinExchange::start(&pin, remote_id, &ctx.device_id, binding, false).and_then(
+            |exchange| {
+                let share = exchange.share();
+                Ok((share, exchange.finish(remote_share)?))
+            },
+        );
+        let (share, key) = match answer {
+            Ok(answer) => answer,
+            Err(e) => {
+                self.log(
+                    Level::Warn,
+                    format!(
+                        "LAN pairing request from {} ({}) was invalid: {:#}",
+                        remote.device_name, remote_id, e
+                    ),
+                )
+                .await;
+                return self.reject_pairing("invalid pairing request").await;
+            }
+        };
+        // A new request replaces one the remote never confirmed.
+        self.incoming_pairing = Some(IncomingPairing { pin, key });
+        self.send(&PeerMessage::PairShare { share })
+            .await
+            .map_err(|e| anyhow!("failed to send pairing reply: {}", e))
+    }
+
+    /// Derive the key from the remote's `PairShare` and confirm it.
+    async fn confirm_pairing(&mut self, remote_share: &str) -> Result<()> {
+        let Some(OutgoingPairing {
+            stage: PairingStage::AwaitingShare(exchange),
+            deadline,
+            reply,
+        }) = self
+            .outgoing_pairing
+            .take_if(|p| matches!(p.stage, PairingStage::AwaitingShare(_)))
+        else {
+            self.log(Level::Warn, "LAN unsolicited PairShare ignored")
+                .await;
+            return Ok(());
+        };
+        let key = match exchange.finish(remote_share) {
+            Ok(key) => key,
+            Err(e) => {
+                let _ = reply.send(Err(e.context(format!(
+                    "{} sent an invalid pairing reply",
+                    self.remote.device_name
+                ))));
+                return Ok(());
+            }
+        };
+        let confirm = PeerMessage::PairConfirm {
+            proof: key.request_proof(),
+        };
+        if let Err(e) = self.send(&confirm).await {
+            let _ = reply.send(Err(anyhow!("failed to send pairing confirmation: {}", e)));
+            return Err(anyhow!("failed to send pairing confirmation: {}", e));
+        }
+        self.outgoing_pairing = Some(OutgoingPairing {
+            stage: PairingStage::AwaitingAccept(key),
+            deadline,
+            reply,
+        });
+        Ok(())
+    }
+
+    /// Check the remote's `PairConfirm` against the PIN displayed here and
+    /// answer it.
+    async fn check_pairing(&mut self, proof: &str) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        let Some(incoming) = self.incoming_pairing.take() else {
+            self.log(Level::Warn, "LAN unsolicited PairConfirm ignored")
+                .await;
+            return Ok(());
+        };
+        let confirmed = incoming.key.verify_request_proof(proof);
+        let mut trusted = false;
+        let reply = match ctx.pairing.settle(&incoming.pin, confirmed) {
+            PinCheck::Accepted => match record_trust(ctx, remote, "PIN pairing").await {
+                Ok(()) => {
+                    trusted = true;
+                    PeerMessage::PairAccept {
+                        proof: incoming.key.accept_proof(),
+                    }
+                }
+                Err(_) => PeerMessage::PairReject {
+                    reason: "the pairing could not be saved".into(),
+                },
+            },
+            PinCheck::WrongPin => {
+                self.log(
+                    Level::Warn,
+                    format!(
+                        "LAN pairing request from {} ({}) used a wrong PIN",
+                        remote.device_name, remote.device_id
+                    ),
+                )
+                .await;
+                PeerMessage::PairReject {
+                    reason: "wrong PIN".into(),
+                }
+            }
+            PinCheck::NoPendingPin => PeerMessage::PairReject {
+                reason: "no pairing PIN is being displayed".into(),
+            },
+        };
+        self.send(&reply)
+            .await
+            .map_err(|e| anyhow!("failed to send pairing reply: {}", e))?;
+        // After the accept, which is when the remote starts trusting us.
+        if trusted {
+            self.catch_up().await?;
+        }
+        Ok(())
+    }
+
+    async fn reject_pairing(&mut self, reason: &str) -> Result<()> {
+        let reject = PeerMessage::PairReject {
+            reason: reason.into(),
+        };
+        self.send(&reject)
+            .await
+            .map_err(|e| anyhow!("failed to send pairing reply: {}", e))
+    }
+
+    /// Complete our pairing request once the remote accepted it with a
+    /// valid confirmation.
+    async fn finish_pairing(&mut self, proof: &str) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        let Some(OutgoingPairing {
+            stage: PairingStage::AwaitingAccept(key),
+            reply,
+            ..
+        }) = self
+            .outgoing_pairing
+            .take_if(|p| matches!(p.stage, PairingStage::AwaitingAccept(_)))
+        else {
+            self.log(Level::Warn, "LAN unsolicited PairAccept ignored")
+                .await;
+            return Ok(());
+        };
+        if !key.verify_accept_proof(proof) {
+            let _ = reply.send(Err(anyhow!(
+                "{} sent an invalid pairing proof",
+                remote.device_name
+            )));
+            return Ok(());
+        }
+        let trusted = record_trust(ctx, remote, "PIN pairing").await;
+        let caught_up = trusted.is_ok();
+        let _ = reply.send(trusted);
+        if caught_up {
+            self.catch_up().await?;
+        }
+        Ok(())
+    }
+
+    /// Start receiving a chunked transfer from a trusted remote, replacing
+    /// any unfinished one. A transfer we cannot take is refused with a
+    /// `TransferAbort` (and a receipt when the item has an origin).
+    async fn begin_transfer(
+        &mut self,
+        transfer_id: u64,
+        transfer: Result<IncomingTransfer>,
+        origin: Option<ItemOrigin>,
+    ) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        if !ctx.trust.is_trusted(&remote.device_id, &remote.public_key) {
+            self.log(
+                Level::Warn,
+                format!(
+                    "LAN dropped transfer from unpaired peer {} ({})",
+                    remote.device_name, remote.device_id
+                ),
+            )
+            .await;
+            return Ok(());
+        }
+        // A new transfer supersedes any unfinished one.
+        self.incoming_transfer = None;
+        match transfer {
+            Ok(t) => self.incoming_transfer = Some(t.with_origin(origin)),
+            Err(e) => self.refuse_transfer(transfer_id, origin, &e).await?,
+        }
+        Ok(())
+    }
+
+    /// Tell the remote we will not take transfer `transfer_id`.
+    async fn refuse_transfer(
+        &mut self,
+        transfer_id: u64,
+        origin: Option<ItemOrigin>,
+        error: &anyhow::Error,
+    ) -> Result<()> {
+        self.log(
+            Level::Warn,
+            format!(
+                "LAN rejected transfer {} from {}: {}",
+                transfer_id, self.remote.device_name, error
+            ),
+        )
+        .await;
+        if let Some(origin) = origin {
+            let receipt = origin.receipt(&self.ctx.device_id, DeliveryStatus::TooLarge);
+            self.send(&PeerMessage::Nack(receipt))
+                .await
+                .map_err(|e| anyhow!("failed to send delivery receipt: {}", e))?;
+        }
+        let abort = PeerMessage::TransferAbort {
+            transfer_id,
+            reason: error.to_string(),
+        };
+        self.send(&abort)
+            .await
+            .map_err(|e| anyhow!("failed to send transfer abort: {}", e))
+    }
+
+    async fn push_chunk(&mut self, transfer_id: u64, index: u32, data: &[u8], binary: bool) {
+        push_chunk(
+            &self.ctx.events,
+            &self.remote.device_name,
+            &mut self.incoming_transfer,
+            transfer_id,
+            index,
+            data,
+            binary,
+        )
+        .await;
+    }
+
+    /// Verify and deliver the incoming transfer `transfer_id`.
+    async fn end_transfer(&mut self, transfer_id: u64) {
+        let Some(transfer) = self.incoming_transfer.take_if(|t| t.id() == transfer_id) else {
+            return;
+        };
+        match transfer.finish(&self.remote.device_id) {
+            Ok(item) => deliver_clipboard(self.ctx, self.remote, item, &self.receipts).await,
+            Err(e) => {
+                self.log(
+                    Level::Warn,
+                    format!(
+                        "LAN transfer {} from {} dropped: {}",
+                        transfer_id, self.remote.device_name, e
+                    ),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Drop the transfer `transfer_id`, in either direction, that the remote
+    /// abandoned or refused.
+    async fn abort_transfer(&mut self, transfer_id: u64, reason: &str) {
+        let remote_name = self.remote.device_name.as_str();
+        if self
+            .incoming_transfer
+            .take_if(|t| t.id() == transfer_id)
+            .is_some()
+        {
+            self.log(
+                Level::Debug,
+                format!(
+                    "LAN transfer {} from {} aborted: {}",
+                    transfer_id, remote_name, reason
+                ),
+            )
+            .await;
+        } else if self
+            .outgoing_transfer
+            .take_if(|t| t.id() == transfer_id)
+            .is_some()
+        {
+            self.log(
+                Level::Warn,
+                format!(
+                    "LAN {} refused transfer {}: {}",
+                    remote_name, transfer_id, reason
+                ),
+            )
+            .await;
+        }
+    }
+
+    /// Send the remote the newest clipboard content right away rather than
+    /// on the next copy: when the session starts and once the remote becomes
+    /// trusted in it. The remote keeps whichever side's item is newer.
+    async fn catch_up(&mut self) -> Result<()> {
+        match self.ctx.clipboard.latest() {
+            Some(latest) => self.send_clipboard(&latest).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Whether a transfer is in flight or waiting to start.
+    fn is_sending(&self) -> bool {
+        self.outgoing_transfer.is_some() || !self.queued_transfers.is_empty()
+    }
+
+    /// Send the next chunk of the outgoing transfer, or start the next
+    /// queued one once it is done.
+    async fn send_next_chunk(&mut self) -> Result<()> {
+        let Some(transfer) = self.outgoing_transfer.as_mut() else {
+            if let Some(next) = self.queued_transfers.pop_front() {
+                return self.send_clipboard(&next).await;
+            }
+            return Ok(());
+        };
+        let msg = transfer.next_message();
+        send_message(&mut self.writer, &msg)
+            .await
+            .map_err(|e| anyhow!("failed to send transfer chunk: {}", e))?;
+        if transfer.is_finished() {
+            let message = format!(
+                "LAN sent clipboard ({}) {} bytes in transfer {}",
+                transfer.content_type(),
+                transfer.total_len(),
+                transfer.id()
+            );
+            self.outgoing_transfer = None;
+            self.log(Level::Debug, message).await;
+        }
+        Ok(())
+    }
+
+    /// Hold a large item until the transfer in flight is done. It replaces
+    /// the older items copied here for the same target that are waiting,
+    /// and the oldest item when too many are; both count as superseded.
+    async fn queue_transfer(&mut self, update: &ClipboardItem) {
+        let ctx = self.ctx;
+        let remote_id = self.remote.device_id.as_str();
+        let (superseded, waiting) = std::mem::take(&mut self.queued_transfers)
+            .into_iter()
+            .partition(|queued| supersedes(ctx, update, &ItemOrigin::of(queued), queued.clock));
+        self.queued_transfers = waiting;
+        for queued in superseded {
+            report_unsent(ctx, remote_id, &queued, DeliveryStatus::Superseded).await;
+        }
+        if self.queued_transfers.len() >= MAX_QUEUED_TRANSFERS {
+            if let Some(oldest) = self.queued_transfers.pop_front() {
+                self.log(
+                    Level::Warn,
+                    format!(
+                        "LAN dropped clipboard ({}) waiting for a transfer to {}",
+                        oldest.content_type, self.remote.device_name
+                    ),
+                )
+                .await;
+                report_unsent(ctx, remote_id, &oldest, DeliveryStatus::Superseded).await;
+            }
+        }
+        self.queued_transfers.push_back(update.clone());
+    }
+
+    /// Send a clipboard item to `remote`: as one frame, or by starting a
+    /// chunked transfer when it is too large. A newer item copied here
+    /// supersedes the transfer in flight for the same target; other large
+    /// items wait for it to finish.
+    ///
+    /// Items the remote may not receive, per trust or sync policy, or cannot
+    /// handle are skipped; rich text goes out as plain text to peers that
+    /// cannot apply HTML.
+    async fn send_clipboard(&mut self, update: &ClipboardItem) -> Result<()> {
+        let ctx = self.ctx;
+        let remote = self.remote;
+        let events = &ctx.events;
+        let remote_id = remote.device_id.as_str();
+        let remote_name = remote.device_name.as_str();
+        if !ctx.trust.is_trusted(remote_id, &remote.public_key) {
+            return Ok(());
+        }
+        // Never echo an item back to where it came from, nor pass on one
+        // addressed to another device.
+        if update.sender_device_id == remote_id
+            || update.relayed_from.as_deref() == Some(remote_id)
+            || update
+                .target
+                .as_deref()
+                .is_some_and(|target| target != remote_id)
+            || !ctx.clipboard.policy.sends_to(remote_id)
+        {
+            return Ok(());
+        }
+        // A peer that cannot apply HTML still gets the plain text of it.
+        let fallback;
+        let update = if remote
+            .capabilities
+            .supports_content_type(&update.content_type)
+        {
+            update
+        } else if let Some(plain) = update.plain_text().filter(|plain| {
+            remote
+                .capabilities
+                .supports_content_type(&plain.content_type)
+        }) {
+            emit_log(
+                events,
+                Level::Debug,
+                format!(
+                    "LAN {} does not support {}, sending plain text",
+                    remote_name, update.content_type
+                ),
+            )
+            .await;
+            fallback = plain;
+            &fallback
+        } else {
+            emit_log(
+                events,
+                Level::Debug,
+                format!(
+                    "LAN {} does not support {}, not sending",
+                    remote_name, update.content_type
+                ),
+            )
+            .await;
+            report_unsent(ctx, remote_id, update, DeliveryStatus::Rejected).await;
+            return Ok(());
+        };
+        // The latest content copied here wins over an older one still in
+        // flight to the same target.
+        if let Some(old) = self
+            .outgoing_transfer
+            .take_if(|old| supersedes(ctx, update, old.origin(), old.clock()))
+        {
+            if let Err(e) = self.send(&old.abort_message("superseded")).await {
+                return Err(anyhow!("failed to send transfer abort: {}", e));
+            }
+            emit_log(
+                events,
+                Level::Debug,
+                format!("LAN transfer {} to {} superseded", old.id(), remote_name),
+            )
+            .await;
+            let receipt = old
+                .origin()
+                .clone()
+                .receipt(remote_id, DeliveryStatus::Superseded);
+            if let Some(report) = ctx.clipboard.deliveries.report(&receipt) {
+                let _ = events.send(RuntimeEvent::Delivery(report)).await;
+            }
+        }
+        let binary = remote.capabilities.binary_frames;
+        let codec = compression::negotiate(&remote.capabilities.compression);
+        let compressed = compression::compress_item(codec, update);
+        let stats = compressed
+            .as_ref()
+            .map(|c| CompressionStats::new("lan", update, c));
+        let payload_len = compressed
+            .as_ref()
+            .map_or(update.data.len(), |c| c.data.len());
+        if remote.capabilities.chunked_transfer && payload_len > CHUNK_SIZE {
+            if self.outgoing_transfer.is_some() {
+                self.queue_transfer(update).await;
+                return Ok(());
+            }
+            let chunk_size = CHUNK_SIZE.min(remote.capabilities.max_frame_size as usize / 2);
+            let transfer = OutgoingTransfer::new(
+                self.next_transfer_id,
+                update,
+                compressed,
+                binary,
+                chunk_size,
+            );
+            self.next_transfer_id += 1;
+            if let Err(e) = self.send(&transfer.begin_message()).await {
+                return Err(anyhow!("failed to send transfer begin: {}", e));
+            }
+            emit_log(
+                events,
+                Level::Debug,
+                format!(
+                    "LAN transfer {} to {} started ({}) {} bytes",
+                    transfer.id(),
+                    remote_name,
+                    transfer.content_type(),
+                    transfer.total_len()
+                ),
+            )
+            .await;
+            if let Some(stats) = stats {
+                let _ = events.send(RuntimeEvent::Compression(stats)).await;
+            }
+            if ctx.clipboard.is_own(update) {
+                ctx.clipboard.deliveries.sent(update, Some(remote_id));
+            }
+            ctx.registry.record_item_sent(remote_id);
+            self.outgoing_transfer = Some(transfer);
+            return Ok(());
+        }
+        let compression = compressed.as_ref().map(|c| c.codec.to_string());
+        let msg = if binary {
+            PeerMessage::ClipboardBinary {
+                content_type: update.content_type.clone(),
+                timestamp: update.clock.wall_ms,
+                logical: update.clock.logical,
+                compression,
+                origin: Some(ItemOrigin::of(update)),
+                data: compressed.map_or_else(|| update.data.to_vec(), |c| c.data),
+            }
+        } else {
+            PeerMessage::Clipboard {
+                content_type: update.content_type.clone(),
+                data: compressed.map_or_else(|| update.wire_data(), |c| c.to_base64()),
+                timestamp: update.clock.wall_ms,
+                logical: update.clock.logical,
+                compression,
+                origin: Some(ItemOrigin::of(update)),
+            }
+        };
+        let frame = encode_peer_message(&msg);
+        if frame.len() - 4 > remote.capabilities.max_frame_size as usize {
+            emit_log(
+                events,
+                Level::Warn,
+                format!(
+                    "LAN clipboard ({}) is {} bytes, over {}'s {} byte frame limit — not sent",
+                    update.content_type,
+                    frame.len() - 4,
+                    remote_name,
+                    remote.capabilities.max_frame_size
+                ),
+            )
+            .await;
+            report_unsent(ctx, remote_id, update, DeliveryStatus::TooLarge).await;
+            return Ok(());
+        }
+        if let Err(e) = self.writer.write_frame(&frame).await {
+            return Err(anyhow!("failed to send clipboard: {}", e));
+        }
+        emit_log(
+            events,
+            Level::Debug,
+            format!(
+                "LAN sent clipboard ({}) {} bytes",
+                update.content_type,
+                frame.len() - 4
+            ),
+        )
+        .await;
+        if let Some(stats) = stats {
+            let _ = events.send(RuntimeEvent::Compression(stats)).await;
+        }
+        if ctx.clipboard.is_own(update) {
+            ctx.clipboard.deliveries.sent(update, Some(remote_id));
+        }
+        ctx.registry.record_item_sent(remote_id);
+        Ok(())
+    }
+}
+
+/// Append a chunk to the incoming transfer it belongs to, dropping the
+/// transfer if the chunk does not fit. Chunks of other transfers are
+/// ignored.
+async fn push_chunk(
+    events: &mpsc::Sender<RuntimeEvent>,
+    remote_name: &str,
+    incoming: &mut Option<IncomingTransfer>,
+    transfer_id: u64,
+    index: u32,
+    data: &[u8],
+    binary: bool,
+) {
+    let Some(transfer) = incoming.as_mut().filter(|t| t.id() == transfer_id) else {
+        return;
+    };
+    match transfer.push(index, data, binary) {
+        Ok(Some(percent)) => {
+            emit_log(
+                events,
+                Level::Debug,
+                format!(
+                    "LAN transfer {} from {}: {}%",
+                    transfer_id, remote_name, percent
+                ),
+            )
+            .await;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            emit_log(
+                events,
+                Level::Warn,
+                format!(
+                    "LAN transfer {} from {} dropped: {}",
+                    transfer_id, remote_name, e
+                ),
+            )
+            .await;
+            *incoming = None;
+        }
+    }
+}
+
+/// [`deliver_clipboard`] for a payload that may have failed to decode,
+/// restoring the identity `origin` it was sent with. An item from a peer
+/// that sends none keeps the remote as its origin and a fresh id.
+async fn deliver_decoded(
+    ctx: &PeerContext,
+    remote: &RemotePeer,
+    item: Result<ClipboardItem>,
+    origin: Option<ItemOrigin>,
+    receipts: &ReceiptSender,
+) {
+    match item {
+        Ok(mut item) => {
+            if let Some(origin) = origin {
+                origin.apply(&mut item);
+            }
+            deliver_clipboard(ctx, remote, item, receipts).await
+        }
+        Err(e) => {
+            emit_log(
+                &ctx.events,
+                Level::Warn,
+                format!(
+                    "LAN dropped undecodable clipboard from {}: {:#}",
+                    remote.device_name, e
+                ),
+            )
+            .await;
+        }
+    }
+}
+
+/// Hand a clipboard item received from `remote` to the local clipboard
+/// setter, unless the remote is not trusted, the item was seen before or
+/// the sync policy refuses it, and relay it to the other sessions when
+/// enabled. The setter's receipt goes back to `remote` through `receipts`.
+async fn deliver_clipboard(
+    ctx: &PeerContext,
+    remote: &RemotePeer,
+    mut item: ClipboardItem,
+    receipts: &ReceiptSender,
+) {
+    if !ctx.trust.is_trusted(&remote.device_id, &remote.public_key) {
+        let _ = receipts.try_send(DeliveryReceipt::new(
+            &item,
+            &ctx.device_id,
+            DeliveryStatus::Rejected,
+        ));
+        emit_log(
+            &ctx.events,
+            Level::Warn,
+            format!(
+                "LAN dropped clipboard from unpaired peer {} ({})",
+                remote.device_name, remote.device_id
+            ),
+        )
+        .await;
+        return;
+    }
+    ctx.registry.record_item_received(&remote.device_id);
+
+    // Our own item came back around the mesh, or another path was faster.
+    if item.sender_device_id == ctx.device_id
+        || !ctx
+            .seen
+            .lock()
+            .insert(&item.sender_device_id, item.item_id, &remote.device_id)
+    {
+        return;
+    }
+
+    // Neither the peer nor the device the item was copied on may push
+    // content onto ours against the sync policy.
+    let policy = &ctx.clipboard.policy;
+    if !policy.receives_from(&remote.device_id) || !policy.receives_item(&item) {
+        let _ = receipts.try_send(DeliveryReceipt::new(
+            &item,
+            &ctx.device_id,
+            DeliveryStatus::Rejected,
+        ));
+        emit_log(
+            &ctx.events,
+            Level::Debug,
+            format!(
+                "LAN ignored clipboard from {} via {}: sync direction",
+                item.sender_device_id, remote.device_name
+            ),
+        )
+        .await;
+        return;
+    }
+
+    let content_type = item.content_type.clone();
+    if ctx.relay && relay::is_relayable(&item) {
+        item.hops += 1;
+        item.relayed_from = Some(remote.device_id.clone());
+        // Nobody else subscribed means there is no other session to reach.
+        if ctx.tx_out.send(item.clone()).is_ok() {
+            emit_log(
+                &ctx.events,
+                Level::Debug,
+                format!(
+                    "LAN relaying clipboard from {} via {} (hop {})",
+                    item.sender_device_id, remote.device_name, item.hops
+                ),
+            )
+            .await;
+        }
+    }
+    item.receipts = Some(receipts.clone());
+    let _ = ctx.tx_in.send(item).await;
+
+    let ct_label = match content_type.as_str() {
+        CONTENT_TYPE_TEXT => "text",
+        CONTENT_TYPE_HTML => "rich text",
+        CONTENT_TYPE_IMAGE_PNG => "image",
+        other => other,
+    };
+    emit_log(
+        &ctx.events,
+        Level::Info,
+        format!("LAN received clipboard ({})", ct_label),
+    )
+    .await;
+
+    let _ = ctx
+        .events
+        .send(RuntimeEvent::ClipboardReceived { content_type })
+        .await;
+}
+
+/// Handle a delivery receipt from `remote`: tally it when the item was
+/// copied here, otherwise pass it on to the peer we received the item from.
+///
+/// Receipts are only taken from trusted peers, and only in the remote's own
+/// name unless the remote relayed the item (see [`relayed_receipt`]).
+async fn route_receipt(ctx: &PeerContext, remote: &RemotePeer, receipt: DeliveryReceipt) {
+    if !ctx.trust.is_trusted(&remote.device_id, &remote.public_key) {
+        emit_log(
+            &ctx.events,
+            Level::Warn,
+            format!(
+                "LAN dropped a delivery receipt from unpaired peer {} ({})",
+                remote.device_name, remote.device_id
+            ),
+        )
+        .await;
+        return;
+    }
+    if receipt.device_id != remote.device_id && !relayed_receipt(ctx, &receipt) {
+        emit_log(
+            &ctx.events,
+            Level::Warn,
+            format!(
+                "LAN dropped a delivery receipt in the name of {} from {}",
+                receipt.device_id, remote.device_name
+            ),
+        )
+        .await;
+        return;
+    }
+    if receipt.origin == ctx.device_id {
+        if let Some(report) = ctx.clipboard.deliveries.report(&receipt) {
+            let _ = ctx.events.send(RuntimeEvent::Delivery(report)).await;
+        }
+        return;
+    }
+    let Some(from) = ctx
+        .seen
+        .lock()
+        .received_from(&receipt.origin, receipt.item_id)
+        .filter(|from| *from != remote.device_id)
+        .map(str::to_string)
+    else {
+        return;
+    };
+    let forwarded = ctx.sessions.lock().get(&from).is_some_and(|handle| {
+        handle
+            .commands
+            .try_send(SessionCommand::Send(PeerMessage::receipt(receipt)))
+            .is_ok()
+    });
+    if !forwarded {
+        emit_log(
+            &ctx.events,
+            Level::Debug,
+            format!("LAN could not pass a delivery receipt on to {}", from),
+        )
+        .await;
+    }
+}
+
+/// Whether a receipt in another device's name can have come back through
+/// the remote: the item went to the remote to be relayed, which items sent
+/// to one peer on demand never are, and items copied elsewhere only are when
+/// this node relays too. Nobody else reports for this device.
+fn relayed_receipt(ctx: &PeerContext, receipt: &DeliveryReceipt) -> bool {
+    if receipt.device_id == ctx.device_id {
+        return false;
+    }
+    if receipt.origin == ctx.device_id {
+        ctx.clipboard.deliveries.relayable(receipt.item_id)
+    } else {
+        ctx.relay
+    }
+}
+
+/// Whether `update` replaces an item not yet sent in full, identified by
+/// `origin` and `clock`: both were copied here for the same target and
+/// `update` is newer.
+fn supersedes(ctx: &PeerContext, update: &ClipboardItem, origin: &ItemOrigin, clock: Hlc) -> bool {
+    ctx.clipboard.is_own(update)
+        && origin.device_id == ctx.device_id
+        && origin.target == update.target
+        && update.clock > clock
+}
+
+/// Record a delivery outcome decided on this side (the item never reached
+/// `remote`) for an item copied here.
+async fn report_unsent(
+    ctx: &PeerContext,
+    remote_id: &str,
+    update: &ClipboardItem,
+    status: DeliveryStatus,
+) {
+    if !ctx.clipboard.is_own(update) {
+        return;
+    }
+    ctx.clipboard.deliveries.sent(update, Some(remote_id));
+    let receipt = DeliveryReceipt::new(update, remote_id, status);
+    if let Some(report) = ctx.clipboard.deliveries.report(&receipt) {
+        let _ = ctx.events.send(RuntimeEvent::Delivery(report)).await;
+    }
+}
+
+/// Trust the remote (after PIN pairing or manual approval, named by `how`),
+/// persist it and let the UI know.
+///
+/// A persistence failure is logged and returned, and the remote stays
+/// untrusted.
+async fn record_trust(ctx: &PeerContext, remote: &RemotePeer, how: &str) -> Result<()> {
+    let remote_id = remote.device_id.as_str();
+    let remote_name = remote.device_name.as_str();
+    let result = ctx.trust.trust(remote_id, remote_name, &remote.public_key);
+    match &result {
+        Ok(()) => {
+            emit_log(
+                &ctx.events,
+                Level::Info,
+                format!("LAN trusted {} ({}) via {}", remote_name, remote_id, how),
+            )
+            .await;
+        }
+        Err(e) => {
+            emit_log(
+                &ctx.events,
+                Level::Error,
+                format!("LAN failed to persist trust for {}: {:#}", remote_id, e),
+            )
+            .await;
+        }
+    }
+    emit_access_changed(&ctx.trust, &ctx.registry, &ctx.events).await;
+    result
+}
+
+/// Tell the UI the access lists changed: the lists themselves and the peer
+/// list whose entries carry each peer's status.
+pub async fn emit_access_changed(
+    trust: &TrustStore,
+    registry: &PeerRegistry,
+    events: &mpsc::Sender<RuntimeEvent>,
+) {
+    let _ = events
+        .send(RuntimeEvent::LanTrustChanged(trust.to_json()))
+        .await;
+    emit_peer_list(registry, trust, events).await;
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Frame I/O helpers
+// ────────────────────────────────────────────────────────────────────────────
+
+/// Encode `msg` and write it through the encrypted session writer.
+async fn send_message(writer: &mut SecureWriter, msg: &PeerMessage) -> Result<()> {
+    writer.write_frame(&encode_peer_message(msg)).await
+}
+
+/// Write a single `PeerMessage` frame (handshake phase).
+async fn write_peer_message(writer: &mut SecureWriter, msg: &PeerMessage) -> Result<()> {
+    send_message(writer, msg)
+        .await
+        .context("write_peer_message")
+}
+
+/// Read and decode a single `PeerMessage` frame.
+async fn read_peer_message(reader: &mut SecureReader) -> Result<PeerMessage> {
+    let payload = reader.read_frame().await?;
+    decode_peer_message(payload)
+}
+
+/// Forward decoded frames to the session loop until the stream fails or the
+/// session goes away. The first error is forwarded and ends the task.
+async fn read_loop(mut reader: SecureReader, tx: mpsc::Sender<Result<PeerMessage>>) {
+    loop {
+        let result = read_peer_message(&mut reader).await;
+        let failed = result.is_err();
+        if tx.send(result).await.is_err() || failed {
+            break;
+        }
+    }
+}
+
+/// Aborts the wrapped task when dropped so a session's reader never
+/// outlives the session.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Tiny helpers
+// ────────────────────────────────────────────────────────────────────────────
+
+/// `true` when a session ended because the peer's protocol version is
+/// incompatible with ours.
+fn is_incompatible(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<IncompatibleProtocol>().is_some()
+}
+
+/// `true` when a session was dropped in favour of another one with the same
+/// peer.
+fn is_duplicate(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<DuplicateSession>().is_some()
+}
+
+/// Surface a protocol mismatch to the user rather than only the log.
+async fn report_incompatible(events: &mpsc::Sender<RuntimeEvent>, err: &anyhow::Error) {
+    emit_log(events, Level::Error, err.to_string()).await;
+    let _ = events.send(RuntimeEvent::Error(err.to_string())).await;
+}
 
 /// Emit a log event to the runtime channel (convenience wrapper).
 async fn emit_log(events: &mpsc::Sender<RuntimeEvent>, level: Level, message: impl Into<String>) {
@@ -628,10 +2311,24 @@ fn now_millis() -> u64 {
 /// Human-readable label for a [`PeerMessage`] variant (for log messages).
 fn msg_type_name(msg: &PeerMessage) -> &'static str {
     match msg {
-        PeerMessage::Hello { .. } => "Hello",
-        PeerMessage::Welcome { .. } => "Welcome",
+        PeerMessage::Hello(_) => "Hello",
+        PeerMessage::Welcome(_) => "Welcome",
         PeerMessage::Ping { .. } => "Ping",
         PeerMessage::Pong { .. } => "Pong",
+        PeerMessage::PairStart { .. } => "PairStart",
+        PeerMessage::PairShare { .. } => "PairShare",
+        PeerMessage::PairConfirm { .. } => "PairConfirm",
+        PeerMessage::PairAccept { .. } => "PairAccept",
+        PeerMessage::PairReject { .. } => "PairReject",
         PeerMessage::Clipboard { .. } => "Clipboard",
+        PeerMessage::ClipboardBinary { .. } => "ClipboardBinary",
+        PeerMessage::TransferBegin { .. } => "TransferBegin",
+        PeerMessage::TransferChunk { .. } => "TransferChunk",
+        PeerMessage::TransferChunkBinary { .. } => "TransferChunkBinary",
+        PeerMessage::TransferEnd { .. } => "TransferEnd",
+        PeerMessage::TransferAbort { .. } => "TransferAbort",
+        PeerMessage::Ack(_) => "Ack",
+        PeerMessage::Nack(_) => "Nack",
+        PeerMessage::Unknown => "Unknown",
     }
 }
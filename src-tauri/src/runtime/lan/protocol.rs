@@ -3,8 +3,19 @@
 //! All messages exchanged over UDP (discovery) and TCP (peer data) are defined
 //! here so that every sub-module speaks the same language.
 
+use std::{
+    fmt,
+    net::{Ipv6Addr, SocketAddr},
+};
+
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 
+use super::group::{SyncGroup, BEACON_TAG_LEN};
+use crate::runtime::compression;
+use crate::runtime::delivery::{DeliveryReceipt, DeliveryStatus};
+use crate::runtime::messages::{self, ClipboardItem};
+
 // ────────────────────────────────────────────────────────────────────────────
 // Constants
 // ────────────────────────────────────────────────────────────────────────────
@@ -16,9 +27,16 @@ pub const DISCOVERY_MAGIC: &[u8; 8] = b"RSCV_LAN";
 /// Default UDP port used for broadcast discovery.
 pub const DEFAULT_DISCOVERY_PORT: u16 = 52741;
 
+/// Link-local multicast group IPv6 beacons are sent to, on the discovery
+/// port (IPv6 has no broadcast). The last two groups spell `RSCV`.
+pub const DISCOVERY_MULTICAST_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0x5253, 0x4356);
+
 /// Default TCP port the "host" peer listens on for data connections.
 pub const DEFAULT_TCP_PORT: u16 = 52742;
 
+/// DNS-SD service type advertised and browsed by mDNS discovery.
+pub const MDNS_SERVICE_TYPE: &str = "_rscv._tcp.local.";
+
 /// How often a discovery beacon is broadcast (seconds).
 pub const DISCOVERY_INTERVAL_SECS: u64 = 3;
 
@@ -39,6 +57,19 @@ pub const INITIAL_RECONNECT_DELAY_SECS: u64 = 1;
 /// Hello/Welcome handshake before giving up.
 pub const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
 
+/// Version of the peer protocol spoken by this build, announced in
+/// `Hello`/`Welcome`. Bump it for changes that capability negotiation
+/// cannot absorb.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest peer protocol version this build can still talk to.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// How long a client waits before retrying a peer whose protocol version is
+/// incompatible (seconds) — long enough not to spam, short enough to pick
+/// up an upgrade.
+pub const INCOMPATIBLE_RETRY_SECS: u64 = 300;
+
 // ────────────────────────────────────────────────────────────────────────────
 // UDP Discovery
 // ────────────────────────────────────────────────────────────────────────────
@@ -50,7 +81,8 @@ pub const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
 /// label shown in the UI.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiscoveryBeacon {
-    /// Unique identifier for this peer (UUID v4, generated once per session).
+    /// Stable identifier for this peer, derived from its device key (see
+    /// [`crate::runtime::identity`]).
     pub device_id: String,
     /// Human-friendly device name (e.g. hostname).
     pub device_name: String,
@@ -76,15 +108,11 @@ pub struct DiscoveryBeacon {
 pub enum PeerMessage {
     // ── Handshake ────────────────────────────────────────────────────────
     /// Sent by the *connecting* side immediately after TCP connect.
-    Hello {
-        device_id: String,
-        device_name: String,
-    },
-    /// Reply from the *host* side acknowledging the handshake.
-    Welcome {
-        device_id: String,
-        device_name: String,
-    },
+    Hello(HandshakeInfo),
+    /// Reply from the *host* side acknowledging the handshake. Always sent,
+    /// even when the versions are incompatible, so the client can tell why
+    /// the session is closing.
+    Welcome(HandshakeInfo),
 
     // ── Heartbeat ────────────────────────────────────────────────────────
     Ping {
@@ -94,14 +122,327 @@ pub enum PeerMessage {
         ts: u64,
     },
 
+    // ── Pairing ──────────────────────────────────────────────────────────
+    /// Sent by the side where the user typed the PIN shown on the remote:
+    /// its share of the PIN exchange (see [`super::pairing`]). Neither the
+    /// PIN nor anything to test guesses of it against is transmitted.
+    PairStart {
+        share: String,
+    },
+    /// The displaying side's share in reply to `PairStart`.
+    PairShare {
+        share: String,
+    },
+    /// The requester's key confirmation, checked by the displaying side
+    /// against its pending PIN.
+    PairConfirm {
+        proof: String,
+    },
+    /// The confirmation matched; `proof` is the displaying side's own, which
+    /// the requester checks before trusting it in return.
+    PairAccept {
+        proof: String,
+    },
+    /// The PIN did not match, expired, or no PIN is being displayed.
+    PairReject {
+        reason: String,
+    },
+
     // ── Clipboard data ───────────────────────────────────────────────────
-    /// Clipboard payload — reuses the same shape as the existing
-    /// `ClipboardBroadcastPayload` so the setter logic doesn't change.
+    /// Clipboard payload in JSON form: text as-is, anything else base64
+    /// (see [`ClipboardItem::wire_data`](crate::runtime::messages::ClipboardItem::wire_data)).
+    /// Used with peers that lack binary frame support. Only exchanged
+    /// between paired peers. With `compression` set, `data` is the base64
+    /// of the payload compressed with that codec. `timestamp` and
+    /// `logical` are the sender's clock stamp (see [`crate::runtime::clock`]).
+    /// `origin` identifies the item across the mesh; older peers send none.
     Clipboard {
         content_type: String,
         data: String,
         timestamp: u64,
+        #[serde(default)]
+        logical: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compression: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        origin: Option<ItemOrigin>,
     },
+    /// Clipboard payload as a binary frame: this header followed by the raw
+    /// (or `compression`-compressed) bytes (see [`encode_peer_message`]).
+    /// Only sent when both sides announced [`Capabilities::binary_frames`].
+    ClipboardBinary {
+        content_type: String,
+        timestamp: u64,
+        #[serde(default)]
+        logical: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compression: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        origin: Option<ItemOrigin>,
+        #[serde(skip)]
+        data: Vec<u8>,
+    },
+
+    // ── Chunked transfer ─────────────────────────────────────────────────
+    /// Announces a clipboard payload too large for one frame (see
+    /// [`super::transfer`]). `total_len` is the byte length of the data
+    /// being streamed and `digest` its base64 SHA-256; with `binary` the
+    /// data is the raw payload sent as `TransferChunkBinary`, otherwise its
+    /// JSON wire form sent as `TransferChunk`. With `compression` set the
+    /// payload was compressed with that codec before being streamed (and
+    /// its JSON wire form is always base64).
+    TransferBegin {
+        transfer_id: u64,
+        content_type: String,
+        timestamp: u64,
+        #[serde(default)]
+        logical: u32,
+        total_len: u64,
+        digest: String,
+        #[serde(default)]
+        binary: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        compression: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        origin: Option<ItemOrigin>,
+    },
+    /// The `index`-th slice of the payload data, in order from 0.
+    TransferChunk {
+        transfer_id: u64,
+        index: u32,
+        data: String,
+    },
+    /// Binary-frame counterpart of `TransferChunk`.
+    TransferChunkBinary {
+        transfer_id: u64,
+        index: u32,
+        #[serde(skip)]
+        data: Vec<u8>,
+    },
+    /// All chunks were sent; the receiver verifies and applies the payload.
+    TransferEnd {
+        transfer_id: u64,
+    },
+    /// The transfer was abandoned (e.g. superseded by a newer clipboard
+    /// update); the receiver discards what it has.
+    TransferAbort {
+        transfer_id: u64,
+        reason: String,
+    },
+
+    // ── Delivery receipts ────────────────────────────────────────────────
+    /// The reporting device applied the item (see
+    /// [`crate::runtime::delivery`]). Sent back to the peer the item came
+    /// from, which passes it on towards the origin if it only relayed it.
+    Ack(DeliveryReceipt),
+    /// The reporting device did not apply the item; `status` says why.
+    Nack(DeliveryReceipt),
+
+    /// Any message type this build does not know, sent by a newer peer.
+    /// Ignored instead of failing the session.
+    #[serde(other)]
+    Unknown,
+}
+
+impl PeerMessage {
+    /// The `Ack` or `Nack` carrying `receipt`, depending on its status.
+    pub fn receipt(receipt: DeliveryReceipt) -> Self {
+        if receipt.status.is_ack() {
+            PeerMessage::Ack(receipt)
+        } else {
+            PeerMessage::Nack(receipt)
+        }
+    }
+}
+
+/// Identity of a clipboard item as it floods the LAN mesh (see
+/// [`super::relay`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemOrigin {
+    /// Device the content was copied on.
+    pub device_id: String,
+    /// [`ClipboardItem::item_id`], unique per origin device.
+    pub item_id: u64,
+    /// Peers that relayed the item before this hop.
+    pub hops: u8,
+    /// The receiving peer, for an item sent to it alone; such items are
+    /// not relayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl ItemOrigin {
+    pub fn of(item: &ClipboardItem) -> Self {
+        Self {
+            device_id: item.sender_device_id.clone(),
+            item_id: item.item_id,
+            hops: item.hops,
+            target: item.target.clone(),
+        }
+    }
+
+    /// `device_id`'s receipt for this item, for when the item itself never
+    /// arrived in full.
+    pub fn receipt(self, device_id: &str, status: DeliveryStatus) -> DeliveryReceipt {
+        DeliveryReceipt {
+            origin: self.device_id,
+            item_id: self.item_id,
+            device_id: device_id.to_string(),
+            status,
+        }
+    }
+
+    /// Restore the identity of an item decoded from the wire.
+    pub fn apply(self, item: &mut ClipboardItem) {
+        item.sender_device_id = self.device_id;
+        item.item_id = self.item_id;
+        item.hops = self.hops;
+        item.target = self.target;
+    }
+}
+
+/// What each side announces about itself in `Hello` / `Welcome`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandshakeInfo {
+    pub device_id: String,
+    pub device_name: String,
+    /// Shows the sender knows the sync group secret (see [`super::group`]).
+    pub group_proof: String,
+    /// The sender's [`PROTOCOL_VERSION`]; `0` from builds that predate
+    /// versioning.
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// The sender's [`MIN_PROTOCOL_VERSION`].
+    #[serde(default)]
+    pub min_protocol_version: u32,
+    #[serde(default)]
+    pub capabilities: Capabilities,
+}
+
+/// Optional features a peer supports. A session uses the intersection of
+/// both sides (see [`Capabilities::negotiate`]).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Clipboard content types the peer can apply (MIME strings).
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    /// Compression codecs the peer can decode, by name.
+    #[serde(default)]
+    pub compression: Vec<String>,
+    /// Largest frame payload the peer accepts, in bytes.
+    #[serde(default)]
+    pub max_frame_size: u32,
+    /// The peer understands the `Transfer*` messages.
+    #[serde(default)]
+    pub chunked_transfer: bool,
+    /// The peer accepts binary frames (`ClipboardBinary`,
+    /// `TransferChunkBinary`).
+    #[serde(default)]
+    pub binary_frames: bool,
+    /// The peer pairs with the PIN exchange (`PairStart` and on) rather
+    /// than the older PIN proofs, which this build no longer accepts.
+    #[serde(default)]
+    pub pin_exchange: bool,
+}
+
+impl Capabilities {
+    /// What this build supports.
+    pub fn local() -> Self {
+        Self {
+            content_types: messages::supported_content_types(),
+            compression: compression::supported_codecs(),
+            max_frame_size: MAX_FRAME_SIZE,
+            chunked_transfer: true,
+            binary_frames: true,
+            pin_exchange: true,
+        }
+    }
+
+    /// Capabilities usable on a session between `self` and `remote`.
+    ///
+    /// A peer that announced no `max_frame_size` is assumed to accept our
+    /// own limit.
+    pub fn negotiate(&self, remote: &Capabilities) -> Capabilities {
+        let common = |ours: &[String], theirs: &[String]| {
+            ours.iter()
+                .filter(|c| theirs.contains(c))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let max_frame_size = match remote.max_frame_size {
+            0 => self.max_frame_size,
+            theirs => theirs.min(self.max_frame_size),
+        };
+        Capabilities {
+            content_types: common(&self.content_types, &remote.content_types),
+            compression: common(&self.compression, &remote.compression),
+            max_frame_size,
+            chunked_transfer: self.chunked_transfer && remote.chunked_transfer,
+            binary_frames: self.binary_frames && remote.binary_frames,
+            pin_exchange: self.pin_exchange && remote.pin_exchange,
+        }
+    }
+
+    /// `true` when clipboard content of `content_type` may be sent.
+    pub fn supports_content_type(&self, content_type: &str) -> bool {
+        self.content_types.iter().any(|c| c == content_type)
+    }
+}
+
+/// The remote peer's protocol version range does not overlap ours.
+#[derive(Debug, Clone)]
+pub struct IncompatibleProtocol {
+    pub device_name: String,
+    pub remote_version: u32,
+    pub remote_min_version: u32,
+}
+
+impl fmt::Display for IncompatibleProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.remote_version < MIN_PROTOCOL_VERSION {
+            write!(
+                f,
+                "LAN peer {} uses protocol v{}, older than the v{} this device requires — update it",
+                self.device_name, self.remote_version, MIN_PROTOCOL_VERSION
+            )
+        } else {
+            write!(
+                f,
+                "LAN peer {} requires protocol v{} or newer, this device speaks v{} — update this device",
+                self.device_name, self.remote_min_version, PROTOCOL_VERSION
+            )
+        }
+    }
+}
+
+impl std::error::Error for IncompatibleProtocol {}
+
+impl HandshakeInfo {
+    /// Fill in our own version and capabilities.
+    pub fn local(device_id: String, device_name: String, group_proof: String) -> Self {
+        Self {
+            device_id,
+            device_name,
+            group_proof,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            capabilities: Capabilities::local(),
+        }
+    }
+
+    /// Check that the sender's version range overlaps ours.
+    pub fn check_version(&self) -> Result<(), IncompatibleProtocol> {
+        if self.protocol_version < MIN_PROTOCOL_VERSION
+            || self.min_protocol_version > PROTOCOL_VERSION
+        {
+            return Err(IncompatibleProtocol {
+                device_name: self.device_name.clone(),
+                remote_version: self.protocol_version,
+                remote_min_version: self.min_protocol_version,
+            });
+        }
+        Ok(())
+    }
 }
 
 // ────────────────────────────────────────────────────────────────────────────
@@ -113,50 +454,305 @@ pub enum PeerMessage {
 pub struct DiscoveredPeer {
     pub device_id: String,
     pub device_name: String,
-    pub addr: String, // IP address (no port)
+    pub addr: String, // IP address (no port), IPv6 link-local with `%<scope>`
     pub tcp_port: u16,
+    /// Local interface the peer was found on, when known.
+    #[serde(default)]
+    pub interface: Option<String>,
     pub last_seen: u64, // unix timestamp (secs)
 }
 
+impl DiscoveredPeer {
+    /// `"<addr>:<tcp_port>"` to dial, with IPv6 addresses in brackets.
+    pub fn dial_addr(&self) -> String {
+        host_port(&self.addr, self.tcp_port)
+    }
+}
+
+/// The address a beacon came from, as stored in [`DiscoveredPeer::addr`].
+///
+/// IPv4-mapped addresses are unwrapped, and link-local IPv6 addresses keep
+/// their scope id (`fe80::1%3`), without which they cannot be dialed.
+pub fn peer_ip(src: &SocketAddr) -> String {
+    match src {
+        SocketAddr::V4(v4) => v4.ip().to_string(),
+        SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => v4.to_string(),
+            None if v6.ip().is_unicast_link_local() && v6.scope_id() != 0 => {
+                format!("{}%{}", v6.ip(), v6.scope_id())
+            }
+            None => v6.ip().to_string(),
+        },
+    }
+}
+
+/// Join a host and port, bracketing IPv6 literals (`[fe80::1%3]:52742`).
+pub fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
 // ────────────────────────────────────────────────────────────────────────────
 // Wire helpers
 // ────────────────────────────────────────────────────────────────────────────
 
-/// Encode a [`DiscoveryBeacon`] into a datagram with the magic prefix.
-pub fn encode_beacon(beacon: &DiscoveryBeacon) -> Vec<u8> {
+/// Encode a [`DiscoveryBeacon`] into a datagram:
+///
+/// ```text
+/// [magic][32-byte group tag][JSON payload]
+/// ```
+pub fn encode_beacon(beacon: &DiscoveryBeacon, group: &SyncGroup) -> Vec<u8> {
     let json = serde_json::to_vec(beacon).expect("beacon serialisation is infallible");
-    let mut buf = Vec::with_capacity(DISCOVERY_MAGIC.len() + json.len());
+    let mut buf = Vec::with_capacity(DISCOVERY_MAGIC.len() + BEACON_TAG_LEN + json.len());
     buf.extend_from_slice(DISCOVERY_MAGIC);
+    buf.extend_from_slice(&group.beacon_tag(&json));
     buf.extend_from_slice(&json);
     buf
 }
 
 /// Try to decode a [`DiscoveryBeacon`] from a raw datagram.
-/// Returns `None` when the magic prefix doesn't match or the JSON is invalid.
-pub fn decode_beacon(data: &[u8]) -> Option<DiscoveryBeacon> {
-    if data.len() <= DISCOVERY_MAGIC.len() {
+/// Returns `None` when the magic prefix doesn't match, the tag was not made
+/// with `group`'s key (another sync group), or the JSON is invalid.
+pub fn decode_beacon(data: &[u8], group: &SyncGroup) -> Option<DiscoveryBeacon> {
+    let header = DISCOVERY_MAGIC.len() + BEACON_TAG_LEN;
+    if data.len() <= header {
         return None;
     }
     if &data[..DISCOVERY_MAGIC.len()] != DISCOVERY_MAGIC {
         return None;
     }
-    serde_json::from_slice(&data[DISCOVERY_MAGIC.len()..]).ok()
+    let (tag, json) = data[DISCOVERY_MAGIC.len()..].split_at(BEACON_TAG_LEN);
+    if !group.verify_beacon(json, tag) {
+        return None;
+    }
+    serde_json::from_slice(json).ok()
 }
 
+/// First payload byte of a binary frame. JSON frames always start with `{`.
+pub const BINARY_FRAME_MARKER: u8 = 0x00;
+
 /// Encode a [`PeerMessage`] into a length-prefixed frame:
 ///
 /// ```text
 /// [4 bytes big-endian length][JSON payload]
 /// ```
+///
+/// Messages with a raw body (`ClipboardBinary`, `TransferChunkBinary`)
+/// become binary frames instead, whose payload is
+///
+/// ```text
+/// [BINARY_FRAME_MARKER][4 bytes big-endian header length][JSON header][raw body]
+/// ```
 pub fn encode_peer_message(msg: &PeerMessage) -> Vec<u8> {
     let json = serde_json::to_vec(msg).expect("peer message serialisation is infallible");
-    let len = json.len() as u32;
-    let mut buf = Vec::with_capacity(4 + json.len());
+    let body: &[u8] = match msg {
+        PeerMessage::ClipboardBinary { data, .. }
+        | PeerMessage::TransferChunkBinary { data, .. } => data,
+        _ => {
+            let mut buf = Vec::with_capacity(4 + json.len());
+            buf.extend_from_slice(&(json.len() as u32).to_be_bytes());
+            buf.extend_from_slice(&json);
+            return buf;
+        }
+    };
+
+    let len = (1 + 4 + json.len() + body.len()) as u32;
+    let mut buf = Vec::with_capacity(4 + len as usize);
     buf.extend_from_slice(&len.to_be_bytes());
+    buf.push(BINARY_FRAME_MARKER);
+    buf.extend_from_slice(&(json.len() as u32).to_be_bytes());
     buf.extend_from_slice(&json);
+    buf.extend_from_slice(body);
     buf
 }
 
+/// Decode a frame payload (without its length prefix) produced by
+/// [`encode_peer_message`].
+pub fn decode_peer_message(mut payload: Vec<u8>) -> Result<PeerMessage> {
+    if payload.first() != Some(&BINARY_FRAME_MARKER) {
+        return serde_json::from_slice(&payload).context("deserialising PeerMessage");
+    }
+
+    if payload.len() < 5 {
+        return Err(anyhow!("truncated binary frame"));
+    }
+    let header_len = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]) as usize;
+    let body_start = 5usize
+        .checked_add(header_len)
+        .filter(|&end| end <= payload.len())
+        .ok_or_else(|| anyhow!("binary frame header overruns the frame"))?;
+    let body = payload.split_off(body_start);
+    let mut msg: PeerMessage =
+        serde_json::from_slice(&payload[5..]).context("deserialising binary frame header")?;
+    match &mut msg {
+        PeerMessage::ClipboardBinary { data, .. }
+        | PeerMessage::TransferChunkBinary { data, .. } => {
+            *data = body;
+        }
+        // A binary message type from a newer peer; ignored like any other.
+        PeerMessage::Unknown => {}
+        _ => return Err(anyhow!("binary frame with a non-binary message header")),
+    }
+    Ok(msg)
+}
+
 /// Maximum allowed frame size (16 MiB) to avoid unbounded allocations from
-/// a misbehaving peer.
+/// a misbehaving peer. Larger clipboard payloads go through the chunked
+/// transfer in [`super::transfer`].
 pub const MAX_FRAME_SIZE: u32 = 16 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddrV6;
+
+    use super::*;
+
+    /// Encode `msg`, check the length prefix, and decode the payload.
+    fn round_trip(msg: &PeerMessage) -> (Vec<u8>, PeerMessage) {
+        let frame = encode_peer_message(msg);
+        let (len, payload) = frame.split_at(4);
+        assert_eq!(
+            u32::from_be_bytes(len.try_into().unwrap()) as usize,
+            payload.len()
+        );
+        let decoded = decode_peer_message(payload.to_vec()).unwrap();
+        (payload.to_vec(), decoded)
+    }
+
+    #[test]
+    fn peer_addresses_are_dialable() {
+        let v4: SocketAddr = "192.168.1.5:40000".parse().unwrap();
+        assert_eq!(peer_ip(&v4), "192.168.1.5");
+        assert_eq!(host_port(&peer_ip(&v4), 52742), "192.168.1.5:52742");
+
+        let mapped: SocketAddr = "[::ffff:10.0.0.1]:40000".parse().unwrap();
+        assert_eq!(peer_ip(&mapped), "10.0.0.1");
+
+        let v6: SocketAddr = "[2001:db8::1]:40000".parse().unwrap();
+        assert_eq!(peer_ip(&v6), "2001:db8::1");
+        assert_eq!(host_port(&peer_ip(&v6), 52742), "[2001:db8::1]:52742");
+
+        let link_local = SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 40000, 0, 3));
+        assert_eq!(peer_ip(&link_local), "fe80::1%3");
+        let dial = host_port(&peer_ip(&link_local), 52742);
+        assert_eq!(dial, "[fe80::1%3]:52742");
+        assert_eq!(
+            dial.parse::<SocketAddr>().unwrap(),
+            SocketAddr::V6(SocketAddrV6::new("fe80::1".parse().unwrap(), 52742, 0, 3))
+        );
+
+        // Only link-local addresses need their scope to be dialed.
+        let scoped_global = SocketAddr::V6(SocketAddrV6::new(
+            "2001:db8::1".parse().unwrap(),
+            40000,
+            0,
+            3,
+        ));
+        assert_eq!(peer_ip(&scoped_global), "2001:db8::1");
+
+        assert_eq!(host_port("desk.local", 52742), "desk.local:52742");
+    }
+
+    #[test]
+    fn json_frames_round_trip() {
+        let (payload, decoded) = round_trip(&PeerMessage::Ping { ts: 42 });
+        assert_eq!(payload[0], b'{');
+        assert!(matches!(decoded, PeerMessage::Ping { ts: 42 }));
+
+        let (_, decoded) = round_trip(&PeerMessage::Clipboard {
+            content_type: "text/plain".to_string(),
+            data: "héllo".to_string(),
+            timestamp: 7,
+            logical: 1,
+            compression: None,
+            origin: None,
+        });
+        let PeerMessage::Clipboard {
+            content_type,
+            data,
+            timestamp,
+            logical,
+            ..
+        } = decoded
+        else {
+            panic!("not a Clipboard message");
+        };
+        assert_eq!(
+            (content_type.as_str(), data.as_str()),
+            ("text/plain", "héllo")
+        );
+        assert_eq!((timestamp, logical), (7, 1));
+    }
+
+    #[test]
+    fn binary_frames_carry_raw_bytes() {
+        // Bytes that would confuse a JSON decoder, including the marker.
+        let body = vec![0x00, b'{', 0xff, b'"', 0x00];
+        let (payload, decoded) = round_trip(&PeerMessage::ClipboardBinary {
+            content_type: "image/png".to_string(),
+            timestamp: 7,
+            logical: 0,
+            compression: None,
+            origin: None,
+            data: body.clone(),
+        });
+        assert_eq!(payload[0], BINARY_FRAME_MARKER);
+        assert!(payload.ends_with(&body));
+        let PeerMessage::ClipboardBinary {
+            content_type, data, ..
+        } = decoded
+        else {
+            panic!("not a ClipboardBinary message");
+        };
+        assert_eq!(content_type, "image/png");
+        assert_eq!(data, body);
+
+        let (_, decoded) = round_trip(&PeerMessage::TransferChunkBinary {
+            transfer_id: 3,
+            index: 4,
+            data: Vec::new(),
+        });
+        assert!(matches!(
+            decoded,
+            PeerMessage::TransferChunkBinary { transfer_id: 3, index: 4, ref data } if data.is_empty()
+        ));
+    }
+
+    #[test]
+    fn unknown_message_types_decode_as_unknown() {
+        let json = br#"{"type":"FromTheFuture","field":1}"#.to_vec();
+        assert!(matches!(
+            decode_peer_message(json),
+            Ok(PeerMessage::Unknown)
+        ));
+
+        let header = br#"{"type":"FutureBinary"}"#;
+        let mut frame = vec![BINARY_FRAME_MARKER];
+        frame.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        frame.extend_from_slice(header);
+        frame.extend_from_slice(b"raw body");
+        assert!(matches!(
+            decode_peer_message(frame),
+            Ok(PeerMessage::Unknown)
+        ));
+    }
+
+    #[test]
+    fn malformed_binary_frames_are_rejected() {
+        assert!(decode_peer_message(vec![BINARY_FRAME_MARKER, 0, 0]).is_err());
+
+        let mut overrun = vec![BINARY_FRAME_MARKER];
+        overrun.extend_from_slice(&100u32.to_be_bytes());
+        overrun.extend_from_slice(b"{}");
+        assert!(decode_peer_message(overrun).is_err());
+
+        let header = br#"{"type":"Ping","ts":1}"#;
+        let mut not_binary = vec![BINARY_FRAME_MARKER];
+        not_binary.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        not_binary.extend_from_slice(header);
+        assert!(decode_peer_message(not_binary).is_err());
+    }
+}
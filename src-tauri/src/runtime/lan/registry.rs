@@ -0,0 +1,530 @@
+//! Live state of every LAN peer: discovery, connection and link health.
+//!
+//! Discovery (UDP beacons and mDNS) records each peer it hears from here,
+//! and the peer connector claims a peer here before dialing it, so one
+//! client task runs per peer. When a peer stops announcing itself its
+//! discovery record expires; unless a session with it is still live, the
+//! client task dialing it is cancelled and the claim cleared, so the peer
+//! is dialed afresh (at whatever address it comes back with) once it is
+//! discovered again.
+//!
+//! Every peer session registers itself here when its handshake completes,
+//! with its role (host or client), and keeps its link statistics up to
+//! date: the heartbeat round-trip time (smoothed like TCP's SRTT), bytes
+//! and clipboard items in each direction, the last time anything arrived
+//! and how long the session has been up. Entries of peers a session was
+//! held with outlive their sessions, so a peer that keeps dropping shows
+//! up with a growing reconnect count instead of vanishing.
+//!
+//! The registry drives [`RuntimeEvent::LanPeersChanged`] (see
+//! [`emit_peer_list`]). Link statistics are published as
+//! [`RuntimeEvent::LanLinkStats`] every [`LINK_STATS_INTERVAL_SECS`] and can
+//! be queried through [`super::LanControl::link_stats_json`].
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex as ParkingMutex;
+use serde::Serialize;
+use tokio::{
+    sync::mpsc,
+    time::{interval, Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use super::discovery::now_unix_secs;
+use super::pairing::{PeerAccess, TrustStore};
+use super::protocol::{
+    DiscoveredPeer, DiscoveryBeacon, DISCOVERY_INTERVAL_SECS, HEARTBEAT_TIMEOUT_SECS,
+};
+use crate::runtime::RuntimeEvent;
+
+/// How many seconds before a peer that has not re-announced is considered
+/// gone.
+const PEER_EXPIRY_SECS: u64 = 15;
+
+/// How often link statistics are published while any session is live
+/// (seconds).
+pub const LINK_STATS_INTERVAL_SECS: u64 = 5;
+
+/// Weight of a new RTT sample in the smoothed RTT (RFC 6298 uses 1/8).
+const RTT_ALPHA: f64 = 0.125;
+
+/// How far a connection with a peer has got.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkState {
+    /// No session, and none being set up.
+    #[default]
+    Idle,
+    /// A client task is opening a TCP connection to the peer.
+    Connecting,
+    /// Connected; the encrypted handshake and `Hello`/`Welcome` are under
+    /// way.
+    Handshaking,
+    /// A peer session is running.
+    Connected,
+}
+
+/// Which end of the TCP connection we are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionRole {
+    /// The peer dialed us.
+    Host,
+    /// We dialed the peer.
+    Client,
+}
+
+/// A client task's claim on a peer.
+struct DialClaim {
+    /// Stops the task when the peer expires; `None` for static addresses,
+    /// which are dialed for as long as LAN mode runs.
+    cancel: Option<CancellationToken>,
+}
+
+/// Link statistics of one peer, as published to the UI.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkStats {
+    pub device_id: String,
+    pub device_name: String,
+    /// A session with the peer is live.
+    pub connected: bool,
+    /// Smoothed heartbeat round-trip time in milliseconds.
+    pub rtt_ms: Option<f64>,
+    /// Most recent heartbeat round-trip time in milliseconds.
+    pub last_rtt_ms: Option<u64>,
+    /// Bytes on the wire in the current (or last) session.
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Clipboard items in the current (or last) session.
+    pub items_sent: u64,
+    pub items_received: u64,
+    /// When a message last arrived from the peer (unix timestamp, secs).
+    pub last_activity: Option<u64>,
+    /// Sessions established after the first one.
+    pub reconnects: u32,
+    /// How long the current session has been up, or 0 when disconnected
+    /// (seconds).
+    pub uptime_secs: u64,
+}
+
+/// A peer as shown in the UI: its discovery record, where its connection
+/// stands and its access status.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerListEntry {
+    pub device_id: String,
+    pub device_name: String,
+    /// Address the peer announced, or that its session comes from.
+    pub addr: String,
+    /// Announced TCP port; 0 for peers only known from a session.
+    pub tcp_port: u16,
+    /// Local interface the peer was found on, when known.
+    pub interface: Option<String>,
+    /// When the peer last announced itself (unix timestamp, secs), or 0.
+    pub last_seen: u64,
+    /// The peer's discovery record is current.
+    pub discovered: bool,
+    pub state: LinkState,
+    pub role: Option<SessionRole>,
+    /// Connected and heard from within the heartbeat timeout.
+    pub healthy: bool,
+    /// Smoothed heartbeat round-trip time in milliseconds.
+    pub rtt_ms: Option<f64>,
+    /// Why the last connection attempt or session failed.
+    pub last_error: Option<String>,
+    pub access: PeerAccess,
+}
+
+/// Everything known about one peer.
+#[derive(Default)]
+struct PeerEntry {
+    device_name: String,
+    discovered: Option<DiscoveredPeer>,
+    dial: Option<DialClaim>,
+    state: LinkState,
+    role: Option<SessionRole>,
+    /// Remote address of the live (or last) session.
+    session_addr: Option<String>,
+    last_error: Option<String>,
+    /// Bumped for every new session, so a replaced session cannot
+    /// unregister its successor.
+    session: u64,
+    sessions: u32,
+    connected_at: Option<Instant>,
+    rtt_ms: Option<f64>,
+    last_rtt_ms: Option<u64>,
+    bytes_sent: Arc<AtomicU64>,
+    bytes_received: Arc<AtomicU64>,
+    items_sent: u64,
+    items_received: u64,
+    last_activity: Option<u64>,
+}
+
+impl PeerEntry {
+    fn stats(&self, device_id: &str) -> LinkStats {
+        LinkStats {
+            device_id: device_id.to_string(),
+            device_name: self.device_name.clone(),
+            connected: self.connected_at.is_some(),
+            rtt_ms: self.rtt_ms,
+            last_rtt_ms: self.last_rtt_ms,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            items_sent: self.items_sent,
+            items_received: self.items_received,
+            last_activity: self.last_activity,
+            reconnects: self.sessions.saturating_sub(1),
+            uptime_secs: self
+                .connected_at
+                .map_or(0, |since| since.elapsed().as_secs()),
+        }
+    }
+
+    fn list_entry(&self, device_id: &str, access: PeerAccess, now: u64) -> PeerListEntry {
+        let discovered = self.discovered.as_ref();
+        PeerListEntry {
+            device_id: device_id.to_string(),
+            device_name: self.device_name.clone(),
+            addr: discovered
+                .map(|p| p.addr.clone())
+                .or_else(|| self.session_addr.clone())
+                .unwrap_or_default(),
+            tcp_port: discovered.map_or(0, |p| p.tcp_port),
+            interface: discovered.and_then(|p| p.interface.clone()),
+            last_seen: discovered.map_or(0, |p| p.last_seen),
+            discovered: discovered.is_some(),
+            state: self.state,
+            role: self.role,
+            healthy: self.state == LinkState::Connected
+                && self
+                    .last_activity
+                    .is_some_and(|t| now.saturating_sub(t) < HEARTBEAT_TIMEOUT_SECS),
+            rtt_ms: self.rtt_ms,
+            last_error: self.last_error.clone(),
+            access,
+        }
+    }
+
+    /// Shown in the peer list: discovered, or being connected to.
+    fn is_listed(&self) -> bool {
+        self.discovered.is_some() || self.state != LinkState::Idle
+    }
+
+    /// Drop a discovered peer's dial claim, stopping its client task.
+    fn cancel_dial(&mut self) {
+        if self.dial.as_ref().is_some_and(|d| d.cancel.is_some()) {
+            if let Some(cancel) = self.dial.take().and_then(|d| d.cancel) {
+                cancel.cancel();
+            }
+            self.state = LinkState::Idle;
+        }
+    }
+}
+
+/// Per-peer state shared by discovery, the connector and all sessions of
+/// this node.
+#[derive(Clone, Default)]
+pub struct PeerRegistry {
+    inner: Arc<ParkingMutex<HashMap<String, PeerEntry>>>,
+}
+
+impl PeerRegistry {
+    // ── Discovery ────────────────────────────────────────────────────────
+
+    /// Record a beacon (or mDNS announcement) from a peer. Returns `true`
+    /// when it is a new peer, or its `device_name`, `addr`, `tcp_port` or
+    /// `interface` differs from what was recorded.
+    pub fn upsert_discovered(
+        &self,
+        beacon: &DiscoveryBeacon,
+        ip: &str,
+        interface: Option<&str>,
+        now: u64,
+    ) -> bool {
+        let mut peers = self.inner.lock();
+        let entry = peers.entry(beacon.device_id.clone()).or_default();
+
+        // A dual-stack peer is heard over both address families; keep its
+        // IPv4 address while it keeps announcing instead of flapping
+        // between them.
+        let (addr, interface) = match &entry.discovered {
+            Some(existing)
+                if !existing.addr.contains(':')
+                    && ip.contains(':')
+                    && now.saturating_sub(existing.last_seen) < DISCOVERY_INTERVAL_SECS * 2 =>
+            {
+                (existing.addr.clone(), existing.interface.clone())
+            }
+            _ => (ip.to_string(), interface.map(str::to_string)),
+        };
+
+        let new_record = DiscoveredPeer {
+            device_id: beacon.device_id.clone(),
+            device_name: beacon.device_name.clone(),
+            addr,
+            tcp_port: beacon.tcp_port,
+            interface,
+            last_seen: now,
+        };
+        // Always update `last_seen`, but only report a change when
+        // user-visible fields actually differ.
+        let changed = entry.discovered.as_ref().is_none_or(|existing| {
+            existing.device_name != new_record.device_name
+                || existing.addr != new_record.addr
+                || existing.tcp_port != new_record.tcp_port
+                || existing.interface != new_record.interface
+        });
+        entry.device_name = new_record.device_name.clone();
+        entry.discovered = Some(new_record);
+        changed
+    }
+
+    /// Expire discovery records not refreshed within [`PEER_EXPIRY_SECS`]
+    /// and return how many expired. Client tasks dialing an expired peer
+    /// are stopped unless its session is live; peers never connected to
+    /// are forgotten.
+    pub fn prune_discovered(&self, now: u64) -> usize {
+        let mut peers = self.inner.lock();
+        let mut expired = 0;
+        for entry in peers.values_mut() {
+            if entry
+                .discovered
+                .as_ref()
+                .is_some_and(|p| now.saturating_sub(p.last_seen) >= PEER_EXPIRY_SECS)
+            {
+                entry.discovered = None;
+                expired += 1;
+                if entry.state != LinkState::Connected {
+                    entry.cancel_dial();
+                }
+            }
+        }
+        peers.retain(|_, entry| entry.is_listed() || entry.dial.is_some() || entry.sessions > 0);
+        expired
+    }
+
+    /// Peers with a current discovery record.
+    pub fn discovered(&self) -> Vec<DiscoveredPeer> {
+        self.inner
+            .lock()
+            .values()
+            .filter_map(|entry| entry.discovered.clone())
+            .collect()
+    }
+
+    /// The name a peer announced or connected with.
+    pub fn device_name(&self, device_id: &str) -> Option<String> {
+        self.inner
+            .lock()
+            .get(device_id)
+            .map(|entry| entry.device_name.clone())
+            .filter(|name| !name.is_empty())
+    }
+
+    // ── Dialing ──────────────────────────────────────────────────────────
+
+    /// Claim `device_id` for a client task, so no other task dials it.
+    /// `cancel` stops the task once the peer expires from discovery; pass
+    /// `None` for a static address. Fails when the peer is already claimed.
+    pub fn claim_dial(&self, device_id: &str, cancel: Option<CancellationToken>) -> bool {
+        let mut peers = self.inner.lock();
+        let entry = peers.entry(device_id.to_string()).or_default();
+        if entry.dial.is_some() {
+            return false;
+        }
+        entry.dial = Some(DialClaim { cancel });
+        true
+    }
+
+    /// Give up a claim taken by [`claim_dial`](Self::claim_dial).
+    pub fn release_dial(&self, device_id: &str) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            entry.dial = None;
+        }
+    }
+
+    /// Note how far a client task dialing `device_id` has got. A live
+    /// session (with either role) is not overridden.
+    pub fn set_connecting(&self, device_id: &str, state: LinkState) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            if entry.state != LinkState::Connected {
+                entry.state = state;
+            }
+        }
+    }
+
+    /// A connection attempt to `device_id` failed before a session started.
+    pub fn connect_failed(&self, device_id: &str, error: &str) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            if entry.state != LinkState::Connected {
+                entry.state = LinkState::Idle;
+                entry.last_error = Some(error.to_string());
+            }
+        }
+    }
+
+    // ── Sessions ─────────────────────────────────────────────────────────
+
+    /// Register a new session with `device_id` from `addr`, counting the
+    /// bytes moved by its secure stream halves. Returns the token to pass
+    /// to [`session_ended`](Self::session_ended).
+    pub fn session_started(
+        &self,
+        device_id: &str,
+        device_name: &str,
+        role: SessionRole,
+        addr: Option<String>,
+        bytes_sent: Arc<AtomicU64>,
+        bytes_received: Arc<AtomicU64>,
+    ) -> u64 {
+        let mut peers = self.inner.lock();
+        let entry = peers.entry(device_id.to_string()).or_default();
+        entry.device_name = device_name.to_string();
+        entry.state = LinkState::Connected;
+        entry.role = Some(role);
+        entry.session_addr = addr;
+        entry.last_error = None;
+        entry.session += 1;
+        entry.sessions += 1;
+        entry.connected_at = Some(Instant::now());
+        entry.rtt_ms = None;
+        entry.last_rtt_ms = None;
+        entry.bytes_sent = bytes_sent;
+        entry.bytes_received = bytes_received;
+        entry.items_sent = 0;
+        entry.items_received = 0;
+        entry.last_activity = Some(now_unix_secs());
+        entry.session
+    }
+
+    /// Mark the session `session` with `device_id` as closed (with `error`
+    /// when it failed), unless a newer session has taken its place. Its
+    /// statistics stay available. A peer that is no longer discovered is
+    /// not redialed.
+    pub fn session_ended(&self, device_id: &str, session: u64, error: Option<String>) {
+        let mut peers = self.inner.lock();
+        let Some(entry) = peers
+            .get_mut(device_id)
+            .filter(|entry| entry.session == session)
+        else {
+            return;
+        };
+        entry.state = LinkState::Idle;
+        entry.role = None;
+        entry.connected_at = None;
+        entry.last_error = error;
+        if entry.discovered.is_none() {
+            entry.cancel_dial();
+        }
+    }
+
+    /// Fold a heartbeat round trip into the peer's smoothed RTT.
+    pub fn record_rtt(&self, device_id: &str, rtt_ms: u64) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            let sample = rtt_ms as f64;
+            entry.rtt_ms = Some(match entry.rtt_ms {
+                Some(srtt) => srtt + RTT_ALPHA * (sample - srtt),
+                None => sample,
+            });
+            entry.last_rtt_ms = Some(rtt_ms);
+        }
+    }
+
+    /// Note that a message arrived from the peer.
+    pub fn record_activity(&self, device_id: &str) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            entry.last_activity = Some(now_unix_secs());
+        }
+    }
+
+    /// Count a clipboard item sent to the peer.
+    pub fn record_item_sent(&self, device_id: &str) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            entry.items_sent += 1;
+        }
+    }
+
+    /// Count a clipboard item received from the peer.
+    pub fn record_item_received(&self, device_id: &str) {
+        if let Some(entry) = self.inner.lock().get_mut(device_id) {
+            entry.items_received += 1;
+        }
+    }
+
+    // ── Snapshots ────────────────────────────────────────────────────────
+
+    /// Discovered and connected peers, tagged with their access status.
+    pub fn peer_list(&self, trust: &TrustStore) -> Vec<PeerListEntry> {
+        let now = now_unix_secs();
+        self.inner
+            .lock()
+            .iter()
+            .filter(|(_, entry)| entry.is_listed())
+            .map(|(device_id, entry)| entry.list_entry(device_id, trust.access(device_id), now))
+            .collect()
+    }
+
+    /// Statistics of every peer a session was ever established with.
+    pub fn link_stats(&self) -> Vec<LinkStats> {
+        self.inner
+            .lock()
+            .iter()
+            .filter(|(_, entry)| entry.sessions > 0)
+            .map(|(device_id, entry)| entry.stats(device_id))
+            .collect()
+    }
+
+    fn any_connected(&self) -> bool {
+        self.inner
+            .lock()
+            .values()
+            .any(|entry| entry.connected_at.is_some())
+    }
+}
+
+/// Emit the current peer list as a JSON-serialised
+/// [`RuntimeEvent::LanPeersChanged`] so the frontend can refresh the
+/// displayed peer list.
+pub async fn emit_peer_list(
+    registry: &PeerRegistry,
+    trust: &TrustStore,
+    events: &mpsc::Sender<RuntimeEvent>,
+) {
+    let json = serde_json::to_string(&registry.peer_list(trust)).unwrap_or_else(|_| "[]".into());
+    let _ = events.send(RuntimeEvent::LanPeersChanged(json)).await;
+}
+
+/// JSON-serialised [`PeerRegistry::link_stats`].
+pub fn link_stats_json(registry: &PeerRegistry) -> String {
+    serde_json::to_string(&registry.link_stats()).unwrap_or_else(|_| "[]".into())
+}
+
+/// Publish link statistics every [`LINK_STATS_INTERVAL_SECS`] while any
+/// session is live, and once more after the last one closes.
+pub async fn run_link_stats_publisher(
+    registry: PeerRegistry,
+    events: mpsc::Sender<RuntimeEvent>,
+    cancel: CancellationToken,
+) {
+    let mut tick = interval(Duration::from_secs(LINK_STATS_INTERVAL_SECS));
+    let mut was_connected = false;
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tick.tick() => {}
+        }
+        let connected = registry.any_connected();
+        if connected || was_connected {
+            let _ = events
+                .send(RuntimeEvent::LanLinkStats(link_stats_json(&registry)))
+                .await;
+        }
+        was_connected = connected;
+    }
+}
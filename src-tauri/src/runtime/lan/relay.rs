@@ -0,0 +1,136 @@
+//! Relaying clipboard items across a partially connected LAN mesh.
+//!
+//! Sessions only exist between peers that can reach each other, so when A
+//! reaches B and B reaches C but A cannot reach C, C would never see A's
+//! clipboard. With `lan_relay` enabled, a peer forwards every item it
+//! accepts from one session to all of its other sessions.
+//!
+//! Every item carries its origin device id, a random item id and a hop
+//! count ([`super::protocol::ItemOrigin`]). Each node remembers the items
+//! it has already seen and drops repeats, so an item floods the mesh once
+//! instead of echoing between peers. An item is never sent back to the
+//! session it came from nor to its origin, and stops after
+//! [`MAX_RELAY_HOPS`] relays. Items sent to one peer on demand name that
+//! peer as their target and are never relayed.
+//!
+//! Delivery receipts travel the reverse path: a node that relayed an item
+//! remembers which peer it got the item from and forwards receipts for it
+//! to that peer.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use parking_lot::Mutex as ParkingMutex;
+
+use crate::runtime::messages::ClipboardItem;
+
+/// Relays an item may pass through before it is no longer forwarded.
+pub const MAX_RELAY_HOPS: u8 = 8;
+
+/// Whether a node relaying items passes `item` on: it is not addressed to
+/// one peer and has not used up its hops.
+pub fn is_relayable(item: &ClipboardItem) -> bool {
+    item.target.is_none() && item.hops < MAX_RELAY_HOPS
+}
+
+/// Items remembered per node. Far more than can be in flight at once, so
+/// only items long superseded are forgotten.
+const SEEN_CAPACITY: usize = 1024;
+
+/// Recently seen items, keyed by origin device and item id, with the peer
+/// each was first received from. Oldest first out.
+#[derive(Default)]
+pub struct SeenItems {
+    from: HashMap<(String, u64), String>,
+    order: VecDeque<(String, u64)>,
+}
+
+impl SeenItems {
+    /// Record an item received from peer `from`; `false` when it was seen
+    /// before.
+    pub fn insert(&mut self, origin: &str, item_id: u64, from: &str) -> bool {
+        let key = (origin.to_string(), item_id);
+        if self.from.contains_key(&key) {
+            return false;
+        }
+        self.from.insert(key.clone(), from.to_string());
+        self.order.push_back(key);
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.from.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// The peer an item was first received from, if it is remembered.
+    pub fn received_from(&self, origin: &str, item_id: u64) -> Option<&str> {
+        self.from
+            .get(&(origin.to_string(), item_id))
+            .map(String::as_str)
+    }
+}
+
+/// Seen-set shared by all sessions of this node.
+pub type SharedSeenItems = Arc<ParkingMutex<SeenItems>>;
+
+/// Create a new, empty seen-set.
+pub fn new_seen_items() -> SharedSeenItems {
+    Arc::new(ParkingMutex::new(SeenItems::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::clock::Hlc;
+
+    #[test]
+    fn items_are_seen_once_and_remember_where_they_came_from() {
+        let mut seen = SeenItems::default();
+        assert!(seen.insert("origin", 1, "peer-a"));
+        assert!(!seen.insert("origin", 1, "peer-b"));
+        assert_eq!(seen.received_from("origin", 1), Some("peer-a"));
+
+        // Item ids are only unique per origin.
+        assert!(seen.insert("other-origin", 1, "peer-b"));
+        assert!(seen.insert("origin", 2, "peer-b"));
+        assert_eq!(seen.received_from("origin", 3), None);
+    }
+
+    #[test]
+    fn the_oldest_items_are_forgotten_first() {
+        let mut seen = SeenItems::default();
+        for item_id in 0..SEEN_CAPACITY as u64 {
+            assert!(seen.insert("origin", item_id, "peer"));
+        }
+        assert!(seen.received_from("origin", 0).is_some());
+
+        assert!(seen.insert("origin", SEEN_CAPACITY as u64, "peer"));
+        assert_eq!(seen.received_from("origin", 0), None);
+        assert!(seen.received_from("origin", 1).is_some());
+        // A forgotten item counts as new again.
+        assert!(seen.insert("origin", 0, "peer"));
+        assert!(!seen.insert("origin", SEEN_CAPACITY as u64, "peer"));
+    }
+
+    #[test]
+    fn items_stop_after_the_hop_limit_or_when_addressed() {
+        let mut item = ClipboardItem::new(
+            "text/plain".to_string(),
+            b"x".to_vec().into(),
+            "origin".to_string(),
+            Hlc::default(),
+        );
+        assert!(is_relayable(&item));
+        item.hops = MAX_RELAY_HOPS - 1;
+        assert!(is_relayable(&item));
+        item.hops = MAX_RELAY_HOPS;
+        assert!(!is_relayable(&item));
+
+        item.hops = 0;
+        item.target = Some("peer".to_string());
+        assert!(!is_relayable(&item));
+    }
+}
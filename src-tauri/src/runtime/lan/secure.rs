@@ -0,0 +1,414 @@
+//! Encrypted, mutually authenticated transport for LAN peer sessions.
+//!
+//! Every TCP peer connection starts with a Noise `XX` handshake
+//! ([`NOISE_PARAMS`]) in which both sides prove possession of their
+//! long-lived device key ([`DeviceIdentity`]). Afterwards each side knows the
+//! other's static public key, which the trust store pins at pairing time,
+//! and a handshake hash unique to the session. The PIN exchange is bound
+//! to it, so a man-in-the-middle holding one session with each side has to
+//! guess the PIN in both (see [`super::pairing`]).
+//!
+//! The existing length-prefixed frames from
+//! [`encode_peer_message`](super::protocol::encode_peer_message) are carried
+//! unchanged on top. Because a single Noise message is limited to
+//! [`MAXMSGLEN`] bytes, a frame is split into segments on the wire:
+//!
+//! ```text
+//! [2 bytes big-endian ciphertext length][ciphertext]  × n
+//! ```
+//!
+//! A frame always starts on a segment boundary, so the first decrypted
+//! segment carries the frame's 4-byte length prefix.
+//!
+//! Each half counts the bytes it moved on the wire after the handshake, for
+//! the link statistics in [`super::registry`].
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use snow::{params::NoiseParams, Builder, HandshakeState, StatelessTransportState};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+};
+
+use super::protocol::MAX_FRAME_SIZE;
+use crate::runtime::identity::DeviceIdentity;
+
+// ────────────────────────────────────────────────────────────────────────────
+// Constants
+// ────────────────────────────────────────────────────────────────────────────
+
+/// Noise protocol used for every peer connection.
+pub const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+
+/// Prologue mixed into the handshake so it cannot be confused with any other
+/// Noise-based protocol.
+const NOISE_PROLOGUE: &[u8] = b"RSCV_LAN/1";
+
+/// Maximum size of a single Noise message, fixed by the Noise spec.
+const MAXMSGLEN: usize = 65535;
+
+/// Size of the ChaChaPoly authentication tag appended to every ciphertext.
+const TAGLEN: usize = 16;
+
+/// Largest plaintext that fits in one Noise transport message.
+const MAX_SEGMENT_PLAINTEXT: usize = MAXMSGLEN - TAGLEN;
+
+/// Parsed [`NOISE_PARAMS`]; also used to generate device keys.
+pub fn noise_params() -> NoiseParams {
+    NOISE_PARAMS
+        .parse()
+        .expect("NOISE_PARAMS is a valid pattern")
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Handshake
+// ────────────────────────────────────────────────────────────────────────────
+
+/// An established encrypted connection, before it is split into halves.
+pub struct SecureStream {
+    reader: SecureReader,
+    writer: SecureWriter,
+    remote_public_key: Vec<u8>,
+    handshake_hash: Vec<u8>,
+}
+
+/// Run the handshake as the connecting (client) side.
+pub async fn secure_connect(stream: TcpStream, identity: &DeviceIdentity) -> Result<SecureStream> {
+    let mut stream = stream;
+    let mut noise = Builder::new(noise_params())
+        .local_private_key(identity.private_key())
+        .prologue(NOISE_PROLOGUE)
+        .build_initiator()
+        .map_err(|e| anyhow!("noise init: {}", e))?;
+
+    // -> e
+    write_handshake_message(&mut stream, &mut noise).await?;
+    // <- e, ee, s, es
+    read_handshake_message(&mut stream, &mut noise).await?;
+    // -> s, se
+    write_handshake_message(&mut stream, &mut noise).await?;
+
+    finish_handshake(stream, noise)
+}
+
+/// Run the handshake as the accepting (host) side.
+pub async fn secure_accept(stream: TcpStream, identity: &DeviceIdentity) -> Result<SecureStream> {
+    let mut stream = stream;
+    let mut noise = Builder::new(noise_params())
+        .local_private_key(identity.private_key())
+        .prologue(NOISE_PROLOGUE)
+        .build_responder()
+        .map_err(|e| anyhow!("noise init: {}", e))?;
+
+    // -> e
+    read_handshake_message(&mut stream, &mut noise).await?;
+    // <- e, ee, s, es
+    write_handshake_message(&mut stream, &mut noise).await?;
+    // -> s, se
+    read_handshake_message(&mut stream, &mut noise).await?;
+
+    finish_handshake(stream, noise)
+}
+
+async fn write_handshake_message(stream: &mut TcpStream, noise: &mut HandshakeState) -> Result<()> {
+    let mut buf = vec![0u8; MAXMSGLEN];
+    let len = noise
+        .write_message(&[], &mut buf)
+        .map_err(|e| anyhow!("noise handshake write: {}", e))?;
+    write_segment(stream, &buf[..len]).await
+}
+
+async fn read_handshake_message(stream: &mut TcpStream, noise: &mut HandshakeState) -> Result<()> {
+    let message = read_segment(stream).await?;
+    let mut payload = vec![0u8; MAXMSGLEN];
+    noise
+        .read_message(&message, &mut payload)
+        .map_err(|e| anyhow!("noise handshake read: {}", e))?;
+    Ok(())
+}
+
+fn finish_handshake(stream: TcpStream, noise: HandshakeState) -> Result<SecureStream> {
+    let remote_public_key = noise
+        .get_remote_static()
+        .ok_or_else(|| anyhow!("peer did not present a static key"))?
+        .to_vec();
+    let handshake_hash = noise.get_handshake_hash().to_vec();
+    let transport = Arc::new(
+        noise
+            .into_stateless_transport_mode()
+            .map_err(|e| anyhow!("noise transport: {}", e))?,
+    );
+
+    let (read_half, write_half) = stream.into_split();
+    Ok(SecureStream {
+        reader: SecureReader {
+            half: read_half,
+            transport: transport.clone(),
+            nonce: 0,
+            bytes: Arc::default(),
+        },
+        writer: SecureWriter {
+            half: write_half,
+            transport,
+            nonce: 0,
+            bytes: Arc::default(),
+        },
+        remote_public_key,
+        handshake_hash,
+    })
+}
+
+// ────────────────────────────────────────────────────────────────────────────
+// Encrypted frame I/O
+// ────────────────────────────────────────────────────────────────────────────
+
+impl SecureStream {
+    /// The remote device's static public key, authenticated by the
+    /// handshake.
+    pub fn remote_public_key(&self) -> &[u8] {
+        &self.remote_public_key
+    }
+
+    /// Hash of the handshake transcript; identical on both ends of this
+    /// connection and unique to it.
+    pub fn handshake_hash(&self) -> &[u8] {
+        &self.handshake_hash
+    }
+
+    /// Split into independently usable read and write halves.
+    pub fn into_split(self) -> (SecureReader, SecureWriter) {
+        (self.reader, self.writer)
+    }
+}
+
+/// Receiving half of a [`SecureStream`].
+pub struct SecureReader {
+    half: OwnedReadHalf,
+    transport: Arc<StatelessTransportState>,
+    nonce: u64,
+    bytes: Arc<AtomicU64>,
+}
+
+/// Sending half of a [`SecureStream`].
+pub struct SecureWriter {
+    half: OwnedWriteHalf,
+    transport: Arc<StatelessTransportState>,
+    nonce: u64,
+    bytes: Arc<AtomicU64>,
+}
+
+impl SecureWriter {
+    /// Running count of bytes written, shared with whoever keeps stats.
+    pub fn byte_counter(&self) -> Arc<AtomicU64> {
+        self.bytes.clone()
+    }
+
+    /// Encrypt `frame` (a complete length-prefixed frame) segment by segment
+    /// and write it in a single `write_all`.
+    pub async fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let segments = frame.len().div_ceil(MAX_SEGMENT_PLAINTEXT).max(1);
+        let mut wire = Vec::with_capacity(frame.len() + segments * (2 + TAGLEN));
+        let mut ciphertext = vec![0u8; MAXMSGLEN];
+
+        for chunk in frame.chunks(MAX_SEGMENT_PLAINTEXT) {
+            let len = self
+                .transport
+                .write_message(self.nonce, chunk, &mut ciphertext)
+                .map_err(|e| anyhow!("encrypting frame: {}", e))?;
+            self.nonce += 1;
+            wire.extend_from_slice(&(len as u16).to_be_bytes());
+            wire.extend_from_slice(&ciphertext[..len]);
+        }
+
+        self.half
+            .write_all(&wire)
+            .await
+            .context("writing encrypted frame")?;
+        self.bytes.fetch_add(wire.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl SecureReader {
+    /// Address of the remote end, as `"<ip>"` with IPv4-mapped addresses
+    /// unwrapped.
+    pub fn peer_addr(&self) -> Option<String> {
+        self.half
+            .peer_addr()
+            .ok()
+            .map(|addr| addr.ip().to_canonical().to_string())
+    }
+
+    /// Running count of bytes read, shared with whoever keeps stats.
+    pub fn byte_counter(&self) -> Arc<AtomicU64> {
+        self.bytes.clone()
+    }
+
+    /// Read and decrypt segments until one complete frame is assembled;
+    /// returns the frame payload without its 4-byte length prefix.
+    ///
+    /// Not cancel-safe: a partially read frame is lost (and the nonce
+    /// sequence with it) if the future is dropped. Drive it from a
+    /// dedicated task.
+    pub async fn read_frame(&mut self) -> Result<Vec<u8>> {
+        let mut plaintext = self.read_decrypted_segment().await?;
+        if plaintext.len() < 4 {
+            return Err(anyhow!("encrypted frame too short"));
+        }
+        let len = u32::from_be_bytes([plaintext[0], plaintext[1], plaintext[2], plaintext[3]]);
+        if len > MAX_FRAME_SIZE {
+            return Err(anyhow!(
+                "frame too large: {} bytes (max {})",
+                len,
+                MAX_FRAME_SIZE
+            ));
+        }
+
+        let total = 4 + len as usize;
+        while plaintext.len() < total {
+            let segment = self.read_decrypted_segment().await?;
+            plaintext.extend_from_slice(&segment);
+        }
+        if plaintext.len() != total {
+            return Err(anyhow!("encrypted frame boundary mismatch"));
+        }
+
+        plaintext.drain(..4);
+        Ok(plaintext)
+    }
+
+    async fn read_decrypted_segment(&mut self) -> Result<Vec<u8>> {
+        let ciphertext = read_segment(&mut self.half).await?;
+        self.bytes
+            .fetch_add(2 + ciphertext.len() as u64, Ordering::Relaxed);
+        let mut plaintext = vec![0u8; ciphertext.len()];
+        let len = self
+            .transport
+            .read_message(self.nonce, &ciphertext, &mut plaintext)
+            .map_err(|e| anyhow!("decrypting frame: {}", e))?;
+        self.nonce += 1;
+        plaintext.truncate(len);
+        Ok(plaintext)
+    }
+}
+
+async fn write_segment<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) -> Result<()> {
+    let mut wire = Vec::with_capacity(2 + data.len());
+    wire.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    wire.extend_from_slice(data);
+    writer.write_all(&wire).await.context("writing segment")
+}
+
+async fn read_segment<R: AsyncReadExt + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut len_buf = [0u8; 2];
+    reader
+        .read_exact(&mut len_buf)
+        .await
+        .context("reading segment length")?;
+    let mut data = vec![0u8; u16::from_be_bytes(len_buf) as usize];
+    reader
+        .read_exact(&mut data)
+        .await
+        .context("reading segment")?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::lan::protocol::{encode_peer_message, PeerMessage};
+    use tokio::net::TcpListener;
+
+    /// Connect two in-process peers over loopback and complete the
+    /// handshake on both ends.
+    async fn loopback_pair(
+        host_identity: &DeviceIdentity,
+        client_identity: &DeviceIdentity,
+    ) -> (SecureStream, SecureStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let host_identity = host_identity.clone();
+        let host = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            secure_accept(stream, &host_identity).await.unwrap()
+        });
+        let client_stream = TcpStream::connect(addr).await.unwrap();
+        let client = secure_connect(client_stream, client_identity)
+            .await
+            .unwrap();
+        (host.await.unwrap(), client)
+    }
+
+    #[tokio::test]
+    async fn handshake_authenticates_both_device_keys() {
+        let host_identity = DeviceIdentity::generate().unwrap();
+        let client_identity = DeviceIdentity::generate().unwrap();
+
+        let (host, client) = loopback_pair(&host_identity, &client_identity).await;
+
+        assert_eq!(host.remote_public_key(), client_identity.public_key());
+        assert_eq!(client.remote_public_key(), host_identity.public_key());
+        assert_eq!(host.handshake_hash(), client.handshake_hash());
+    }
+
+    #[tokio::test]
+    async fn peer_messages_round_trip_in_both_directions() {
+        let (host, client) = loopback_pair(
+            &DeviceIdentity::generate().unwrap(),
+            &DeviceIdentity::generate().unwrap(),
+        )
+        .await;
+        let (mut host_rx, mut host_tx) = host.into_split();
+        let (mut client_rx, mut client_tx) = client.into_split();
+
+        // Larger than one Noise message so it spans several segments.
+        let big = PeerMessage::Clipboard {
+            content_type: "text/plain".into(),
+            data: "x".repeat(3 * MAXMSGLEN),
+            timestamp: 7,
+            logical: 0,
+            compression: None,
+            origin: None,
+        };
+        client_tx
+            .write_frame(&encode_peer_message(&big))
+            .await
+            .unwrap();
+        client_tx
+            .write_frame(&encode_peer_message(&PeerMessage::Ping { ts: 1 }))
+            .await
+            .unwrap();
+
+        let received: PeerMessage =
+            serde_json::from_slice(&host_rx.read_frame().await.unwrap()).unwrap();
+        match received {
+            PeerMessage::Clipboard {
+                data, timestamp, ..
+            } => {
+                assert_eq!(data.len(), 3 * MAXMSGLEN);
+                assert_eq!(timestamp, 7);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        let ping: PeerMessage =
+            serde_json::from_slice(&host_rx.read_frame().await.unwrap()).unwrap();
+        assert!(matches!(ping, PeerMessage::Ping { ts: 1 }));
+
+        host_tx
+            .write_frame(&encode_peer_message(&PeerMessage::Pong { ts: 1 }))
+            .await
+            .unwrap();
+        let pong: PeerMessage =
+            serde_json::from_slice(&client_rx.read_frame().await.unwrap()).unwrap();
+        assert!(matches!(pong, PeerMessage::Pong { ts: 1 }));
+    }
+}
@@ -0,0 +1,510 @@
+//! Chunked transfer of clipboard payloads too large for a single frame.
+//!
+//! A payload whose data exceeds [`CHUNK_SIZE`] is announced with
+//! `TransferBegin` (transfer id, content type, total size and SHA-256
+//! digest), streamed as numbered chunks and closed with `TransferEnd`, at
+//! which point the receiver checks size and digest before applying it.
+//! `TransferAbort` discards a transfer on either side.
+//!
+//! When the session negotiated binary frames the raw bytes are streamed as
+//! `TransferChunkBinary`; otherwise the payload's JSON wire form (text, or
+//! base64 for images) is streamed as `TransferChunk`. A payload compressed
+//! for the session (see [`crate::runtime::compression`]) is streamed in its
+//! compressed form and only decompressed once complete.
+//!
+//! The session loop sends one chunk per iteration, so heartbeats, pairing
+//! and small clipboard updates keep flowing while a large screenshot is in
+//! flight. Only one transfer runs at a time: a newer item copied on this
+//! device supersedes an unfinished transfer of an older one for the same
+//! target, and any other large item waits for the transfer to finish.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use ring::digest;
+use tokio::time::{Duration, Instant};
+
+use super::protocol::{ItemOrigin, PeerMessage};
+use crate::runtime::clock::Hlc;
+use crate::runtime::compression::Compressed;
+use crate::runtime::config::Config;
+use crate::runtime::messages::ClipboardItem;
+
+/// Payloads with more data bytes than this are sent in chunks of at most
+/// this size.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Large items that may wait per session for the transfer in flight; the
+/// oldest is dropped when another one comes in.
+pub const MAX_QUEUED_TRANSFERS: usize = 8;
+
+/// Largest transfer accepted from a peer: the base64 wire form of the
+/// biggest image the clipboard monitor will ever emit.
+pub const MAX_TRANSFER_SIZE: u64 = (Config::MAX_IMAGE_KB * 1024).div_ceil(3) * 4;
+
+/// An incoming transfer without a new chunk for this long is dropped
+/// (seconds).
+pub const TRANSFER_IDLE_TIMEOUT_SECS: u64 = 30;
+
+/// A payload being sent chunk by chunk.
+pub struct OutgoingTransfer {
+    id: u64,
+    content_type: String,
+    clock: Hlc,
+    /// Raw payload bytes (`binary`) or its UTF-8 JSON wire form.
+    data: Arc<[u8]>,
+    binary: bool,
+    compression: Option<&'static str>,
+    origin: ItemOrigin,
+    chunk_size: usize,
+    offset: usize,
+    index: u32,
+    finished: bool,
+}
+
+impl OutgoingTransfer {
+    /// Prepare `item` for sending in slices of at most `chunk_size` bytes,
+    /// as raw bytes when `binary` or in its JSON wire form otherwise. When
+    /// `compressed` is given it is streamed in place of the item's data.
+    pub fn new(
+        id: u64,
+        item: &ClipboardItem,
+        compressed: Option<Compressed>,
+        binary: bool,
+        chunk_size: usize,
+    ) -> Self {
+        let compression = compressed.as_ref().map(|c| c.codec);
+        let data = match (compressed, binary) {
+            (Some(c), true) => c.data.into(),
+            (Some(c), false) => c.to_base64().into_bytes().into(),
+            (None, true) => item.data.clone(),
+            (None, false) => item.wire_data().into_bytes().into(),
+        };
+        Self {
+            id,
+            content_type: item.content_type.clone(),
+            clock: item.clock,
+            data,
+            binary,
+            compression,
+            origin: ItemOrigin::of(item),
+            chunk_size: chunk_size.max(4),
+            offset: 0,
+            index: 0,
+            finished: false,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    pub fn total_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Identity of the item being sent.
+    pub fn origin(&self) -> &ItemOrigin {
+        &self.origin
+    }
+
+    pub fn clock(&self) -> Hlc {
+        self.clock
+    }
+
+    /// `true` once [`next_message`](Self::next_message) has produced the
+    /// `TransferEnd`.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The `TransferBegin` announcing this transfer.
+    pub fn begin_message(&self) -> PeerMessage {
+        PeerMessage::TransferBegin {
+            transfer_id: self.id,
+            content_type: self.content_type.clone(),
+            timestamp: self.clock.wall_ms,
+            logical: self.clock.logical,
+            total_len: self.data.len() as u64,
+            digest: payload_digest(&self.data),
+            binary: self.binary,
+            compression: self.compression.map(str::to_string),
+            origin: Some(self.origin.clone()),
+        }
+    }
+
+    /// The next chunk, or `TransferEnd` once all data has been sent.
+    pub fn next_message(&mut self) -> PeerMessage {
+        let data = &self.data;
+        if self.offset >= data.len() {
+            self.finished = true;
+            return PeerMessage::TransferEnd {
+                transfer_id: self.id,
+            };
+        }
+
+        let mut end = (self.offset + self.chunk_size).min(data.len());
+        let chunk = if self.binary {
+            PeerMessage::TransferChunkBinary {
+                transfer_id: self.id,
+                index: self.index,
+                data: data[self.offset..end].to_vec(),
+            }
+        } else {
+            // Text payloads may hold multi-byte characters; never split one
+            // (UTF-8 continuation bytes look like 0b10xxxxxx).
+            while end < data.len() && data[end] & 0xC0 == 0x80 {
+                end -= 1;
+            }
+            PeerMessage::TransferChunk {
+                transfer_id: self.id,
+                index: self.index,
+                data: String::from_utf8_lossy(&data[self.offset..end]).into_owned(),
+            }
+        };
+        self.offset = end;
+        self.index += 1;
+        chunk
+    }
+
+    /// The `TransferAbort` to send when giving up on this transfer.
+    pub fn abort_message(&self, reason: &str) -> PeerMessage {
+        PeerMessage::TransferAbort {
+            transfer_id: self.id,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// A payload being reassembled from chunks.
+pub struct IncomingTransfer {
+    id: u64,
+    content_type: String,
+    clock: Hlc,
+    total_len: u64,
+    digest: String,
+    binary: bool,
+    compression: Option<String>,
+    origin: Option<ItemOrigin>,
+    data: Vec<u8>,
+    next_index: u32,
+    last_activity: Instant,
+    reported_quarter: u64,
+}
+
+impl IncomingTransfer {
+    /// Start reassembling a transfer announced by `TransferBegin`.
+    pub fn new(
+        id: u64,
+        content_type: String,
+        clock: Hlc,
+        total_len: u64,
+        digest: String,
+        binary: bool,
+        compression: Option<String>,
+    ) -> Result<Self> {
+        if total_len > MAX_TRANSFER_SIZE {
+            return Err(anyhow!(
+                "transfer of {} bytes exceeds the {} byte limit",
+                total_len,
+                MAX_TRANSFER_SIZE
+            ));
+        }
+        Ok(Self {
+            id,
+            content_type,
+            clock,
+            total_len,
+            digest,
+            binary,
+            compression,
+            origin: None,
+            // Grow with the chunks rather than trusting the announced size.
+            data: Vec::with_capacity((total_len as usize).min(16 * 1024 * 1024)),
+            next_index: 0,
+            last_activity: Instant::now(),
+            reported_quarter: 0,
+        })
+    }
+
+    /// Identity the item announced in `TransferBegin`, restored by
+    /// [`finish`](Self::finish).
+    pub fn with_origin(mut self, origin: Option<ItemOrigin>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Append chunk `index`, which arrived as a binary frame when `binary`.
+    /// Returns the progress in percent when it crosses another quarter of
+    /// the total, for logging.
+    pub fn push(&mut self, index: u32, chunk: &[u8], binary: bool) -> Result<Option<u64>> {
+        if binary != self.binary {
+            return Err(anyhow!("chunk encoding does not match the transfer"));
+        }
+        if index != self.next_index {
+            return Err(anyhow!(
+                "expected chunk {} but got {}",
+                self.next_index,
+                index
+            ));
+        }
+        if (self.data.len() + chunk.len()) as u64 > self.total_len {
+            return Err(anyhow!(
+                "chunks exceed the announced {} bytes",
+                self.total_len
+            ));
+        }
+        self.data.extend_from_slice(chunk);
+        self.next_index += 1;
+        self.last_activity = Instant::now();
+
+        let quarter = self.data.len() as u64 * 4 / self.total_len.max(1);
+        if quarter > self.reported_quarter && quarter < 4 {
+            self.reported_quarter = quarter;
+            return Ok(Some(quarter * 25));
+        }
+        Ok(None)
+    }
+
+    /// `true` when no chunk arrived for [`TRANSFER_IDLE_TIMEOUT_SECS`].
+    pub fn is_idle(&self) -> bool {
+        self.last_activity.elapsed() > Duration::from_secs(TRANSFER_IDLE_TIMEOUT_SECS)
+    }
+
+    /// Verify size and digest and hand out the reassembled item.
+    pub fn finish(self, sender_device_id: &str) -> Result<ClipboardItem> {
+        if self.data.len() as u64 != self.total_len {
+            return Err(anyhow!(
+                "transfer ended after {} of {} bytes",
+                self.data.len(),
+                self.total_len
+            ));
+        }
+        if payload_digest(&self.data) != self.digest {
+            return Err(anyhow!("transfer digest mismatch"));
+        }
+        let mut item = if self.binary {
+            ClipboardItem::from_bytes(
+                self.content_type,
+                self.data,
+                self.compression.as_deref(),
+                sender_device_id.to_string(),
+                self.clock,
+            )?
+        } else {
+            let wire =
+                String::from_utf8(self.data).map_err(|_| anyhow!("transfer is not UTF-8"))?;
+            ClipboardItem::from_wire(
+                self.content_type,
+                wire,
+                self.compression.as_deref(),
+                sender_device_id.to_string(),
+                self.clock,
+            )?
+        };
+        if let Some(origin) = self.origin {
+            origin.apply(&mut item);
+        }
+        Ok(item)
+    }
+}
+
+/// Base64 SHA-256 of the streamed data.
+fn payload_digest(data: &[u8]) -> String {
+    let hash = digest::digest(&digest::SHA256, data);
+    base64::engine::general_purpose::STANDARD.encode(hash.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::compression::{compress_item, CODEC_ZSTD};
+    use crate::runtime::messages::{CONTENT_TYPE_IMAGE_PNG, CONTENT_TYPE_TEXT};
+
+    fn item(content_type: &str, data: Vec<u8>) -> ClipboardItem {
+        ClipboardItem::new(
+            content_type.to_string(),
+            data.into(),
+            "sender".to_string(),
+            Hlc::new(1_700_000_000_000, 2),
+        )
+    }
+
+    /// The receiving side of a `TransferBegin`.
+    fn incoming(begin: PeerMessage) -> IncomingTransfer {
+        let PeerMessage::TransferBegin {
+            transfer_id,
+            content_type,
+            timestamp,
+            logical,
+            total_len,
+            digest,
+            binary,
+            compression,
+            origin,
+        } = begin
+        else {
+            panic!("not a TransferBegin");
+        };
+        IncomingTransfer::new(
+            transfer_id,
+            content_type,
+            Hlc::new(timestamp, logical),
+            total_len,
+            digest,
+            binary,
+            compression,
+        )
+        .unwrap()
+        .with_origin(origin)
+    }
+
+    /// The chunks of `transfer` as the receiver pushes them: index, bytes
+    /// and whether they came as a binary frame.
+    fn chunks(transfer: &mut OutgoingTransfer) -> Vec<(u32, Vec<u8>, bool)> {
+        let mut chunks = Vec::new();
+        loop {
+            match transfer.next_message() {
+                PeerMessage::TransferChunk { index, data, .. } => {
+                    chunks.push((index, data.into_bytes(), false))
+                }
+                PeerMessage::TransferChunkBinary { index, data, .. } => {
+                    chunks.push((index, data, true))
+                }
+                PeerMessage::TransferEnd { .. } => break,
+                _ => panic!("unexpected transfer message"),
+            }
+        }
+        assert!(transfer.is_finished());
+        chunks
+    }
+
+    fn send(
+        item: &ClipboardItem,
+        compressed: Option<Compressed>,
+        binary: bool,
+        chunk_size: usize,
+    ) -> Result<ClipboardItem> {
+        let mut transfer = OutgoingTransfer::new(1, item, compressed, binary, chunk_size);
+        let mut receiver = incoming(transfer.begin_message());
+        for (index, data, binary) in chunks(&mut transfer) {
+            receiver.push(index, &data, binary)?;
+        }
+        receiver.finish("relay")
+    }
+
+    #[test]
+    fn items_reassemble_in_binary_and_json_mode() {
+        let image = item(
+            CONTENT_TYPE_IMAGE_PNG,
+            (0..10_000u32).map(|i| i as u8).collect(),
+        );
+        let text = item(CONTENT_TYPE_TEXT, b"line of text\n".repeat(1_000));
+        let compressed = compress_item(Some(CODEC_ZSTD), &text);
+        assert!(compressed.is_some());
+
+        for binary in [true, false] {
+            for (original, compressed) in
+                [(&image, None), (&text, None), (&text, compressed.clone())]
+            {
+                let received = send(original, compressed, binary, 1_000).unwrap();
+                assert_eq!(received.data, original.data);
+                assert_eq!(received.content_type, original.content_type);
+                assert_eq!(received.clock, original.clock);
+                assert_eq!(received.sender_device_id, original.sender_device_id);
+                assert_eq!(received.item_id, original.item_id);
+            }
+        }
+    }
+
+    #[test]
+    fn multi_byte_characters_are_never_split() {
+        let text = "añ中😀".repeat(50);
+        let original = item(CONTENT_TYPE_TEXT, text.clone().into_bytes());
+        // Below the four bytes a character may take, so it is raised to that.
+        let mut transfer = OutgoingTransfer::new(1, &original, None, false, 1);
+        for (_, data, _) in chunks(&mut transfer) {
+            let chunk = String::from_utf8(data).unwrap();
+            assert!(!chunk.is_empty() && !chunk.contains('\u{FFFD}'));
+        }
+
+        for chunk_size in 4..=9 {
+            let received = send(&original, None, false, chunk_size).unwrap();
+            assert_eq!(&*received.data, text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn tampered_payloads_are_rejected() {
+        let original = item(CONTENT_TYPE_IMAGE_PNG, vec![7; 4_000]);
+        let mut transfer = OutgoingTransfer::new(1, &original, None, true, 1_000);
+        let mut receiver = incoming(transfer.begin_message());
+        for (index, mut data, binary) in chunks(&mut transfer) {
+            if index == 2 {
+                data[0] ^= 1;
+            }
+            receiver.push(index, &data, binary).unwrap();
+        }
+        assert!(receiver.finish("relay").is_err());
+
+        // Intact chunks, but a digest of other content.
+        let mut transfer = OutgoingTransfer::new(1, &original, None, true, 1_000);
+        let mut begin = transfer.begin_message();
+        if let PeerMessage::TransferBegin { digest, .. } = &mut begin {
+            *digest = payload_digest(&[8; 4_000]);
+        }
+        let mut receiver = incoming(begin);
+        for (index, data, binary) in chunks(&mut transfer) {
+            receiver.push(index, &data, binary).unwrap();
+        }
+        assert!(receiver.finish("relay").is_err());
+
+        // Missing the last chunk.
+        let mut transfer = OutgoingTransfer::new(1, &original, None, true, 1_000);
+        let mut receiver = incoming(transfer.begin_message());
+        for (index, data, binary) in chunks(&mut transfer).into_iter().take(3) {
+            receiver.push(index, &data, binary).unwrap();
+        }
+        assert!(receiver.finish("relay").is_err());
+    }
+
+    #[test]
+    fn chunks_out_of_order_too_large_or_in_the_wrong_encoding_are_refused() {
+        let original = item(CONTENT_TYPE_IMAGE_PNG, vec![7; 4_000]);
+        let mut transfer = OutgoingTransfer::new(1, &original, None, true, 1_000);
+        let chunks = chunks(&mut transfer);
+
+        let mut receiver = incoming(transfer.begin_message());
+        assert!(receiver.push(1, &chunks[1].1, true).is_err());
+        receiver.push(0, &chunks[0].1, true).unwrap();
+        assert!(receiver.push(0, &chunks[0].1, true).is_err());
+        assert!(receiver.push(1, &chunks[1].1, false).is_err());
+        assert!(receiver.push(1, &[0; 3_001], true).is_err());
+    }
+
+    #[test]
+    fn transfers_over_the_size_limit_are_refused() {
+        let announce = |total_len| {
+            IncomingTransfer::new(
+                1,
+                CONTENT_TYPE_IMAGE_PNG.to_string(),
+                Hlc::default(),
+                total_len,
+                String::new(),
+                true,
+                None,
+            )
+        };
+        assert!(announce(MAX_TRANSFER_SIZE).is_ok());
+        assert!(announce(MAX_TRANSFER_SIZE + 1).is_err());
+    }
+}
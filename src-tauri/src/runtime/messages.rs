@@ -1,11 +1,38 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
 
+use crate::runtime::clock::Hlc;
+use crate::runtime::compression::{self, Compressed};
+use crate::runtime::delivery::{DeliveryReceipt, ReceiptSender};
+
 // 消息类型常量
 pub const MSG_TYPE_CLIPBOARD_UPDATE: &str = "clipboard_update";
+/// 仅发给 `target_device_id` 一台设备的剪贴板内容
+pub const MSG_TYPE_CLIPBOARD_DIRECT: &str = "clipboard_direct";
+pub const MSG_TYPE_CLIPBOARD_ACK: &str = "clipboard_ack";
+pub const MSG_TYPE_CLIPBOARD_NACK: &str = "clipboard_nack";
 
 // 内容类型常量
 pub const CONTENT_TYPE_TEXT: &str = "text/plain";
 pub const CONTENT_TYPE_IMAGE_PNG: &str = "image/png";
+/// 富文本：数据为 [`HtmlContent`] 的 JSON，同时带有 HTML 和纯文本
+pub const CONTENT_TYPE_HTML: &str = "text/html";
+
+/// 本机能写入剪贴板的内容类型，在 LAN 握手和服务器认证时声明
+pub fn supported_content_types() -> Vec<String> {
+    vec![
+        CONTENT_TYPE_TEXT.to_string(),
+        CONTENT_TYPE_HTML.to_string(),
+        CONTENT_TYPE_IMAGE_PNG.to_string(),
+    ]
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[allow(dead_code)]
@@ -25,6 +52,12 @@ pub struct AuthRequestPayload {
     pub username: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// Compression codecs we can decode, see [`crate::runtime::compression`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<String>,
+    /// Clipboard content types we can apply.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_types: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -39,7 +72,242 @@ pub struct ClipboardUpdatePayload {
     pub content_type: String,
     pub data: String,
     pub sender_device_id: String,
+    /// [`ClipboardItem::item_id`], echoed in delivery receipts.
+    #[serde(default)]
+    pub item_id: u64,
+    /// Wall-clock part of the sender's [`Hlc`] stamp.
     pub timestamp: u64,
+    /// Logical part of the sender's [`Hlc`] stamp.
+    #[serde(default)]
+    pub logical: u32,
+    /// Codec `data` was compressed with (then always base64).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    /// The one device a `clipboard_direct` message is for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_device_id: Option<String>,
+}
+
+/// Clipboard content as it moves through the runtime, from the monitor
+/// through the transports to the setter.
+///
+/// `data` holds the raw bytes (UTF-8 for text, PNG for images, JSON
+/// [`HtmlContent`] for rich text) and is shared rather than copied between
+/// subscribers. Base64 is only applied at the
+/// edges that need a string: the WebSocket server protocol and JSON LAN
+/// frames for peers without binary frame support.
+///
+/// `sender_device_id` is the device the content was copied on, and
+/// `item_id` tells its items apart; together they identify the item across
+/// the LAN mesh (see [`crate::runtime::lan::relay`]). Items are ordered by
+/// [`version`](Self::version) (see [`crate::runtime::clock`]).
+///
+/// Items are synced to every device unless [`target`](Self::target) names
+/// the one device an item was sent to on demand.
+#[derive(Debug, Clone)]
+pub struct ClipboardItem {
+    pub content_type: String,
+    pub data: Arc<[u8]>,
+    pub sender_device_id: String,
+    /// Hybrid logical clock stamp given by the sender.
+    pub clock: Hlc,
+    /// Random id, unique per sender.
+    pub item_id: u64,
+    /// How many LAN peers relayed the item on its way here.
+    pub hops: u8,
+    /// The only device the item is for; it is neither relayed nor synced
+    /// to any other device.
+    pub target: Option<String>,
+    /// The LAN peer a relayed item was received from, so it is not sent
+    /// back there. Never leaves this device.
+    pub relayed_from: Option<String>,
+    /// Where the setter reports what became of a received item: the
+    /// connection it arrived on. Never leaves this device.
+    pub receipts: Option<ReceiptSender>,
+}
+
+impl ClipboardItem {
+    /// A new item with a fresh [`item_id`](Self::item_id).
+    pub fn new(
+        content_type: String,
+        data: Arc<[u8]>,
+        sender_device_id: String,
+        clock: Hlc,
+    ) -> Self {
+        Self {
+            content_type,
+            data,
+            sender_device_id,
+            clock,
+            item_id: new_item_id(),
+            hops: 0,
+            target: None,
+            relayed_from: None,
+            receipts: None,
+        }
+    }
+
+    /// Sort key deciding which of two items is newer: the clock stamp,
+    /// then the sender's device id for identical stamps.
+    pub fn version(&self) -> (Hlc, &str) {
+        (self.clock, &self.sender_device_id)
+    }
+
+    /// The plain text of a [`CONTENT_TYPE_HTML`] item as a `text/plain`
+    /// item with the same identity and stamp, for devices that cannot
+    /// apply HTML. `None` for other content types.
+    pub fn plain_text(&self) -> Option<ClipboardItem> {
+        if self.content_type != CONTENT_TYPE_HTML {
+            return None;
+        }
+        let content = HtmlContent::decode(&self.data)
+            .ok()
+            .filter(|content| !content.text.is_empty())?;
+        Some(Self {
+            content_type: CONTENT_TYPE_TEXT.to_string(),
+            data: content.text.into_bytes().into(),
+            ..self.clone()
+        })
+    }
+
+    /// `true` for content types carried as text (UTF-8) rather than base64
+    /// on string wire formats.
+    pub fn is_text_type(content_type: &str) -> bool {
+        content_type.starts_with("text/")
+    }
+
+    /// `data` in its string wire form: text as-is, anything else base64.
+    pub fn wire_data(&self) -> String {
+        if Self::is_text_type(&self.content_type) {
+            String::from_utf8_lossy(&self.data).into_owned()
+        } else {
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        }
+    }
+
+    /// Rebuild an item from the string wire form produced by
+    /// [`wire_data`](Self::wire_data), or from base64 of a payload
+    /// compressed with `compression`.
+    pub fn from_wire(
+        content_type: String,
+        data: String,
+        compression: Option<&str>,
+        sender_device_id: String,
+        clock: Hlc,
+    ) -> Result<Self> {
+        if compression.is_none() && Self::is_text_type(&content_type) {
+            return Ok(Self::new(
+                content_type,
+                data.into_bytes().into(),
+                sender_device_id,
+                clock,
+            ));
+        }
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .with_context(|| format!("decoding base64 {} payload", content_type))?;
+        Self::from_bytes(content_type, data, compression, sender_device_id, clock)
+    }
+
+    /// Rebuild an item from its raw bytes, decompressing them first when
+    /// they were compressed with `compression`.
+    pub fn from_bytes(
+        content_type: String,
+        data: Vec<u8>,
+        compression: Option<&str>,
+        sender_device_id: String,
+        clock: Hlc,
+    ) -> Result<Self> {
+        let data = match compression {
+            Some(codec) => compression::decompress(codec, &data)
+                .with_context(|| format!("decompressing {} payload", content_type))?,
+            None => data,
+        };
+        Ok(Self::new(
+            content_type,
+            data.into(),
+            sender_device_id,
+            clock,
+        ))
+    }
+
+    /// The `clipboard_update` message sent to the WebSocket server, or
+    /// `clipboard_direct` for an item with a [`target`](Self::target),
+    /// carrying `compressed` instead of `data` when given.
+    pub fn to_update(&self, compressed: Option<&Compressed>) -> ClipboardUpdate {
+        let (data, compression) = match compressed {
+            Some(c) => (c.to_base64(), Some(c.codec.to_string())),
+            None => (self.wire_data(), None),
+        };
+        let msg_type = if self.target.is_some() {
+            MSG_TYPE_CLIPBOARD_DIRECT
+        } else {
+            MSG_TYPE_CLIPBOARD_UPDATE
+        };
+        ClipboardUpdate {
+            msg_type: msg_type.to_string(),
+            payload: ClipboardUpdatePayload {
+                content_type: self.content_type.clone(),
+                data,
+                sender_device_id: self.sender_device_id.clone(),
+                item_id: self.item_id,
+                timestamp: self.clock.wall_ms,
+                logical: self.clock.logical,
+                compression,
+                target_device_id: self.target.clone(),
+            },
+        }
+    }
+}
+
+/// Data of a [`CONTENT_TYPE_HTML`] item: the HTML copied and the plain
+/// text copied along with it, so that paste targets can pick either.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HtmlContent {
+    pub html: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl HtmlContent {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("HTML content serialisation is infallible")
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        serde_json::from_slice(data).context("decoding text/html payload")
+    }
+}
+
+/// A random item id; falls back to the clock should the system RNG fail.
+fn new_item_id() -> u64 {
+    let mut bytes = [0u8; 8];
+    match SystemRandom::new().fill(&mut bytes) {
+        Ok(()) => u64::from_le_bytes(bytes),
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64,
+    }
+}
+
+impl ClipboardBroadcastPayload {
+    /// Decode a server broadcast into a [`ClipboardItem`]. Its sender is
+    /// left empty unless the server passed on both sender and item id.
+    pub fn into_item(self) -> Result<ClipboardItem> {
+        let mut item = ClipboardItem::from_wire(
+            self.content_type,
+            self.data,
+            self.compression.as_deref(),
+            String::new(),
+            Hlc::new(self.timestamp, self.logical),
+        )?;
+        if let (Some(sender_device_id), Some(item_id)) = (self.sender_device_id, self.item_id) {
+            item.sender_device_id = sender_device_id;
+            item.item_id = item_id;
+        }
+        Ok(item)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -55,6 +323,15 @@ pub struct AuthResponsePayload {
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Compression codecs the server accepts on `clipboard_update`; servers
+    /// that predate compression send none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<String>,
+    /// Content types the server passes on to its clients. Servers that
+    /// predate this list send none and get only `text/plain` and
+    /// `image/png`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_types: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -68,7 +345,50 @@ pub struct ClipboardBroadcast {
 pub struct ClipboardBroadcastPayload {
     pub content_type: String,
     pub data: String,
+    /// The original sender, from servers that pass it through.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_device_id: Option<String>,
+    /// The original [`ClipboardItem::item_id`], likewise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<u64>,
+    /// Wall-clock part of the original sender's [`Hlc`] stamp.
     pub timestamp: u64,
+    /// Logical part of the stamp, from servers that pass it through.
+    #[serde(default)]
+    pub logical: u32,
+    /// Codec `data` was compressed with (then always base64).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+}
+
+/// `clipboard_ack` / `clipboard_nack`: a client's report on a broadcast
+/// item, sent to the server, which passes it on to the item's sender.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClipboardReceipt {
+    #[serde(rename = "type")]
+    pub msg_type: String,
+    pub payload: DeliveryReceipt,
+}
+
+impl From<DeliveryReceipt> for ClipboardReceipt {
+    fn from(payload: DeliveryReceipt) -> Self {
+        let msg_type = if payload.status.is_ack() {
+            MSG_TYPE_CLIPBOARD_ACK
+        } else {
+            MSG_TYPE_CLIPBOARD_NACK
+        };
+        Self {
+            msg_type: msg_type.to_string(),
+            payload,
+        }
+    }
+}
+
+impl ClipboardReceipt {
+    /// `true` for `clipboard_ack` / `clipboard_nack` messages.
+    pub fn is_receipt_type(&self) -> bool {
+        self.msg_type == MSG_TYPE_CLIPBOARD_ACK || self.msg_type == MSG_TYPE_CLIPBOARD_NACK
+    }
 }
 
 // 客户端发送的消息结构
@@ -1,4 +1,7 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use anyhow::{anyhow, Context, Result};
 use futures_util::{SinkExt, StreamExt};
@@ -6,7 +9,7 @@ use log::Level;
 use rustls::{ClientConfig, RootCertStore};
 use std::sync::Arc as StdArc;
 use tokio::{
-    sync::{broadcast, mpsc},
+    sync::{broadcast, mpsc, oneshot},
     task::JoinHandle,
     time::{sleep, Duration},
 };
@@ -14,19 +17,38 @@ use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::{connect_async, connect_async_tls_with_config, Connector, MaybeTlsStream};
 use tokio_util::sync::CancellationToken;
 use url::Url;
-use uuid::Uuid;
 use webpki_roots::TLS_SERVER_ROOTS;
 
 type WsStream = tokio_tungstenite::WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+type WsWriter = futures_util::stream::SplitSink<WsStream, Message>;
 
+pub mod backend;
 pub mod clipboard;
+pub mod clock;
+pub mod compression;
 pub mod config;
+pub mod delivery;
+pub mod identity;
 pub mod lan;
 pub mod messages;
+pub mod policy;
+pub mod watch;
+
+#[cfg(test)]
+mod tests;
 
-use clipboard::{start_clipboard_monitor, start_clipboard_setter};
+use backend::ClipboardBackend;
+use clipboard::{start_clipboard_monitor, start_clipboard_setter, ClipboardState};
+use compression::CompressionStats;
 use config::Config;
-use messages::{AuthResponse, ClipboardBroadcast, ClipboardBroadcastPayload, ClipboardUpdate};
+use delivery::{DeliveryReceipt, DeliveryStatus};
+use delivery::{DeliveryReport, DeliveryTracker};
+use identity::DeviceIdentity;
+use messages::{
+    AuthResponse, ClipboardBroadcast, ClipboardBroadcastPayload, ClipboardItem, ClipboardReceipt,
+    CONTENT_TYPE_HTML,
+};
+use policy::{SyncDirection, SyncPolicy};
 
 #[derive(Debug, Clone)]
 pub enum ConnectionStateEvent {
@@ -68,11 +90,24 @@ pub enum RuntimeEvent {
     /// JSON-serialised list of discovered LAN peers (emitted by the
     /// discovery listener whenever the peer map changes).
     LanPeersChanged(String),
+    /// JSON-serialised list of PIN-paired LAN peers (emitted when LAN mode
+    /// starts and after every successful pairing).
+    LanTrustChanged(String),
+    /// A clipboard payload was compressed before sending.
+    Compression(CompressionStats),
+    /// A device reported what became of an item copied here.
+    Delivery(DeliveryReport),
+    /// JSON-serialised link statistics of the LAN peers (see
+    /// [`lan::registry`]).
+    LanLinkStats(String),
 }
 
 #[derive(Debug, Clone)]
 pub struct StartOptions {
     pub config: config::Config,
+    /// Application data directory (where `config.json` lives); holds the
+    /// device identity and the LAN trust store.
+    pub data_dir: PathBuf,
 }
 
 enum RuntimeCommand {
@@ -80,6 +115,37 @@ enum RuntimeCommand {
     Reload(StartOptions),
     Pause,
     Resume,
+    LanBeginPairing {
+        reply: oneshot::Sender<Result<String>>,
+    },
+    LanPairPeer {
+        device_id: String,
+        pin: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    LanApprovePeer {
+        device_id: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    LanRevokePeer {
+        device_id: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    LanBlockPeer {
+        device_id: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    LanListDevices {
+        reply: oneshot::Sender<Result<String>>,
+    },
+    LanLinkStats {
+        reply: oneshot::Sender<Result<String>>,
+    },
+    SendClipboardTo {
+        device_id: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    SetSyncDirection(SyncDirection),
 
     #[allow(dead_code)]
     Shutdown,
@@ -119,6 +185,102 @@ impl RuntimeHandle {
             .context("Failed to send reload command")
     }
 
+    /// Generate a pairing PIN to display so another LAN device can pair
+    /// with us.
+    pub async fn lan_begin_pairing(&self) -> Result<String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanBeginPairing { reply })
+            .await
+            .context("Failed to send pairing command")?;
+        rx.await.context("Runtime dropped pairing command")?
+    }
+
+    /// Pair with the connected LAN peer `device_id` using the PIN shown on
+    /// that device.
+    pub async fn lan_pair_peer(&self, device_id: String, pin: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanPairPeer {
+                device_id,
+                pin,
+                reply,
+            })
+            .await
+            .context("Failed to send pairing command")?;
+        rx.await.context("Runtime dropped pairing command")?
+    }
+
+    /// Trust the connected, pending LAN peer `device_id` without a PIN.
+    pub async fn lan_approve_peer(&self, device_id: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanApprovePeer { device_id, reply })
+            .await
+            .context("Failed to send approve command")?;
+        rx.await.context("Runtime dropped approve command")?
+    }
+
+    /// Remove LAN peer `device_id` from the trusted or blocked list.
+    pub async fn lan_revoke_peer(&self, device_id: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanRevokePeer { device_id, reply })
+            .await
+            .context("Failed to send revoke command")?;
+        rx.await.context("Runtime dropped revoke command")?
+    }
+
+    /// Block LAN peer `device_id` and drop any session with it.
+    pub async fn lan_block_peer(&self, device_id: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanBlockPeer { device_id, reply })
+            .await
+            .context("Failed to send block command")?;
+        rx.await.context("Runtime dropped block command")?
+    }
+
+    /// JSON snapshot of discovered, trusted and blocked LAN peers.
+    pub async fn lan_list_devices(&self) -> Result<String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanListDevices { reply })
+            .await
+            .context("Failed to send device list command")?;
+        rx.await.context("Runtime dropped device list command")?
+    }
+
+    /// JSON snapshot of the link statistics of LAN peers.
+    pub async fn lan_link_stats(&self) -> Result<String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::LanLinkStats { reply })
+            .await
+            .context("Failed to send link stats command")?;
+        rx.await.context("Runtime dropped link stats command")?
+    }
+
+    /// Send the current clipboard content to device `device_id` alone: a
+    /// connected LAN peer, or a device behind the server.
+    pub async fn send_clipboard_to(&self, device_id: String) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(RuntimeCommand::SendClipboardTo { device_id, reply })
+            .await
+            .context("Failed to send clipboard send command")?;
+        rx.await.context("Runtime dropped clipboard send command")?
+    }
+
+    /// Switch which way clipboard content flows with all devices; applies
+    /// to live connections without a restart.
+    pub async fn set_sync_direction(&self, direction: SyncDirection) -> Result<()> {
+        self.command_tx
+            .send(RuntimeCommand::SetSyncDirection(direction))
+            .await
+            .context("Failed to send sync direction command")
+    }
+
     #[allow(dead_code)]
     pub async fn shutdown(&self) -> Result<()> {
         self.command_tx
@@ -128,13 +290,16 @@ impl RuntimeHandle {
     }
 }
 
+/// Start the runtime worker on `runtime`, syncing the clipboard behind
+/// `backend`.
 pub fn spawn_runtime(
     runtime: &Arc<tokio::runtime::Runtime>,
+    backend: Arc<dyn ClipboardBackend>,
 ) -> (RuntimeHandle, mpsc::Receiver<RuntimeEvent>) {
     let (command_tx, command_rx) = mpsc::channel(32);
     let (event_tx, event_rx) = mpsc::channel(512);
 
-    let mut worker = RuntimeWorker::new(event_tx);
+    let mut worker = RuntimeWorker::new(event_tx, backend);
     runtime.spawn(async move {
         worker.run(command_rx).await;
     });
@@ -147,12 +312,19 @@ struct RuntimeWorker {
     active: Option<ActiveTasks>,
     last_options: Option<StartOptions>,
     paused: bool,
+    /// Sync directions, shared with the running tasks.
+    policy: SyncPolicy,
+    backend: Arc<dyn ClipboardBackend>,
 }
 
 enum ActiveTasks {
     /// WebSocket server mode — three individual task handles.
     Server {
         cancel: CancellationToken,
+        /// Outbound items of the connection; carries items addressed to
+        /// one device too.
+        tx_out: broadcast::Sender<ClipboardItem>,
+        clipboard: ClipboardState,
         monitor_handle: JoinHandle<()>,
         setter_handle: JoinHandle<()>,
         connection_handle: JoinHandle<()>,
@@ -162,12 +334,14 @@ enum ActiveTasks {
 }
 
 impl RuntimeWorker {
-    fn new(events: mpsc::Sender<RuntimeEvent>) -> Self {
+    fn new(events: mpsc::Sender<RuntimeEvent>, backend: Arc<dyn ClipboardBackend>) -> Self {
         Self {
             events,
             active: None,
             last_options: None,
             paused: true,
+            policy: SyncPolicy::default(),
+            backend,
         }
     }
 
@@ -209,6 +383,92 @@ impl RuntimeWorker {
                         self.emit_error(format!("重新加载失败: {}", err)).await;
                     }
                 }
+                RuntimeCommand::LanBeginPairing { reply } => {
+                    let result = self
+                        .lan_control()
+                        .and_then(|control| control.begin_pairing());
+                    let _ = reply.send(result);
+                }
+                RuntimeCommand::LanPairPeer {
+                    device_id,
+                    pin,
+                    reply,
+                } => match self.lan_control() {
+                    // Pairing waits on the remote; don't block the command
+                    // loop while it does.
+                    Ok(control) => {
+                        tokio::spawn(async move {
+                            let _ = reply.send(control.pair_with_peer(&device_id, &pin).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = reply.send(Err(err));
+                    }
+                },
+                RuntimeCommand::LanApprovePeer { device_id, reply } => match self.lan_control() {
+                    Ok(control) => {
+                        tokio::spawn(async move {
+                            let _ = reply.send(control.approve_peer(&device_id).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = reply.send(Err(err));
+                    }
+                },
+                RuntimeCommand::LanRevokePeer { device_id, reply } => match self.lan_control() {
+                    Ok(control) => {
+                        tokio::spawn(async move {
+                            let _ = reply.send(control.revoke_peer(&device_id).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = reply.send(Err(err));
+                    }
+                },
+                RuntimeCommand::LanBlockPeer { device_id, reply } => match self.lan_control() {
+                    Ok(control) => {
+                        tokio::spawn(async move {
+                            let _ = reply.send(control.block_peer(&device_id).await);
+                        });
+                    }
+                    Err(err) => {
+                        let _ = reply.send(Err(err));
+                    }
+                },
+                RuntimeCommand::LanListDevices { reply } => {
+                    let result = self.lan_control().map(|control| control.devices_json());
+                    let _ = reply.send(result);
+                }
+                RuntimeCommand::LanLinkStats { reply } => {
+                    let result = self.lan_control().map(|control| control.link_stats_json());
+                    let _ = reply.send(result);
+                }
+                RuntimeCommand::SendClipboardTo { device_id, reply } => match &self.active {
+                    Some(ActiveTasks::Lan(tasks)) => {
+                        let control = tasks.control.clone();
+                        tokio::spawn(async move {
+                            let _ = reply.send(control.send_clipboard_to(&device_id).await);
+                        });
+                    }
+                    Some(ActiveTasks::Server {
+                        tx_out, clipboard, ..
+                    }) => {
+                        let _ = reply.send(send_to_device(tx_out, clipboard, &device_id));
+                    }
+                    None => {
+                        let _ = reply.send(Err(anyhow!("同步未运行")));
+                    }
+                },
+                RuntimeCommand::SetSyncDirection(direction) => {
+                    self.policy.set_global(direction);
+                    // Keep it across pause/resume, which restart from the
+                    // last options.
+                    if let Some(options) = self.last_options.as_mut() {
+                        options.config.sync_direction = direction;
+                    }
+                    self.emit_log(Level::Info, format!("同步方向: {:?}", direction))
+                        .await;
+                }
                 RuntimeCommand::Shutdown => {
                     self.stop_tasks(true).await;
                     break;
@@ -217,6 +477,15 @@ impl RuntimeWorker {
         }
     }
 
+    /// Control handle of the running LAN mode, or an error when LAN mode is
+    /// not active.
+    fn lan_control(&self) -> Result<lan::LanControl> {
+        match &self.active {
+            Some(ActiveTasks::Lan(tasks)) => Ok(tasks.control.clone()),
+            _ => Err(anyhow!("LAN mode is not running")),
+        }
+    }
+
     async fn start_tasks(&mut self, options: StartOptions) -> Result<()> {
         if self.active.is_some() {
             return Ok(());
@@ -224,6 +493,9 @@ impl RuntimeWorker {
         self.paused = false;
 
         let cfg = options.config.clone();
+        self.policy.apply(&cfg);
+        let identity = DeviceIdentity::load_or_generate(&options.data_dir)
+            .context("failed to load device identity")?;
 
         if cfg.is_lan_mode() {
             // ── LAN (serverless) mode ────────────────────────────────
@@ -233,7 +505,10 @@ impl RuntimeWorker {
             let cancel = CancellationToken::new();
             let lan_tasks = lan::start_lan_mode(
                 &cfg,
-                Some(cfg.lan_device_name.clone()),
+                &options.data_dir,
+                identity,
+                self.policy.clone(),
+                self.backend.clone(),
                 self.events.clone(),
                 cancel.clone(),
             )
@@ -262,22 +537,25 @@ impl RuntimeWorker {
             self.emit_connection(ConnectionStateEvent::Connecting).await;
 
             let disable_flag = Arc::new(AtomicBool::new(false));
-            let device_id = Uuid::new_v4().to_string();
-            let (tx_out, _) = broadcast::channel::<ClipboardUpdate>(100);
-            let (tx_in, rx_in) = mpsc::channel::<ClipboardBroadcastPayload>(100);
+            let device_id = identity.device_id().to_string();
+            let clipboard = ClipboardState::new(device_id.clone(), self.policy.clone());
+            let (tx_out, _) = broadcast::channel::<ClipboardItem>(100);
+            let (tx_in, rx_in) = mpsc::channel::<ClipboardItem>(100);
             let cancel = CancellationToken::new();
 
             let monitor_events = self.events.clone();
             let monitor_cancel = cancel.clone();
             let monitor_disable = disable_flag.clone();
-            let monitor_device = device_id.clone();
+            let monitor_state = clipboard.clone();
+            let monitor_backend = self.backend.clone();
             let monitor_cfg = cfg.max_image_kb;
             let tx_out_for_monitor = tx_out.clone();
             let monitor_handle = tokio::spawn(async move {
                 start_clipboard_monitor(
                     tx_out_for_monitor,
                     monitor_disable,
-                    monitor_device,
+                    monitor_state,
+                    monitor_backend,
                     monitor_cfg,
                     monitor_events,
                     monitor_cancel,
@@ -288,20 +566,33 @@ impl RuntimeWorker {
             let setter_events = self.events.clone();
             let setter_cancel = cancel.clone();
             let setter_disable = disable_flag.clone();
+            let setter_state = clipboard.clone();
+            let setter_backend = self.backend.clone();
             let setter_handle = tokio::spawn(async move {
-                start_clipboard_setter(rx_in, setter_disable, setter_events, setter_cancel).await;
+                start_clipboard_setter(
+                    rx_in,
+                    setter_state,
+                    setter_backend,
+                    setter_disable,
+                    setter_events,
+                    setter_cancel,
+                )
+                .await;
             });
 
             let connection_events = self.events.clone();
             let connection_cancel = cancel.clone();
             let cfg_clone = cfg.clone();
             let server_url_clone = server_url.clone();
+            let tx_out_for_connection = tx_out.clone();
+            let clipboard_for_connection = clipboard.clone();
             let connection_handle = tokio::spawn(async move {
                 run_connection_loop(
                     cfg_clone,
                     server_url_clone,
-                    tx_out,
+                    tx_out_for_connection,
                     tx_in,
+                    clipboard_for_connection,
                     connection_events,
                     connection_cancel,
                 )
@@ -310,6 +601,8 @@ impl RuntimeWorker {
 
             self.active = Some(ActiveTasks::Server {
                 cancel,
+                tx_out,
+                clipboard,
                 monitor_handle,
                 setter_handle,
                 connection_handle,
@@ -327,6 +620,7 @@ impl RuntimeWorker {
                     monitor_handle,
                     setter_handle,
                     connection_handle,
+                    ..
                 } => {
                     cancel.cancel();
                     if hard {
@@ -374,8 +668,9 @@ impl RuntimeWorker {
 async fn run_connection_loop(
     cfg: Config,
     server_url: Url,
-    tx_out: broadcast::Sender<ClipboardUpdate>,
-    tx_in: mpsc::Sender<ClipboardBroadcastPayload>,
+    tx_out: broadcast::Sender<ClipboardItem>,
+    tx_in: mpsc::Sender<ClipboardItem>,
+    clipboard: ClipboardState,
     events: mpsc::Sender<RuntimeEvent>,
     cancel: CancellationToken,
 ) {
@@ -434,24 +729,33 @@ async fn run_connection_loop(
                     .await;
                 let _ = events.send(RuntimeEvent::Status("已连接".into())).await;
 
-                if let Err(err) = authenticate_stream(&cfg, &mut ws_stream, &events).await {
-                    let _ = events
-                        .send(RuntimeEvent::Error(format!("认证失败: {}", err)))
-                        .await;
-                    let _ = events
-                        .send(RuntimeEvent::Connection(ConnectionStateEvent::Disconnected))
-                        .await;
-                    if cancel.is_cancelled() {
-                        break;
+                let features = match authenticate_stream(&cfg, &mut ws_stream, &events).await {
+                    Ok(features) => features,
+                    Err(err) => {
+                        let _ = events
+                            .send(RuntimeEvent::Error(format!("认证失败: {}", err)))
+                            .await;
+                        let _ = events
+                            .send(RuntimeEvent::Connection(ConnectionStateEvent::Disconnected))
+                            .await;
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                        sleep(Duration::from_secs(3)).await;
+                        continue;
                     }
-                    sleep(Duration::from_secs(3)).await;
-                    continue;
-                }
+                };
 
                 let (mut write, mut read) = ws_stream.split();
 
                 let mut rx_updates = tx_out.subscribe();
                 let tx_in_clone = tx_in.clone();
+                // 连接建立后先同步本机最新的剪贴板内容；来自其他设备的无需回传
+                let mut initial = clipboard
+                    .latest()
+                    .filter(|item| clipboard.is_own(item) && clipboard.policy.sends_item(item));
+                // 剪贴板写入任务对收到内容的处理结果，经此回报给服务器
+                let (receipt_tx, mut receipt_rx) = mpsc::channel(16);
 
                 loop {
                     tokio::select! {
@@ -459,14 +763,28 @@ async fn run_connection_loop(
                             let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Debug, String::from("连接任务取消")))).await;
                             break;
                         }
+                        _ = std::future::ready(()), if initial.is_some() => {
+                            let Some(update) = initial.take() else {
+                                continue;
+                            };
+                            if send_update(&mut write, &features, &update, &clipboard.deliveries, &events).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(receipt) = receipt_rx.recv() => {
+                            if let Ok(text) = serde_json::to_string(&ClipboardReceipt::from(receipt)) {
+                                if let Err(err) = write.send(Message::Text(text.into())).await {
+                                    let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Error, format!("发送失败: {}", err)))).await;
+                                    break;
+                                }
+                            }
+                        }
                         outbound = rx_updates.recv() => {
                             match outbound {
+                                Ok(update) if !clipboard.policy.sends_item(&update) => {}
                                 Ok(update) => {
-                                    if let Ok(text) = serde_json::to_string(&update) {
-                                        if let Err(err) = write.send(Message::Text(text.into())).await {
-                                            let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Error, format!("发送失败: {}", err)))).await;
-                                            break;
-                                        }
+                                    if send_update(&mut write, &features, &update, &clipboard.deliveries, &events).await.is_err() {
+                                        break;
                                     }
                                 }
                                 Err(_) => break,
@@ -475,14 +793,38 @@ async fn run_connection_loop(
                         incoming = read.next() => {
                             match incoming {
                                 Some(Ok(Message::Text(text))) => {
-                                    let handled = if let Ok(broadcast) = serde_json::from_str::<ClipboardBroadcast>(&text) {
-                                        let _ = tx_in_clone.send(broadcast.payload).await;
-                                        true
-                                    } else if let Ok(payload) = serde_json::from_str::<ClipboardBroadcastPayload>(&text) {
-                                        let _ = tx_in_clone.send(payload).await;
-                                        true
+                                    if let Some(receipt) = serde_json::from_str::<ClipboardReceipt>(&text).ok().filter(ClipboardReceipt::is_receipt_type) {
+                                        if let Some(report) = clipboard.deliveries.report(&receipt.payload) {
+                                            let _ = events.send(RuntimeEvent::Delivery(report)).await;
+                                        }
+                                        continue;
+                                    }
+                                    let payload = if let Ok(broadcast) = serde_json::from_str::<ClipboardBroadcast>(&text) {
+                                        Some(broadcast.payload)
                                     } else {
-                                        false
+                                        serde_json::from_str::<ClipboardBroadcastPayload>(&text).ok()
+                                    };
+                                    let handled = match payload.map(ClipboardBroadcastPayload::into_item) {
+                                        Some(Ok(item)) if !clipboard.policy.receives_item(&item) => {
+                                            // 按同步方向不接收该设备的内容
+                                            if !item.sender_device_id.is_empty() {
+                                                let _ = receipt_tx.try_send(DeliveryReceipt::new(&item, clipboard.device_id(), DeliveryStatus::Rejected));
+                                            }
+                                            let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Debug, format!("按同步方向忽略来自 {} 的剪贴板", item.sender_device_id)))).await;
+                                            true
+                                        }
+                                        Some(Ok(mut item)) => {
+                                            if !item.sender_device_id.is_empty() {
+                                                item.receipts = Some(receipt_tx.clone());
+                                            }
+                                            let _ = tx_in_clone.send(item).await;
+                                            true
+                                        }
+                                        Some(Err(err)) => {
+                                            let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Warn, format!("广播内容解码失败: {:#}", err)))).await;
+                                            true
+                                        }
+                                        None => false,
                                     };
                                     if !handled {
                                         let _ = events.send(RuntimeEvent::Log(RuntimeLogEvent::new(Level::Warn, format!("未识别的广播: {}", text)))).await;
@@ -557,19 +899,99 @@ async fn run_connection_loop(
     }
 }
 
+/// 把当前剪贴板内容作为仅发给 `device_id` 的条目交给服务器连接，由服务器
+/// 以 `clipboard_direct` 消息转发。未连接服务器时返回错误。
+fn send_to_device(
+    tx_out: &broadcast::Sender<ClipboardItem>,
+    clipboard: &ClipboardState,
+    device_id: &str,
+) -> Result<()> {
+    if !clipboard.policy.sends_to(device_id) {
+        return Err(anyhow!("同步方向不允许向 {} 发送", device_id));
+    }
+    let item = clipboard
+        .addressed_to(device_id)
+        .ok_or_else(|| anyhow!("剪贴板为空"))?;
+    tx_out
+        .send(item)
+        .map(|_| ())
+        .map_err(|_| anyhow!("未连接服务器"))
+}
+
+/// 将剪贴板内容发送到服务器，按认证时协商的 `features` 压缩，并在
+/// `deliveries` 中登记以统计回执。服务器不支持富文本时只发送其纯文本。
+/// 发送失败时记录日志并返回错误。
+async fn send_update(
+    write: &mut WsWriter,
+    features: &ServerFeatures,
+    update: &ClipboardItem,
+    deliveries: &DeliveryTracker,
+    events: &mpsc::Sender<RuntimeEvent>,
+) -> Result<()> {
+    let fallback;
+    let update = if update.content_type == CONTENT_TYPE_HTML && !features.html {
+        let Some(plain) = update.plain_text() else {
+            return Ok(());
+        };
+        fallback = plain;
+        &fallback
+    } else {
+        update
+    };
+    let compressed = compression::compress_item(features.codec, update);
+    let Ok(text) = serde_json::to_string(&update.to_update(compressed.as_ref())) else {
+        return Ok(());
+    };
+    if let Err(err) = write.send(Message::Text(text.into())).await {
+        let _ = events
+            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                Level::Error,
+                format!("发送失败: {}", err),
+            )))
+            .await;
+        return Err(err.into());
+    }
+    deliveries.sent(update, None);
+    if let Some(c) = &compressed {
+        let _ = events
+            .send(RuntimeEvent::Compression(CompressionStats::new(
+                "server", update, c,
+            )))
+            .await;
+    }
+    Ok(())
+}
+
+/// 认证时与服务器协商出的功能。
+struct ServerFeatures {
+    /// 压缩算法（服务器不支持时为 `None`）
+    codec: Option<&'static str>,
+    /// 服务器会转发富文本；否则只发送其纯文本
+    html: bool,
+}
+
+/// 认证并返回与服务器协商出的功能。
 async fn authenticate_stream(
     cfg: &Config,
     stream: &mut WsStream,
     events: &mpsc::Sender<RuntimeEvent>,
-) -> Result<()> {
+) -> Result<ServerFeatures> {
     let auth_json = if let Some(token) = cfg.token.clone() {
-        serde_json::json!({ "token": token })
+        serde_json::json!({
+            "token": token,
+            "compression": compression::supported_codecs(),
+            "content_types": messages::supported_content_types(),
+        })
     } else {
         serde_json::json!({
             "username": cfg.username.clone().ok_or_else(|| anyhow!("缺少用户名"))?,
             "password": cfg.password.clone().ok_or_else(|| anyhow!("缺少密码"))?,
+            "compression": compression::supported_codecs(),
+            "content_types": messages::supported_content_types(),
         })
     };
+    let codec;
+    let content_types: Vec<String>;
 
     let auth_text = serde_json::to_string(&auth_json)?;
     stream.send(Message::Text(auth_text.into())).await?;
@@ -592,6 +1014,8 @@ async fn authenticate_stream(
                     if !resp.payload.success {
                         return Err(anyhow!("认证失败: {}", resp.payload.message));
                     }
+                    codec = compression::negotiate(&resp.payload.compression);
+                    content_types = resp.payload.content_types;
                     events
                         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                             Level::Info,
@@ -612,6 +1036,15 @@ async fn authenticate_stream(
                     if !success {
                         return Err(anyhow!("认证失败: {}", message));
                     }
+                    let offered: Vec<String> = value
+                        .get("compression")
+                        .and_then(|c| serde_json::from_value(c.clone()).ok())
+                        .unwrap_or_default();
+                    codec = compression::negotiate(&offered);
+                    content_types = value
+                        .get("content_types")
+                        .and_then(|c| serde_json::from_value(c.clone()).ok())
+                        .unwrap_or_default();
                     events
                         .send(RuntimeEvent::Log(RuntimeLogEvent::new(
                             Level::Info,
@@ -638,5 +1071,24 @@ async fn authenticate_stream(
         return Err(anyhow!("服务器未返回认证结果"));
     }
 
-    Ok(())
+    if let Some(codec) = codec {
+        events
+            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                Level::Debug,
+                format!("服务器支持 {} 压缩", codec),
+            )))
+            .await
+            .ok();
+    }
+    let html = content_types.iter().any(|c| c == CONTENT_TYPE_HTML);
+    if html {
+        events
+            .send(RuntimeEvent::Log(RuntimeLogEvent::new(
+                Level::Debug,
+                "服务器支持富文本",
+            )))
+            .await
+            .ok();
+    }
+    Ok(ServerFeatures { codec, html })
 }
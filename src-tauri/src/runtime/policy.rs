@@ -0,0 +1,120 @@
+//! Which way clipboard content may flow.
+//!
+//! A [`SyncDirection`] applies to the whole device (`sync_direction` in the
+//! config, switched from the main window next to pause) and, per remote
+//! device, to single peers (`peer_sync_directions`). An item crosses a link
+//! only when both allow it: a kiosk set to [`SyncDirection::SendOnly`]
+//! receives our clipboard but never pushes its own onto ours.
+//!
+//! LAN sessions check the policy of the peer at the other end and of the
+//! device an item was copied on. Through the server, items are filtered by
+//! `sender_device_id` on the way in; on the way out the server fans an
+//! item out to every device, so per-device restrictions only hold back
+//! items sent to one device on demand.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use super::config::Config;
+use super::messages::ClipboardItem;
+
+/// Which way content flows between this device and another one (or all
+/// of them).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    /// Send and receive.
+    #[default]
+    Both,
+    /// Send our clipboard, never apply theirs.
+    SendOnly,
+    /// Apply their clipboard, never send ours.
+    ReceiveOnly,
+    /// Neither send nor receive.
+    Muted,
+}
+
+impl SyncDirection {
+    pub fn sends(self) -> bool {
+        matches!(self, SyncDirection::Both | SyncDirection::SendOnly)
+    }
+
+    pub fn receives(self) -> bool {
+        matches!(self, SyncDirection::Both | SyncDirection::ReceiveOnly)
+    }
+}
+
+/// The global direction and the per-device ones, shared by the runtime
+/// and its transports so a change applies to live connections.
+#[derive(Debug, Clone, Default)]
+pub struct SyncPolicy {
+    inner: Arc<RwLock<PolicyState>>,
+}
+
+#[derive(Debug, Default)]
+struct PolicyState {
+    global: SyncDirection,
+    peers: BTreeMap<String, SyncDirection>,
+}
+
+impl SyncPolicy {
+    /// Take both the global and the per-device directions from `cfg`.
+    pub fn apply(&self, cfg: &Config) {
+        let mut state = self.inner.write();
+        state.global = cfg.sync_direction;
+        state.peers = cfg.peer_sync_directions.clone();
+    }
+
+    pub fn set_global(&self, direction: SyncDirection) {
+        self.inner.write().global = direction;
+    }
+
+    /// Whether anything may be sent at all.
+    pub fn sends(&self) -> bool {
+        self.inner.read().global.sends()
+    }
+
+    /// Whether anything may be applied at all.
+    pub fn receives(&self) -> bool {
+        self.inner.read().global.receives()
+    }
+
+    /// Whether items may be sent to `device_id`.
+    pub fn sends_to(&self, device_id: &str) -> bool {
+        let state = self.inner.read();
+        state.global.sends() && state.direction(device_id).sends()
+    }
+
+    /// Whether items from `device_id` may be applied.
+    pub fn receives_from(&self, device_id: &str) -> bool {
+        let state = self.inner.read();
+        state.global.receives() && state.direction(device_id).receives()
+    }
+
+    /// Whether `item` may go out over a link that reaches every device:
+    /// only to its [`target`](ClipboardItem::target), if it has one.
+    pub fn sends_item(&self, item: &ClipboardItem) -> bool {
+        match &item.target {
+            Some(target) => self.sends_to(target),
+            None => self.sends(),
+        }
+    }
+
+    /// Whether `item` may be applied, judged by the device it was copied
+    /// on when that is known.
+    pub fn receives_item(&self, item: &ClipboardItem) -> bool {
+        if item.sender_device_id.is_empty() {
+            self.receives()
+        } else {
+            self.receives_from(&item.sender_device_id)
+        }
+    }
+}
+
+impl PolicyState {
+    fn direction(&self, device_id: &str) -> SyncDirection {
+        self.peers.get(device_id).copied().unwrap_or_default()
+    }
+}
@@ -0,0 +1,220 @@
+//! The whole sync loop between two runtimes in one process: each one runs
+//! LAN mode on an in-memory clipboard, and they reach each other over
+//! loopback.
+
+use std::{
+    net::{TcpListener, UdpSocket},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use tokio::time::{sleep, Duration, Instant};
+
+use super::backend::{ClipboardBackend, ClipboardContent, MemoryBackend};
+use super::config::Config;
+use super::identity::DeviceIdentity;
+use super::{spawn_runtime, RuntimeHandle, StartOptions};
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// A name no other test, in this process or another one, uses.
+fn unique_name(prefix: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}-{}",
+        prefix,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+struct Device {
+    handle: RuntimeHandle,
+    clipboard: MemoryBackend,
+    device_id: String,
+    data_dir: PathBuf,
+}
+
+impl Device {
+    fn spawn(runtime: &Arc<tokio::runtime::Runtime>) -> Self {
+        let data_dir = std::env::temp_dir().join(unique_name("rustsynccv-test"));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let device_id = DeviceIdentity::load_or_generate(&data_dir)
+            .unwrap()
+            .device_id()
+            .to_string();
+
+        let clipboard = MemoryBackend::new();
+        let (handle, mut events) = spawn_runtime(runtime, Arc::new(clipboard.clone()));
+        runtime.spawn(async move { while events.recv().await.is_some() {} });
+
+        Self {
+            handle,
+            clipboard,
+            device_id,
+            data_dir,
+        }
+    }
+
+    async fn start(&self, config: Config) {
+        let options = StartOptions {
+            config,
+            data_dir: self.data_dir.clone(),
+        };
+        self.handle.start(options).await.unwrap();
+    }
+
+    fn copy(&self, content: &ClipboardContent) {
+        self.clipboard.write(content.clone()).unwrap();
+    }
+
+    /// Wait until the clipboard holds `expected`.
+    async fn receive(&self, expected: &ClipboardContent) {
+        let deadline = Instant::now() + SYNC_TIMEOUT;
+        while self.clipboard.read().unwrap().as_ref() != Some(expected) {
+            assert!(
+                Instant::now() < deadline,
+                "{} never received {:?}",
+                self.device_id,
+                expected
+            );
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.data_dir);
+    }
+}
+
+fn free_tcp_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+fn free_udp_port() -> u16 {
+    UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// LAN mode in a group of its own, with discovery kept on loopback.
+fn lan_config(
+    group: &str,
+    discovery_port: u16,
+    tcp_port: u16,
+    static_peers: Vec<String>,
+) -> Config {
+    Config {
+        connection_mode: "lan".to_string(),
+        lan_group_name: group.to_string(),
+        lan_group_secret: "secret".to_string(),
+        lan_interfaces: vec!["127.0.0.0/8".to_string()],
+        lan_discovery_port: discovery_port,
+        lan_tcp_port: tcp_port,
+        lan_static_peers: static_peers,
+        ..Config::default()
+    }
+}
+
+/// Start two devices, the second dialing the first.
+async fn connected(a: &Device, b: &Device) {
+    let group = unique_name("test");
+    let discovery_port = free_udp_port();
+    let a_port = free_tcp_port();
+    a.start(lan_config(&group, discovery_port, a_port, Vec::new()))
+        .await;
+    b.start(lan_config(
+        &group,
+        discovery_port,
+        0,
+        vec![format!("127.0.0.1:{}", a_port)],
+    ))
+    .await;
+}
+
+/// Pair `b` with `a` once `b` has a session with it.
+async fn pair(a: &Device, b: &Device) {
+    let pin = a.handle.lan_begin_pairing().await.unwrap();
+    let deadline = Instant::now() + SYNC_TIMEOUT;
+    // Pairing needs the session, which comes up in the background.
+    while let Err(err) = b
+        .handle
+        .lan_pair_peer(a.device_id.clone(), pin.clone())
+        .await
+    {
+        assert!(Instant::now() < deadline, "pairing failed: {:#}", err);
+        sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Start two devices, the second dialing the first, and pair them.
+async fn paired(a: &Device, b: &Device) {
+    connected(a, b).await;
+    pair(a, b).await;
+}
+
+#[test]
+fn content_copied_before_pairing_arrives_once_paired() {
+    let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+    let a = Device::spawn(&runtime);
+    let b = Device::spawn(&runtime);
+
+    runtime.block_on(async {
+        connected(&a, &b).await;
+        let text = ClipboardContent::Text("copied before pairing".to_string());
+        a.copy(&text);
+        // Let the monitor take it in while the devices are still unpaired.
+        sleep(Duration::from_secs(1)).await;
+
+        pair(&a, &b).await;
+        b.receive(&text).await;
+
+        a.handle.shutdown().await.unwrap();
+        b.handle.shutdown().await.unwrap();
+    });
+}
+
+#[test]
+fn paired_devices_sync_text_rich_text_and_images_both_ways() {
+    let runtime = Arc::new(tokio::runtime::Runtime::new().unwrap());
+    let a = Device::spawn(&runtime);
+    let b = Device::spawn(&runtime);
+
+    runtime.block_on(async {
+        paired(&a, &b).await;
+
+        let text = ClipboardContent::Text("copied on a".to_string());
+        a.copy(&text);
+        b.receive(&text).await;
+
+        let reply = ClipboardContent::Text("copied on b".to_string());
+        b.copy(&reply);
+        a.receive(&reply).await;
+
+        let rich = ClipboardContent::Html {
+            html: "<table><tr><td>a</td><td>b</td></tr></table>".to_string(),
+            text: "a\tb".to_string(),
+        };
+        a.copy(&rich);
+        b.receive(&rich).await;
+
+        let pixels = (0..16u8).collect::<Vec<_>>();
+        let image = ClipboardContent::Image(pixels, 2, 2);
+        a.copy(&image);
+        b.receive(&image).await;
+
+        a.handle.shutdown().await.unwrap();
+        b.handle.shutdown().await.unwrap();
+    });
+}
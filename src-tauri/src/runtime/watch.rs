@@ -0,0 +1,146 @@
+//! Clipboard change notification.
+//!
+//! Reading the clipboard means copying its whole content and, for images,
+//! hashing every pixel, so the monitor should only read when something has
+//! changed. A [`ChangeSource`] tells it when that may be the case: on X11
+//! the XFixes extension reports every new owner of the `CLIPBOARD`
+//! selection. Other platforms plug in by implementing the trait and
+//! returning their source from [`platform_source`]. Where there is none,
+//! or once it fails, the monitor falls back to polling.
+
+use std::thread;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+/// Signals that the clipboard may have changed. A source may report a
+/// change that turns out to be none (the same text copied again, or our
+/// own write); the monitor compares the content before sending it.
+pub trait ChangeSource: Send + 'static {
+    /// Short name for the log.
+    fn name(&self) -> &'static str;
+
+    /// Block until the clipboard may have changed.
+    fn wait(&mut self) -> Result<()>;
+}
+
+/// The change source of this platform, if it has one and it can be set up
+/// here (an X11 session on Linux).
+pub fn platform_source() -> Result<Box<dyn ChangeSource>> {
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    {
+        Ok(Box::new(x11::XFixesSource::connect()?))
+    }
+    #[cfg(not(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    )))]
+    {
+        Err(anyhow::anyhow!(
+            "no clipboard change source on this platform"
+        ))
+    }
+}
+
+/// Run `source` on its own thread. Each change arrives as `Ok(())`;
+/// changes that come in before the previous one was taken are merged. A
+/// failure arrives as `Err` and closes the channel.
+///
+/// [`ChangeSource::wait`] cannot be interrupted, so after the receiver is
+/// dropped the thread lingers until the next change (or failure) and then
+/// exits.
+pub fn spawn(mut source: Box<dyn ChangeSource>) -> mpsc::Receiver<Result<()>> {
+    let (tx, rx) = mpsc::channel(1);
+    // If the thread cannot start, `tx` is dropped with it and the monitor
+    // polls.
+    let _ = thread::Builder::new()
+        .name(format!("clipboard-watch-{}", source.name()))
+        .spawn(move || loop {
+            match source.wait() {
+                Ok(()) => {
+                    if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(Ok(())) {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    let _ = tx.blocking_send(Err(err));
+                    break;
+                }
+            }
+        });
+    rx
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+mod x11 {
+    use anyhow::{Context, Result};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+    use x11rb::protocol::xproto::{ConnectionExt as _, CreateWindowAux, WindowClass};
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+
+    use super::ChangeSource;
+
+    /// XFixes selection notifications for `CLIPBOARD`, received on a
+    /// connection of our own with a hidden window.
+    pub struct XFixesSource {
+        conn: RustConnection,
+    }
+
+    impl XFixesSource {
+        pub fn connect() -> Result<Self> {
+            let (conn, screen_num) = x11rb::connect(None).context("无法连接 X11 显示")?;
+            // The extension has to be negotiated before any other request.
+            conn.xfixes_query_version(5, 0)?
+                .reply()
+                .context("X11 服务器不支持 XFixes")?;
+
+            let root = conn.setup().roots[screen_num].root;
+            let window = conn.generate_id()?;
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )?;
+            let clipboard = conn.intern_atom(false, b"CLIPBOARD")?.reply()?.atom;
+            conn.xfixes_select_selection_input(
+                window,
+                clipboard,
+                SelectionEventMask::SET_SELECTION_OWNER
+                    | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                    | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+            )?;
+            conn.flush()?;
+            Ok(Self { conn })
+        }
+    }
+
+    impl ChangeSource for XFixesSource {
+        fn name(&self) -> &'static str {
+            "xfixes"
+        }
+
+        fn wait(&mut self) -> Result<()> {
+            loop {
+                if let Event::XfixesSelectionNotify(_) = self.conn.wait_for_event()? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
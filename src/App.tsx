@@ -5,7 +5,10 @@ import { getCurrentWindow } from "@tauri-apps/api/window";
 import { WebviewWindow } from "@tauri-apps/api/webviewWindow";
 import { useTranslation } from "react-i18next";
 import { MainWindow, type LanPeer } from "./components/MainWindow";
-import SettingsWindow, { type SettingsForm } from "./components/SettingsWindow";
+import SettingsWindow, {
+  type SettingsForm,
+  type SyncDirection,
+} from "./components/SettingsWindow";
 
 // --- Types ---
 
@@ -29,6 +32,7 @@ export default function App() {
   const [statusText, setStatusText] = useState("");
   const [connectionMode, setConnectionMode] = useState("server");
   const [lanPeers, setLanPeers] = useState<LanPeer[]>([]);
+  const [syncDirection, setSyncDirection] = useState<SyncDirection>("both");
 
   useEffect(() => {
     const win = getCurrentWindow();
@@ -45,6 +49,7 @@ export default function App() {
       .then((state) => {
         setPaused(state.paused);
         setConnectionMode(state.config.connection_mode || "server");
+        setSyncDirection(state.config.sync_direction || "both");
         applyLanguage(state.config.language);
         invoke("apply_window_effects", {
           effect: state.config.material_effect,
@@ -71,6 +76,7 @@ export default function App() {
 
     const unlistenConfig = listen<SettingsForm>("config-changed", (event) => {
       setConnectionMode(event.payload.connection_mode || "server");
+      setSyncDirection(event.payload.sync_direction || "both");
       applyLanguage(event.payload.language);
       invoke("apply_window_effects", {
         effect: event.payload.material_effect,
@@ -119,6 +125,17 @@ export default function App() {
     }
   };
 
+  const handleSetSyncDirection = async (direction: SyncDirection) => {
+    try {
+      await invoke("set_sync_direction", { direction });
+    } catch (e) {
+      invoke("frontend_log", { level: "error", message: String(e) }).catch(
+        () => {},
+      );
+      setStatusText(`Error: ${e}`);
+    }
+  };
+
   const handleOpenLogFolder = async () => {
     try {
       await invoke("open_log_folder");
@@ -129,6 +146,30 @@ export default function App() {
     }
   };
 
+  const handleShowPairingPin = () => invoke<string>("lan_begin_pairing");
+
+  const handlePairPeer = async (deviceId: string, pin: string) => {
+    try {
+      await invoke("lan_pair_peer", { deviceId, pin });
+    } catch (e) {
+      invoke("frontend_log", { level: "warn", message: String(e) }).catch(
+        () => {},
+      );
+      throw e;
+    }
+  };
+
+  const handleSendToPeer = async (deviceId: string) => {
+    try {
+      await invoke("send_clipboard_to", { deviceId });
+    } catch (e) {
+      invoke("frontend_log", { level: "warn", message: String(e) }).catch(
+        () => {},
+      );
+      throw e;
+    }
+  };
+
   const handleOpenSettings = async () => {
     try {
       const existing = await WebviewWindow.getByLabel("settings");
@@ -175,7 +216,12 @@ export default function App() {
         statusText={statusText}
         connectionMode={connectionMode}
         lanPeers={lanPeers}
+        syncDirection={syncDirection}
         onTogglePause={handleTogglePause}
+        onSetSyncDirection={handleSetSyncDirection}
+        onShowPairingPin={handleShowPairingPin}
+        onPairPeer={handlePairPeer}
+        onSendToPeer={handleSendToPeer}
         onOpenSettings={handleOpenSettings}
         onOpenLogFolder={handleOpenLogFolder}
       />
@@ -0,0 +1,190 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useTranslation } from "react-i18next";
+import { BaseLabel, cn } from "./ui/Styles";
+import type {
+  BlockedPeer,
+  LanPeer,
+  PeerAccess,
+  TrustedPeer,
+} from "./MainWindow";
+import type { SyncDirection } from "./SettingsWindow";
+
+// --- Types ---
+
+interface DeviceLists {
+  peers: LanPeer[];
+  trusted: TrustedPeer[];
+  blocked: BlockedPeer[];
+}
+
+interface DeviceRow {
+  device_id: string;
+  device_name: string;
+  access: PeerAccess;
+  online: boolean;
+}
+
+interface LanDevicesProps {
+  directions: Record<string, SyncDirection>;
+  onDirectionChange: (deviceId: string, direction: SyncDirection) => void;
+}
+
+// Order the per-device direction button cycles through.
+const directionCycle: SyncDirection[] = [
+  "both",
+  "send_only",
+  "receive_only",
+  "muted",
+];
+
+const actionButton =
+  "shrink-0 px-2 py-0.5 rounded text-[10px] font-medium border transition-colors bg-slate-700/40 border-slate-600/40 text-slate-300 hover:bg-slate-600/50 hover:text-slate-100";
+
+// Merge discovered peers with the persisted lists so offline trusted and
+// blocked devices can still be managed.
+function toRows(lists: DeviceLists): DeviceRow[] {
+  const rows = new Map<string, DeviceRow>();
+  for (const p of lists.trusted) {
+    rows.set(p.device_id, { ...p, access: "trusted", online: false });
+  }
+  for (const p of lists.blocked) {
+    rows.set(p.device_id, { ...p, access: "blocked", online: false });
+  }
+  for (const p of lists.peers) {
+    rows.set(p.device_id, {
+      device_id: p.device_id,
+      device_name: p.device_name,
+      access: p.access,
+      online: true,
+    });
+  }
+  return [...rows.values()].sort((a, b) =>
+    a.device_name.localeCompare(b.device_name),
+  );
+}
+
+// --- Component ---
+
+export default function LanDevices({
+  directions,
+  onDirectionChange,
+}: LanDevicesProps) {
+  const { t } = useTranslation();
+  const [rows, setRows] = useState<DeviceRow[]>([]);
+  const [error, setError] = useState("");
+
+  const refresh = () =>
+    invoke<string>("lan_list_devices")
+      .then((json) => setRows(toRows(JSON.parse(json))))
+      .catch(() => setRows([]));
+
+  useEffect(() => {
+    refresh();
+    const unlistenPeers = listen("lan-peers-changed", refresh);
+    const unlistenTrust = listen("lan-trust-changed", refresh);
+    return () => {
+      unlistenPeers.then((f) => f());
+      unlistenTrust.then((f) => f());
+    };
+  }, []);
+
+  const run = async (command: string, deviceId: string) => {
+    try {
+      await invoke(command, { deviceId });
+      setError("");
+    } catch (e) {
+      setError(String(e));
+    }
+    refresh();
+  };
+
+  return (
+    <div>
+      <BaseLabel>{t("lan.devices_title")}</BaseLabel>
+      {rows.length === 0 ? (
+        <div className="text-xs text-slate-500">{t("lan.no_devices")}</div>
+      ) : (
+        <div className="space-y-1.5">
+          {rows.map((row) => (
+            <div
+              key={row.device_id}
+              className="flex items-center gap-2 px-2.5 py-2 rounded-md bg-slate-800/40 border border-slate-700/30"
+            >
+              <div className="flex flex-col min-w-0 overflow-hidden">
+                <span className="text-xs font-medium text-slate-200 truncate">
+                  {row.device_name || row.device_id}
+                </span>
+                <span
+                  className={cn(
+                    "text-[10px]",
+                    row.access === "trusted"
+                      ? "text-emerald-400/80"
+                      : row.access === "blocked"
+                        ? "text-red-400/80"
+                        : "text-amber-300/80",
+                  )}
+                >
+                  {t(`lan.${row.access}`)}
+                  {!row.online && ` · ${t("lan.offline")}`}
+                </span>
+              </div>
+              <div className="ml-auto flex items-center gap-1.5 shrink-0">
+                {row.access === "pending" && row.online && (
+                  <button
+                    onClick={() => run("lan_approve_peer", row.device_id)}
+                    className={actionButton}
+                  >
+                    {t("lan.approve")}
+                  </button>
+                )}
+                {row.access === "trusted" && (
+                  <button
+                    onClick={() => {
+                      const current = directions[row.device_id] ?? "both";
+                      const next =
+                        directionCycle[
+                          (directionCycle.indexOf(current) + 1) %
+                            directionCycle.length
+                        ];
+                      onDirectionChange(row.device_id, next);
+                    }}
+                    title={t("lan.direction_hint")}
+                    className={actionButton}
+                  >
+                    {t(`lan.direction_${directions[row.device_id] ?? "both"}`)}
+                  </button>
+                )}
+                {row.access === "trusted" && (
+                  <button
+                    onClick={() => run("lan_revoke_peer", row.device_id)}
+                    className={actionButton}
+                  >
+                    {t("lan.revoke")}
+                  </button>
+                )}
+                {row.access === "blocked" ? (
+                  <button
+                    onClick={() => run("lan_revoke_peer", row.device_id)}
+                    className={actionButton}
+                  >
+                    {t("lan.unblock")}
+                  </button>
+                ) : (
+                  <button
+                    onClick={() => run("lan_block_peer", row.device_id)}
+                    className={actionButton}
+                  >
+                    {t("lan.block")}
+                  </button>
+                )}
+              </div>
+            </div>
+          ))}
+        </div>
+      )}
+      {error && <div className="mt-2 text-[10px] text-red-300">{error}</div>}
+    </div>
+  );
+}
@@ -1,13 +1,40 @@
+import { useState } from "react";
 import { useTranslation } from "react-i18next";
-import { BaseButton, cn } from "./ui/Styles";
+import { BaseButton, BaseInput, cn } from "./ui/Styles";
+import type { SyncDirection } from "./SettingsWindow";
 
 // --- Types ---
 
+export type PeerAccess = "trusted" | "pending" | "blocked";
+
+export type LinkState = "idle" | "connecting" | "handshaking" | "connected";
+
 export interface LanPeer {
   device_id: string;
   device_name: string;
   addr: string;
+  tcp_port: number;
+  interface?: string | null;
   last_seen: number;
+  discovered: boolean;
+  state: LinkState;
+  role: "host" | "client" | null;
+  healthy: boolean;
+  rtt_ms: number | null;
+  last_error: string | null;
+  access: PeerAccess;
+}
+
+export interface TrustedPeer {
+  device_id: string;
+  device_name: string;
+  paired_at: number;
+}
+
+export interface BlockedPeer {
+  device_id: string;
+  device_name: string;
+  blocked_at: number;
 }
 
 interface MainWindowProps {
@@ -15,11 +42,19 @@ interface MainWindowProps {
   statusText: string;
   connectionMode: string;
   lanPeers: LanPeer[];
+  syncDirection: SyncDirection;
   onTogglePause: () => void;
+  onSetSyncDirection: (direction: SyncDirection) => void;
+  onShowPairingPin: () => Promise<string>;
+  onPairPeer: (deviceId: string, pin: string) => Promise<void>;
+  onSendToPeer: (deviceId: string) => Promise<void>;
   onOpenSettings: () => void;
   onOpenLogFolder: () => void;
 }
 
+const smallButton =
+  "shrink-0 px-2 py-0.5 rounded text-[10px] font-medium border transition-colors bg-slate-700/40 border-slate-600/40 text-slate-300 hover:bg-slate-600/50 hover:text-slate-100";
+
 // --- Component ---
 
 export function MainWindow({
@@ -27,14 +62,52 @@ export function MainWindow({
   statusText,
   connectionMode,
   lanPeers,
+  syncDirection,
   onTogglePause,
+  onSetSyncDirection,
+  onShowPairingPin,
+  onPairPeer,
+  onSendToPeer,
   onOpenSettings,
   onOpenLogFolder,
 }: MainWindowProps) {
   const { t } = useTranslation();
+  const [pairingPin, setPairingPin] = useState<string | null>(null);
+  const [pairingPeerId, setPairingPeerId] = useState<string | null>(null);
+  const [pinInput, setPinInput] = useState("");
+  const [peerError, setPeerError] = useState("");
 
   const isLan = connectionMode === "lan";
 
+  const handleShowPin = async () => {
+    try {
+      setPairingPin(await onShowPairingPin());
+      setPeerError("");
+    } catch (e) {
+      setPeerError(String(e));
+    }
+  };
+
+  const handleSubmitPin = async (deviceId: string) => {
+    try {
+      await onPairPeer(deviceId, pinInput);
+      setPairingPeerId(null);
+      setPinInput("");
+      setPeerError("");
+    } catch (e) {
+      setPeerError(String(e));
+    }
+  };
+
+  const handleSendToPeer = async (deviceId: string) => {
+    try {
+      await onSendToPeer(deviceId);
+      setPeerError("");
+    } catch (e) {
+      setPeerError(String(e));
+    }
+  };
+
   return (
     <div className="flex flex-col h-full w-full p-6 gap-6 overflow-hidden select-none">
       {/* Status Card */}
@@ -90,11 +163,33 @@ export function MainWindow({
             <span className="text-xs font-semibold text-slate-400 uppercase tracking-wider">
               {t("lan.peers_title")}
             </span>
-            <span className="text-[10px] text-slate-500 font-medium tabular-nums">
-              {lanPeers.length}
-            </span>
+            <div className="flex items-center gap-2">
+              <button onClick={handleShowPin} className={smallButton}>
+                {t("lan.show_pin")}
+              </button>
+              <span className="text-[10px] text-slate-500 font-medium tabular-nums">
+                {lanPeers.length}
+              </span>
+            </div>
           </div>
 
+          {pairingPin && (
+            <div className="flex items-center justify-between px-3.5 py-2 border-b border-slate-700/40 bg-violet-500/10 shrink-0">
+              <span className="text-[10px] text-violet-200/80">
+                {t("lan.pin_hint")}
+              </span>
+              <span className="text-sm font-mono font-bold tracking-[0.3em] text-violet-100">
+                {pairingPin}
+              </span>
+            </div>
+          )}
+
+          {peerError && (
+            <div className="px-3.5 py-1.5 text-[10px] text-red-300 bg-red-500/10 border-b border-red-500/20 shrink-0">
+              {peerError}
+            </div>
+          )}
+
           <div className="flex-1 overflow-y-auto px-3.5 py-2 space-y-1.5">
             {lanPeers.length === 0 ? (
               <div className="flex items-center justify-center py-4">
@@ -110,7 +205,16 @@ export function MainWindow({
                   className="flex items-center gap-2.5 px-2.5 py-2 rounded-md bg-slate-700/20 border border-slate-700/30 hover:bg-slate-700/30 transition-colors"
                 >
                   <div className="relative shrink-0">
-                    <div className="w-2 h-2 rounded-full bg-emerald-500 shadow-sm shadow-emerald-500/40" />
+                    <div
+                      className={cn(
+                        "w-2 h-2 rounded-full shadow-sm",
+                        peer.access === "trusted"
+                          ? "bg-emerald-500 shadow-emerald-500/40"
+                          : peer.access === "blocked"
+                            ? "bg-red-500 shadow-red-500/40"
+                            : "bg-amber-400 shadow-amber-400/40",
+                      )}
+                    />
                   </div>
                   <div className="flex flex-col min-w-0 overflow-hidden">
                     <span className="text-xs font-medium text-slate-200 truncate">
@@ -118,8 +222,62 @@ export function MainWindow({
                     </span>
                     <span className="text-[10px] text-slate-500 truncate tabular-nums">
                       {peer.addr}
+                      {peer.interface ? ` · ${peer.interface}` : ""}
+                      {` · ${t(`lan.state_${peer.state}`)}`}
+                      {peer.state === "connected" && peer.rtt_ms !== null
+                        ? ` · ${Math.round(peer.rtt_ms)} ms`
+                        : ""}
                     </span>
                   </div>
+                  <div className="ml-auto flex items-center gap-1.5 shrink-0">
+                    {peer.access === "trusted" ? (
+                      peer.state === "connected" ? (
+                        <button
+                          onClick={() => handleSendToPeer(peer.device_id)}
+                          title={t("lan.send_clipboard_hint", {
+                            name: peer.device_name,
+                          })}
+                          className={smallButton}
+                        >
+                          {t("lan.send_clipboard")}
+                        </button>
+                      ) : (
+                        <span className="text-[10px] text-emerald-400/80">
+                          {t("lan.paired")}
+                        </span>
+                      )
+                    ) : peer.access === "blocked" ? (
+                      <span className="text-[10px] text-red-400/80">
+                        {t("lan.blocked")}
+                      </span>
+                    ) : pairingPeerId === peer.device_id ? (
+                      <>
+                        <BaseInput
+                          value={pinInput}
+                          onChange={(e) => setPinInput(e.target.value)}
+                          placeholder={t("lan.enter_pin")}
+                          inputMode="numeric"
+                          className="w-20 px-2 py-0.5 text-xs"
+                        />
+                        <button
+                          onClick={() => handleSubmitPin(peer.device_id)}
+                          className={smallButton}
+                        >
+                          {t("lan.pair")}
+                        </button>
+                      </>
+                    ) : (
+                      <button
+                        onClick={() => {
+                          setPairingPeerId(peer.device_id);
+                          setPinInput("");
+                        }}
+                        className={smallButton}
+                      >
+                        {t("lan.pair")}
+                      </button>
+                    )}
+                  </div>
                 </div>
               ))
             )}
@@ -129,6 +287,22 @@ export function MainWindow({
 
       {/* Primary Actions */}
       <div className="flex flex-col gap-3 shrink-0">
+        <div className="grid grid-cols-3 gap-1.5">
+          {(["both", "send_only", "receive_only"] as const).map((direction) => (
+            <button
+              key={direction}
+              onClick={() => onSetSyncDirection(direction)}
+              className={cn(
+                "px-2 py-1.5 rounded-lg text-xs transition-all border",
+                syncDirection === direction
+                  ? "bg-blue-500/20 text-blue-100 border-blue-500/30 font-medium"
+                  : "bg-slate-800/40 text-slate-400 border-transparent hover:bg-slate-800/60 hover:text-slate-200",
+              )}
+            >
+              {t(`sync.${direction}`)}
+            </button>
+          ))}
+        </div>
         <BaseButton
           onClick={onTogglePause}
           className={cn(
@@ -3,9 +3,12 @@ import { invoke } from "@tauri-apps/api/core";
 import { enable, disable, isEnabled } from "@tauri-apps/plugin-autostart";
 import { useTranslation } from "react-i18next";
 import { BaseInput, BaseLabel, cn } from "./ui/Styles";
+import LanDevices from "./LanDevices";
 
 // --- Types ---
 
+export type SyncDirection = "both" | "send_only" | "receive_only" | "muted";
+
 export interface SettingsForm {
   server_url: string;
   token: string;
@@ -18,6 +21,16 @@ export interface SettingsForm {
   connection_mode: string;
   lan_device_name: string;
   close_behavior: string;
+  lan_group_name: string;
+  lan_group_secret: string;
+  lan_static_peers: string[];
+  lan_discovery: string;
+  lan_interfaces: string[];
+  lan_discovery_port: number;
+  lan_tcp_port: number;
+  lan_relay: boolean;
+  sync_direction: SyncDirection;
+  peer_sync_directions: Record<string, SyncDirection>;
 }
 
 interface InitialState {
@@ -38,6 +51,16 @@ export default function SettingsWindow() {
           connection_mode: state.config.connection_mode || "server",
           lan_device_name: state.config.lan_device_name || "",
           close_behavior: state.config.close_behavior || "minimize_to_tray",
+          lan_group_name: state.config.lan_group_name || "",
+          lan_group_secret: state.config.lan_group_secret || "",
+          lan_static_peers: state.config.lan_static_peers || [],
+          lan_discovery: state.config.lan_discovery || "broadcast",
+          lan_interfaces: state.config.lan_interfaces || [],
+          lan_discovery_port: state.config.lan_discovery_port ?? 52741,
+          lan_tcp_port: state.config.lan_tcp_port ?? 52742,
+          lan_relay: state.config.lan_relay ?? false,
+          sync_direction: state.config.sync_direction || "both",
+          peer_sync_directions: state.config.peer_sync_directions || {},
         });
         applyLanguage(state.config.language);
       })
@@ -98,7 +121,10 @@ export default function SettingsWindow() {
     }
   };
 
-  const handleChange = (field: keyof SettingsForm, value: string | number) => {
+  const handleChange = (
+    field: keyof SettingsForm,
+    value: string | number | string[],
+  ) => {
     setFormData((prev) => {
       if (!prev) return null;
       return { ...prev, [field]: value };
@@ -121,6 +147,39 @@ export default function SettingsWindow() {
     }
   };
 
+  const handleDiscoveryChange = (discovery: string) => {
+    if (formData) {
+      const newData = { ...formData, lan_discovery: discovery };
+      setFormData(newData);
+      handleSave(newData);
+    }
+  };
+
+  const toggleRelay = () => {
+    if (formData) {
+      const newData = { ...formData, lan_relay: !formData.lan_relay };
+      setFormData(newData);
+      handleSave(newData);
+    }
+  };
+
+  const handlePeerDirectionChange = (
+    deviceId: string,
+    direction: SyncDirection,
+  ) => {
+    if (formData) {
+      const newData = {
+        ...formData,
+        peer_sync_directions: {
+          ...formData.peer_sync_directions,
+          [deviceId]: direction,
+        },
+      };
+      setFormData(newData);
+      handleSave(newData);
+    }
+  };
+
   const handleAutoDetectHostname = async () => {
     try {
       const hostname = await invoke<string>("get_hostname");
@@ -340,6 +399,172 @@ export default function SettingsWindow() {
                 </button>
               </div>
             </div>
+
+            {/* Sync group */}
+            <div>
+              <BaseLabel>{t("settings.lan_group_name")}</BaseLabel>
+              <BaseInput
+                value={formData.lan_group_name}
+                onChange={(e) =>
+                  handleChange("lan_group_name", e.target.value)
+                }
+                onBlur={handleBlur}
+                placeholder={t("settings.lan_group_name_placeholder")}
+              />
+            </div>
+            <div>
+              <BaseLabel>{t("settings.lan_group_secret")}</BaseLabel>
+              <BaseInput
+                type="password"
+                value={formData.lan_group_secret}
+                onChange={(e) =>
+                  handleChange("lan_group_secret", e.target.value)
+                }
+                onBlur={handleBlur}
+              />
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_group_hint")}
+              </div>
+            </div>
+
+            {/* Discovery backend */}
+            <div>
+              <BaseLabel>{t("settings.lan_discovery")}</BaseLabel>
+              <div className="grid grid-cols-3 gap-2">
+                {(
+                  [
+                    {
+                      key: "broadcast",
+                      label: t("settings.lan_discovery_broadcast"),
+                    },
+                    { key: "mdns", label: t("settings.lan_discovery_mdns") },
+                    { key: "both", label: t("settings.lan_discovery_both") },
+                  ] as const
+                ).map(({ key, label }) => (
+                  <button
+                    key={key}
+                    onClick={() => handleDiscoveryChange(key)}
+                    className={cn(
+                      "px-2 py-2 rounded-lg text-xs transition-all border leading-tight",
+                      formData.lan_discovery === key
+                        ? "bg-blue-500/20 text-blue-100 border-blue-500/30 font-medium"
+                        : "bg-slate-800/40 text-slate-400 border-transparent hover:bg-slate-800/60 hover:text-slate-200",
+                    )}
+                  >
+                    {label}
+                  </button>
+                ))}
+              </div>
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_discovery_hint")}
+              </div>
+            </div>
+
+            {/* Ports */}
+            <div>
+              <div className="grid grid-cols-2 gap-2">
+                <div>
+                  <BaseLabel>{t("settings.lan_discovery_port")}</BaseLabel>
+                  <BaseInput
+                    type="number"
+                    min={1}
+                    max={65535}
+                    value={formData.lan_discovery_port}
+                    onChange={(e) =>
+                      handleChange(
+                        "lan_discovery_port",
+                        parseInt(e.target.value) || 52741,
+                      )
+                    }
+                    onBlur={handleBlur}
+                  />
+                </div>
+                <div>
+                  <BaseLabel>{t("settings.lan_tcp_port")}</BaseLabel>
+                  <BaseInput
+                    type="number"
+                    min={0}
+                    max={65535}
+                    value={formData.lan_tcp_port}
+                    onChange={(e) =>
+                      handleChange("lan_tcp_port", parseInt(e.target.value) || 0)
+                    }
+                    onBlur={handleBlur}
+                  />
+                </div>
+              </div>
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_ports_hint")}
+              </div>
+            </div>
+
+            {/* Interface selection */}
+            <div>
+              <BaseLabel>{t("settings.lan_interfaces")}</BaseLabel>
+              <BaseInput
+                value={formData.lan_interfaces.join(", ")}
+                onChange={(e) =>
+                  handleChange(
+                    "lan_interfaces",
+                    e.target.value.split(",").map((s) => s.trim()),
+                  )
+                }
+                onBlur={handleBlur}
+                placeholder="eth0, 192.168.1.0/24, !docker*"
+              />
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_interfaces_hint")}
+              </div>
+            </div>
+
+            {/* Static peers */}
+            <div>
+              <BaseLabel>{t("settings.lan_static_peers")}</BaseLabel>
+              <BaseInput
+                value={formData.lan_static_peers.join(", ")}
+                onChange={(e) =>
+                  handleChange(
+                    "lan_static_peers",
+                    e.target.value.split(",").map((s) => s.trim()),
+                  )
+                }
+                onBlur={handleBlur}
+                placeholder="192.168.1.20:52742, laptop.local"
+              />
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_static_peers_hint")}
+              </div>
+            </div>
+
+            {/* Mesh relay */}
+            <div>
+              <div className="flex items-center justify-between">
+                <BaseLabel>{t("settings.lan_relay")}</BaseLabel>
+                <button
+                  onClick={toggleRelay}
+                  className={cn(
+                    "w-11 h-6 rounded-full transition-colors relative focus:outline-none",
+                    formData.lan_relay ? "bg-emerald-500" : "bg-slate-700",
+                  )}
+                >
+                  <div
+                    className={cn(
+                      "absolute top-1 left-1 bg-white w-4 h-4 rounded-full transition-transform",
+                      formData.lan_relay ? "translate-x-5" : "translate-x-0",
+                    )}
+                  />
+                </button>
+              </div>
+              <div className="mt-1 text-[10px] text-slate-500">
+                {t("settings.lan_relay_hint")}
+              </div>
+            </div>
+
+            {/* Trusted / pending / blocked devices */}
+            <LanDevices
+              directions={formData.peer_sync_directions}
+              onDirectionChange={handlePeerDirectionChange}
+            />
           </>
         )}
 
@@ -6,6 +6,11 @@ export default {
     ready: "Ready to start",
     error: "Error",
   },
+  sync: {
+    both: "Send & receive",
+    send_only: "Send only",
+    receive_only: "Receive only",
+  },
   action: {
     resume: "Resume Sync",
     pause: "Pause Sync",
@@ -36,6 +41,24 @@ export default {
     lan_device_name: "Device Name",
     lan_device_name_placeholder: "Leave empty to use hostname",
     lan_auto_detect: "Auto Detect",
+    lan_group_name: "Sync Group",
+    lan_group_name_placeholder: "Leave empty for the default group",
+    lan_group_secret: "Group Secret",
+    lan_group_hint: "Only devices with the same group name and secret discover each other.",
+    lan_discovery: "Discovery",
+    lan_discovery_broadcast: "Broadcast",
+    lan_discovery_mdns: "mDNS",
+    lan_discovery_both: "Both",
+    lan_discovery_hint: "Use mDNS where UDP broadcast is filtered or the device has several network adapters.",
+    lan_discovery_port: "Discovery Port (UDP)",
+    lan_tcp_port: "Connection Port (TCP)",
+    lan_ports_hint: "All devices in a group must share the discovery port. TCP port 0 picks a free port.",
+    lan_interfaces: "Network Interfaces",
+    lan_interfaces_hint: "Names or CIDRs (comma-separated) to discover peers on; prefix with ! to exclude. Empty uses all.",
+    lan_static_peers: "Static Peers",
+    lan_static_peers_hint: "Addresses (host:port or [IPv6]:port, comma-separated) to connect to directly when discovery is blocked.",
+    lan_relay: "Relay Between Peers",
+    lan_relay_hint: "Forward clipboard items to peers that cannot reach each other directly.",
     lan_section_title: "LAN Settings",
     server_section_title: "Server Settings",
     close_behavior: "Close Button Behavior",
@@ -49,8 +72,34 @@ export default {
     peer_at: "at",
     status_active: "LAN Mode Active",
     status_searching: "Searching for peers on the local network…",
+    pair: "Pair",
+    paired: "Paired",
+    send_clipboard: "Send",
+    send_clipboard_hint: "Send current clipboard to {{name}} only",
+    show_pin: "Show PIN",
+    pin_hint: "Enter this PIN on the other device",
+    enter_pin: "PIN",
+    blocked: "Blocked",
+    trusted: "Trusted",
+    pending: "Pending",
+    approve: "Approve",
+    revoke: "Revoke",
+    block: "Block",
+    unblock: "Unblock",
+    offline: "offline",
+    direction_both: "Send & receive",
+    direction_send_only: "Send only",
+    direction_receive_only: "Receive only",
+    direction_muted: "Muted",
+    direction_hint: "Which way the clipboard syncs with this device; click to change",
+    state_idle: "idle",
+    state_connecting: "connecting",
+    state_handshaking: "handshaking",
+    state_connected: "connected",
+    devices_title: "Devices",
+    no_devices: "No devices discovered yet",
     security_warning:
-      "LAN mode has no authentication or encryption. Any device on the same network can discover this peer and exchange clipboard data. Only use on trusted networks.",
+      "LAN mode announces this device to everyone on the same network. Peer traffic is encrypted, and clipboard data is only exchanged with devices you have paired or approved.",
   },
   logs: {
     title: "Logs",
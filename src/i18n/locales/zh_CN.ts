@@ -6,6 +6,11 @@ export default {
     ready: "准备启动",
     error: "错误",
   },
+  sync: {
+    both: "双向同步",
+    send_only: "仅发送",
+    receive_only: "仅接收",
+  },
   action: {
     resume: "恢复同步",
     pause: "暂停同步",
@@ -36,6 +41,24 @@ export default {
     lan_device_name: "设备名称",
     lan_device_name_placeholder: "留空则使用主机名",
     lan_auto_detect: "自动获取",
+    lan_group_name: "同步组",
+    lan_group_name_placeholder: "留空则使用默认组",
+    lan_group_secret: "组密钥",
+    lan_group_hint: "只有同步组名称和密钥都相同的设备才能互相发现。",
+    lan_discovery: "发现方式",
+    lan_discovery_broadcast: "广播",
+    lan_discovery_mdns: "mDNS",
+    lan_discovery_both: "两者",
+    lan_discovery_hint: "UDP 广播被过滤或设备有多个网卡时使用 mDNS。",
+    lan_discovery_port: "发现端口 (UDP)",
+    lan_tcp_port: "连接端口 (TCP)",
+    lan_ports_hint: "同一同步组的设备必须使用相同的发现端口。TCP 端口为 0 时自动选择空闲端口。",
+    lan_interfaces: "网络接口",
+    lan_interfaces_hint: "用于发现节点的接口名或网段（逗号分隔），前缀 ! 表示排除。留空则使用全部接口。",
+    lan_static_peers: "静态节点",
+    lan_static_peers_hint: "局域网发现被屏蔽时直接连接的地址（host:port 或 [IPv6]:port，逗号分隔）。",
+    lan_relay: "节点间中继",
+    lan_relay_hint: "将剪贴板内容转发给彼此无法直连的节点。",
     lan_section_title: "局域网设置",
     server_section_title: "服务器设置",
     close_behavior: "关闭按钮行为",
@@ -49,8 +72,34 @@ export default {
     peer_at: "位于",
     status_active: "局域网模式已启动",
     status_searching: "正在局域网中搜索设备…",
+    pair: "配对",
+    paired: "已配对",
+    send_clipboard: "发送",
+    send_clipboard_hint: "仅将当前剪贴板发送到 {{name}}",
+    show_pin: "显示 PIN",
+    pin_hint: "请在另一台设备上输入此 PIN",
+    enter_pin: "PIN",
+    blocked: "已屏蔽",
+    trusted: "已信任",
+    pending: "待确认",
+    approve: "批准",
+    revoke: "撤销",
+    block: "屏蔽",
+    unblock: "解除屏蔽",
+    offline: "离线",
+    direction_both: "双向同步",
+    direction_send_only: "仅发送",
+    direction_receive_only: "仅接收",
+    direction_muted: "静音",
+    direction_hint: "与该设备的同步方向，点击切换",
+    state_idle: "未连接",
+    state_connecting: "连接中",
+    state_handshaking: "握手中",
+    state_connected: "已连接",
+    devices_title: "设备",
+    no_devices: "尚未发现设备",
     security_warning:
-      "局域网模式没有身份验证或加密。同一网络上的任何设备都可以发现此节点并交换剪贴板数据。请仅在受信任的网络中使用。",
+      "局域网模式会向同一网络中的所有设备广播本机。设备间通信已加密，剪贴板数据仅与已配对或已批准的设备交换。",
   },
   logs: {
     title: "日志",